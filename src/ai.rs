@@ -1,8 +1,9 @@
 use bevy::prelude::*;
 
 use crate::{
+    boost::{Boost, BOOST_MAX_CHARGE},
     combine::Combine,
-    events::{SpeedControlEvent, SteerControlEvent},
+    events::{BoostControlEvent, SpeedControlEvent, SteerControlEvent},
 };
 
 #[derive(Component)]
@@ -24,12 +25,39 @@ impl Default for AiState {
     }
 }
 
+/// Whether the AI drives its combines, switched from the console.
+#[derive(Resource)]
+pub struct AiControl {
+    pub enabled: bool,
+}
+
+impl Default for AiControl {
+    fn default() -> Self {
+        AiControl { enabled: true }
+    }
+}
+
 pub fn combine_ai_system(
-    mut combine_ai_query: Query<(&mut AiState, &Combine)>,
+    ai_control: Res<AiControl>,
+    mut combine_ai_query: Query<(&mut AiState, &Combine, &Boost)>,
     mut speed_control_events: ResMut<Events<SpeedControlEvent>>,
     mut steer_control_events: ResMut<Events<SteerControlEvent>>,
+    mut boost_control_events: ResMut<Events<BoostControlEvent>>,
 ) {
-    for (mut ai, combine) in combine_ai_query.iter_mut() {
+    for (mut ai, combine, boost) in combine_ai_query.iter_mut() {
+        // leave the combines coasting while the AI is switched off.
+        if !ai_control.enabled {
+            speed_control_events.send(SpeedControlEvent {
+                combine_id: combine.combine_id,
+                action: crate::events::SpeedControlAction::NoPower,
+            });
+            steer_control_events.send(SteerControlEvent {
+                combine_id: combine.combine_id,
+                action: crate::events::SteerControlAction::NoSteer,
+            });
+            continue;
+        }
+
         if combine.velocity < 0.1 {
             ai.frames_at_zero_velocity += 1;
         } else {
@@ -54,6 +82,13 @@ pub fn combine_ai_system(
                 combine_id: combine.combine_id,
                 action: crate::events::SpeedControlAction::Forward,
             });
+
+            // save boost up and use it all in one go.
+            if boost.active || boost.charge >= BOOST_MAX_CHARGE {
+                boost_control_events.send(BoostControlEvent {
+                    combine_id: combine.combine_id,
+                });
+            }
         }
     }
 }
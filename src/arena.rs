@@ -1,31 +1,54 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::Indices,
+        render_resource::{AddressMode, PrimitiveTopology},
+        texture::ImageSampler,
+    },
+};
 use bevy_rapier3d::prelude::*;
 
+use crate::{
+    events::SoundSampleKind,
+    layout::{ArenaLayout, RampLayout},
+    sounds::SoundCollider,
+};
+
 pub const PLANE_SIZE: f32 = 200.0;
 pub const RAMP_HEIGHT: f32 = 5.0;
+pub const GROUND_Y_POSITION: f32 = -2.0;
+pub const PEN_FENCE_HEIGHT: f32 = 10.0;
+
+const TILE_SIZE: f32 = 20.0;
+
+/// Textures that should wrap rather than stretch when their UVs go beyond 0 to 1.
+#[derive(Resource)]
+pub struct RepeatingTextures {
+    pub handles: Vec<Handle<Image>>,
+}
 
 pub fn setup_arena(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
+    arena_layout: Res<ArenaLayout>,
 ) {
-    const TILE_SIZE: f32 = 20.0;
     const FENCE_HEIGHT: f32 = 20.0;
-    let ground_plane_handle = meshes.add(Mesh::from(shape::Plane { size: TILE_SIZE }));
-    let fence_handle = meshes.add(Mesh::from(shape::Quad {
-        size: Vec2 {
-            x: TILE_SIZE,
-            y: TILE_SIZE * 4.0,
-        },
-        flip: false,
-    }));
 
     let ground_texture = asset_server.load("ground_texture.png");
     let ground_normal_texture: Handle<Image> = asset_server.load("ground_normal_texture.png");
 
     let fence_texture = asset_server.load("fence.png");
 
+    commands.insert_resource(RepeatingTextures {
+        handles: vec![
+            ground_texture.clone(),
+            ground_normal_texture.clone(),
+            fence_texture.clone(),
+        ],
+    });
+
     let ground_material_handle = materials.add(StandardMaterial {
         base_color_texture: Some(ground_texture),
         normal_map_texture: Some(ground_normal_texture),
@@ -34,12 +57,12 @@ pub fn setup_arena(
     });
 
     let fence_material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(fence_texture),
+        base_color_texture: Some(fence_texture.clone()),
         perceptual_roughness: 1.0,
         ..default()
     });
 
-    let ground_y_position = -2.0;
+    let ground_y_position = GROUND_Y_POSITION;
 
     /* Create the ground. */
     commands
@@ -58,403 +81,340 @@ pub fn setup_arena(
             FENCE_HEIGHT * 10.0,
             0.0,
         )))
-        .insert(Collider::cuboid(0.1, FENCE_HEIGHT * 10.0, PLANE_SIZE));
+        .insert(Collider::cuboid(0.1, FENCE_HEIGHT * 10.0, PLANE_SIZE))
+        .insert(SoundCollider {
+            sound_sample: SoundSampleKind::Fence,
+        });
     commands
         .spawn(TransformBundle::from(Transform::from_xyz(
             -PLANE_SIZE,
             FENCE_HEIGHT * 10.0,
             0.0,
         )))
-        .insert(Collider::cuboid(0.1, FENCE_HEIGHT * 10.0, PLANE_SIZE));
+        .insert(Collider::cuboid(0.1, FENCE_HEIGHT * 10.0, PLANE_SIZE))
+        .insert(SoundCollider {
+            sound_sample: SoundSampleKind::Fence,
+        });
     commands
         .spawn(TransformBundle::from(Transform::from_xyz(
             0.0,
             FENCE_HEIGHT * 10.0,
             PLANE_SIZE,
         )))
-        .insert(Collider::cuboid(PLANE_SIZE, FENCE_HEIGHT * 10.0, 0.1));
+        .insert(Collider::cuboid(PLANE_SIZE, FENCE_HEIGHT * 10.0, 0.1))
+        .insert(SoundCollider {
+            sound_sample: SoundSampleKind::Fence,
+        });
     commands
         .spawn(TransformBundle::from(Transform::from_xyz(
             0.0,
             FENCE_HEIGHT * 10.0,
             -PLANE_SIZE,
         )))
-        .insert(Collider::cuboid(PLANE_SIZE, FENCE_HEIGHT * 10.0, 0.1));
-
-    let tile_count = (PLANE_SIZE / TILE_SIZE) as i32;
-    for i in -tile_count..=tile_count {
-        for j in -tile_count..=tile_count {
-            commands.spawn(PbrBundle {
-                mesh: ground_plane_handle.clone(),
-                material: ground_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    (i as f32 * TILE_SIZE) as f32,
-                    ground_y_position,
-                    (j as f32 * TILE_SIZE) as f32,
-                )),
-                ..default()
-            });
-        }
-
-        // setup arena fences
-        commands.spawn(PbrBundle {
-            mesh: fence_handle.clone(),
-            material: fence_material_handle.clone(),
-            transform: Transform::from_translation(Vec3::new(
-                (i as f32 * TILE_SIZE) as f32,
-                ground_y_position + (TILE_SIZE / 2.0),
-                -PLANE_SIZE,
-            )),
-            ..default()
+        .insert(Collider::cuboid(PLANE_SIZE, FENCE_HEIGHT * 10.0, 0.1))
+        .insert(SoundCollider {
+            sound_sample: SoundSampleKind::Fence,
         });
-        commands.spawn(PbrBundle {
-            mesh: fence_handle.clone(),
-            material: fence_material_handle.clone(),
-            transform: Transform::from_translation(Vec3::new(
-                -(i as f32 * TILE_SIZE) as f32,
-                ground_y_position + (TILE_SIZE / 2.0),
-                PLANE_SIZE,
-            ))
+
+    // the ground and each fence side are a single mesh, with the texture repeated once per tile.
+    // this cuts hundreds of entities, but the frame time hasn't been compared with the old
+    // per-tile floor yet. the F3 overlay shows the average frame time to measure it with.
+    let tile_count = ((2.0 * PLANE_SIZE) / TILE_SIZE) as i32 + 1;
+    let tiled_length = TILE_SIZE * tile_count as f32;
+
+    let ground_handle = meshes.add(tiled_plane(tiled_length, tile_count as f32));
+    let fence_handle = meshes.add(tiled_quad(
+        Vec2::new(tiled_length, TILE_SIZE * 4.0),
+        Vec2::new(tile_count as f32, 1.0),
+    ));
+
+    commands.spawn(PbrBundle {
+        mesh: ground_handle,
+        material: ground_material_handle,
+        transform: Transform::from_xyz(0.0, ground_y_position, 0.0),
+        ..default()
+    });
+
+    // setup arena fences
+    let fence_y_position = ground_y_position + (TILE_SIZE / 2.0);
+    let fence_transforms = [
+        Transform::from_xyz(0.0, fence_y_position, -PLANE_SIZE),
+        Transform::from_xyz(0.0, fence_y_position, PLANE_SIZE)
             .with_rotation(Quat::from_rotation_y(180.0_f32.to_radians())),
-            ..default()
-        });
-        commands.spawn(PbrBundle {
-            mesh: fence_handle.clone(),
-            material: fence_material_handle.clone(),
-            transform: Transform::from_translation(Vec3::new(
-                -PLANE_SIZE,
-                ground_y_position + (TILE_SIZE / 2.0),
-                (i as f32 * TILE_SIZE) as f32,
-            ))
+        Transform::from_xyz(-PLANE_SIZE, fence_y_position, 0.0)
             .with_rotation(Quat::from_rotation_y(90.0_f32.to_radians())),
-            ..default()
-        });
+        Transform::from_xyz(PLANE_SIZE, fence_y_position, 0.0)
+            .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
+    ];
+    for transform in fence_transforms {
         commands.spawn(PbrBundle {
             mesh: fence_handle.clone(),
             material: fence_material_handle.clone(),
-            transform: Transform::from_translation(Vec3::new(
-                PLANE_SIZE,
-                ground_y_position + (TILE_SIZE / 2.0),
-                -(i as f32 * TILE_SIZE) as f32,
-            ))
-            .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
+            transform,
             ..default()
         });
     }
 
     // setup ramps
 
-    let half_ramp_height = RAMP_HEIGHT / 2.0;
-    let ramp_length = PLANE_SIZE / 2.0;
-    let slope_rotation = (RAMP_HEIGHT / ramp_length).atan();
-
-    let ramp_plane_handle = meshes.add(Mesh::from(shape::Plane {
-        size: PLANE_SIZE / 2.0,
-    }));
     let ramp_material_handle = materials.add(StandardMaterial {
         base_color: Color::rgb(0.66, 0.66, 0.68),
         perceptual_roughness: 0.1,
         ..default()
     });
 
-    // ramp 1
+    for ramp in arena_layout.ramps.iter() {
+        let ramp_plane_handle = meshes.add(Mesh::from(shape::Plane { size: ramp.size }));
+        commands = spawn_ramp(
+            commands,
+            ramp,
+            ramp_plane_handle,
+            ramp_material_handle.clone(),
+        );
+    }
 
-    // plinth
-    commands
-        .spawn(SpatialBundle::from(Transform::from_xyz(
-            PLANE_SIZE - (PLANE_SIZE / 4.0),
-            ground_y_position + half_ramp_height,
-            PLANE_SIZE - (PLANE_SIZE / 4.0),
-        )))
-        .insert(Collider::cuboid(
-            PLANE_SIZE / 4.0,
-            half_ramp_height,
-            PLANE_SIZE / 4.0,
-        ))
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(0.0, half_ramp_height, 0.0)),
-                ..default()
-            });
-        });
+    // setup cow pens
 
-    // slope
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-            )
-            .with_rotation(Quat::from_rotation_y(180.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
+    let pen_material_handle = materials.add(StandardMaterial {
+        base_color_texture: Some(fence_texture),
+        perceptual_roughness: 1.0,
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+
+    for pen in arena_layout.cow_pens.iter() {
+        let pen_fence_handle = meshes.add(tiled_quad(
+            Vec2::new(pen.half_size * 2.0, PEN_FENCE_HEIGHT),
+            Vec2::new(((pen.half_size * 2.0) / TILE_SIZE).round().max(1.0), 1.0),
         ));
+        commands = spawn_pen(
+            commands,
+            pen.transform(),
+            pen.half_size,
+            pen_fence_handle,
+            pen_material_handle.clone(),
+        );
+    }
 
-    commands
-        .spawn(SpatialBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-            )
-            .with_rotation(Quat::from_rotation_y(180.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ))
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    -0.25 * PLANE_SIZE,
-                    half_ramp_height,
-                    0.25 * PLANE_SIZE,
-                ))
-                .with_rotation(Quat::from_rotation_x(slope_rotation)),
+    // directional 'sun' light
+    const HALF_SIZE: f32 = PLANE_SIZE;
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            // Configure the projection to better fit the scene
+            shadow_projection: OrthographicProjection {
+                left: -HALF_SIZE,
+                right: HALF_SIZE,
+                bottom: -HALF_SIZE,
+                top: HALF_SIZE,
+                near: -10.0 * HALF_SIZE,
+                far: 10.0 * HALF_SIZE,
                 ..default()
-            });
-        });
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-            )
-            .with_rotation(Quat::from_rotation_y(180.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(-PLANE_SIZE / 2.0, 0.0, ramp_length),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
+            },
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform {
+            translation: Vec3::new(0.0, 2.0, 0.0),
+            rotation: Quat::from_rotation_x(-std::f32::consts::FRAC_PI_4),
+            ..default()
+        },
+        ..default()
+    });
+}
 
-    // slope
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-            )
-            .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
+/// Spawns a raised plinth with slopes leading down from two of its sides.
+fn spawn_ramp<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    ramp: &RampLayout,
+    plane_handle: Handle<Mesh>,
+    material_handle: Handle<StandardMaterial>,
+) -> Commands<'w, 's> {
+    let ramp_transform = ramp.transform();
+    let half_size = ramp.size / 2.0;
+    let half_height = ramp.height / 2.0;
+    let slope_rotation = (ramp.height / ramp.size).atan();
+
+    // plinth
     commands
         .spawn(SpatialBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE,
-            )
-            .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
+            ramp_transform * Transform::from_xyz(0.0, half_height, 0.0),
         ))
+        .insert(Collider::cuboid(half_size, half_height, half_size))
         .with_children(|parent| {
             parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    -0.25 * PLANE_SIZE,
-                    half_ramp_height,
-                    0.25 * PLANE_SIZE,
-                ))
-                .with_rotation(Quat::from_rotation_x(slope_rotation)),
+                mesh: plane_handle.clone(),
+                material: material_handle.clone(),
+                transform: Transform::from_translation(Vec3::new(0.0, half_height, 0.0)),
                 ..default()
             });
         });
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                PLANE_SIZE - (PLANE_SIZE / 2.0),
-                ground_y_position,
-                PLANE_SIZE,
-            )
-            .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(-PLANE_SIZE / 2.0, 0.0, ramp_length),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
 
-    // ramp 2
-
-    // plinth
+    // slope running down the -Z side
+    let z_slope = Collider::convex_hull(&[
+        Vec3::new(-half_size, 0.0, -half_size),
+        Vec3::new(half_size, 0.0, -half_size),
+        Vec3::new(-half_size, ramp.height, -half_size),
+        Vec3::new(half_size, ramp.height, -half_size),
+        Vec3::new(-half_size, 0.0, -half_size - ramp.size),
+        Vec3::new(half_size, 0.0, -half_size - ramp.size),
+    ])
+    .expect("ramp slope should be convex");
     commands
-        .spawn(SpatialBundle::from(Transform::from_xyz(
-            -(PLANE_SIZE - (PLANE_SIZE / 4.0)),
-            ground_y_position + half_ramp_height,
-            -(PLANE_SIZE - (PLANE_SIZE / 4.0)),
-        )))
-        .insert(Collider::cuboid(
-            PLANE_SIZE / 4.0,
-            half_ramp_height,
-            PLANE_SIZE / 4.0,
-        ))
+        .spawn(SpatialBundle::from(ramp_transform))
+        .insert(z_slope)
         .with_children(|parent| {
             parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(0.0, half_ramp_height, 0.0)),
+                mesh: plane_handle.clone(),
+                material: material_handle.clone(),
+                transform: Transform::from_xyz(0.0, half_height, -ramp.size)
+                    .with_rotation(Quat::from_rotation_x(-slope_rotation)),
                 ..default()
             });
         });
 
-    // slope
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-            )
-            .with_rotation(Quat::from_rotation_y(90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
-
+    // slope running down the -X side
+    let x_slope = Collider::convex_hull(&[
+        Vec3::new(-half_size, 0.0, -half_size),
+        Vec3::new(-half_size, 0.0, half_size),
+        Vec3::new(-half_size, ramp.height, -half_size),
+        Vec3::new(-half_size, ramp.height, half_size),
+        Vec3::new(-half_size - ramp.size, 0.0, -half_size),
+        Vec3::new(-half_size - ramp.size, 0.0, half_size),
+    ])
+    .expect("ramp slope should be convex");
     commands
-        .spawn(SpatialBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -PLANE_SIZE,
-            )
-            .with_rotation(Quat::from_rotation_y(90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ))
+        .spawn(SpatialBundle::from(ramp_transform))
+        .insert(x_slope)
         .with_children(|parent| {
             parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    -0.25 * PLANE_SIZE,
-                    half_ramp_height,
-                    0.25 * PLANE_SIZE,
-                ))
-                .with_rotation(Quat::from_rotation_x(slope_rotation)),
+                mesh: plane_handle,
+                material: material_handle,
+                transform: Transform::from_xyz(-ramp.size, half_height, 0.0)
+                    .with_rotation(Quat::from_rotation_z(slope_rotation)),
                 ..default()
             });
         });
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -PLANE_SIZE,
-            )
-            .with_rotation(Quat::from_rotation_y(90.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(-PLANE_SIZE / 2.0, 0.0, ramp_length),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
 
-    // slope
-    commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-            )
-            .with_rotation(Quat::from_rotation_y(0.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
     commands
-        .spawn(SpatialBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-            )
-            .with_rotation(Quat::from_rotation_y(0.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(0.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ))
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh: ramp_plane_handle.clone(),
-                material: ramp_material_handle.clone(),
-                transform: Transform::from_translation(Vec3::new(
-                    -0.25 * PLANE_SIZE,
-                    half_ramp_height,
-                    0.25 * PLANE_SIZE,
-                ))
-                .with_rotation(Quat::from_rotation_x(slope_rotation)),
-                ..default()
+}
+
+/// Spawns a square fence with its local +Z side left open.
+pub fn spawn_pen<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    pen_transform: Transform,
+    half_size: f32,
+    fence_handle: Handle<Mesh>,
+    material_handle: Handle<StandardMaterial>,
+) -> Commands<'w, 's> {
+    let half_height = PEN_FENCE_HEIGHT / 2.0;
+
+    let walls = [
+        // back
+        (
+            Transform::from_xyz(0.0, half_height, -half_size),
+            Collider::cuboid(half_size, half_height, 0.5),
+        ),
+        // left
+        (
+            Transform::from_xyz(-half_size, half_height, 0.0)
+                .with_rotation(Quat::from_rotation_y(90.0_f32.to_radians())),
+            Collider::cuboid(half_size, half_height, 0.5),
+        ),
+        // right
+        (
+            Transform::from_xyz(half_size, half_height, 0.0)
+                .with_rotation(Quat::from_rotation_y(-90.0_f32.to_radians())),
+            Collider::cuboid(half_size, half_height, 0.5),
+        ),
+    ];
+
+    for (wall_transform, collider) in walls {
+        commands
+            .spawn(SpatialBundle::from(pen_transform * wall_transform))
+            .insert(collider)
+            .insert(SoundCollider {
+                sound_sample: SoundSampleKind::Fence,
+            })
+            .with_children(|parent| {
+                parent.spawn(PbrBundle {
+                    mesh: fence_handle.clone(),
+                    material: material_handle.clone(),
+                    ..default()
+                });
             });
-        });
+    }
+
     commands
-        .spawn(TransformBundle::from(
-            Transform::from_xyz(
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-                ground_y_position,
-                -(PLANE_SIZE - (PLANE_SIZE / 2.0)),
-            )
-            .with_rotation(Quat::from_rotation_y(0.0_f32.to_radians())),
-        ))
-        .insert(Collider::triangle(
-            Vec3::new(-PLANE_SIZE / 2.0, 0.0, ramp_length),
-            Vec3::new(-PLANE_SIZE / 2.0, RAMP_HEIGHT, 0.0),
-            Vec3::new(0.0, 0.0, ramp_length),
-        ));
+}
 
-    // directional 'sun' light
-    const HALF_SIZE: f32 = PLANE_SIZE;
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            // Configure the projection to better fit the scene
-            shadow_projection: OrthographicProjection {
-                left: -HALF_SIZE,
-                right: HALF_SIZE,
-                bottom: -HALF_SIZE,
-                top: HALF_SIZE,
-                near: -10.0 * HALF_SIZE,
-                far: 10.0 * HALF_SIZE,
-                ..default()
-            },
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform {
-            translation: Vec3::new(0.0, 2.0, 0.0),
-            rotation: Quat::from_rotation_x(-std::f32::consts::FRAC_PI_4),
-            ..default()
-        },
-        ..default()
-    });
+/// Creates a horizontal square facing up, with UVs running from 0 to `repeat`.
+fn tiled_plane(size: f32, repeat: f32) -> Mesh {
+    let extent = size / 2.0;
+
+    let vertices = [
+        ([extent, 0.0, -extent], [repeat, repeat]),
+        ([extent, 0.0, extent], [repeat, 0.0]),
+        ([-extent, 0.0, extent], [0.0, 0.0]),
+        ([-extent, 0.0, -extent], [0.0, repeat]),
+    ];
+
+    create_tiled_mesh(&vertices, [0.0, 1.0, 0.0])
+}
+
+/// Creates a vertical rectangle facing +Z, with UVs running from 0 to `repeat`.
+pub fn tiled_quad(size: Vec2, repeat: Vec2) -> Mesh {
+    let extent_x = size.x / 2.0;
+    let extent_y = size.y / 2.0;
+
+    let vertices = [
+        ([-extent_x, -extent_y, 0.0], [0.0, repeat.y]),
+        ([-extent_x, extent_y, 0.0], [0.0, 0.0]),
+        ([extent_x, extent_y, 0.0], [repeat.x, 0.0]),
+        ([extent_x, -extent_y, 0.0], [repeat.x, repeat.y]),
+    ];
+
+    create_tiled_mesh(&vertices, [0.0, 0.0, 1.0])
+}
+
+fn create_tiled_mesh(vertices: &[([f32; 3], [f32; 2]); 4], normal: [f32; 3]) -> Mesh {
+    let positions: Vec<_> = vertices.iter().map(|(p, _)| *p).collect();
+    let normals: Vec<_> = vertices.iter().map(|_| normal).collect();
+    let uvs: Vec<_> = vertices.iter().map(|(_, uv)| *uv).collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(vec![0, 2, 1, 0, 3, 2])));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+/// Switches the arena textures to a repeating sampler once they have loaded.
+pub fn repeating_texture_system(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    repeating_textures: Option<Res<RepeatingTextures>>,
+) {
+    let repeating_textures = match repeating_textures {
+        Some(repeating_textures) => repeating_textures,
+        None => return,
+    };
+
+    for event in image_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if !repeating_textures.handles.contains(handle) {
+            continue;
+        }
+        if let Some(image) = images.get_mut(handle) {
+            let mut descriptor = ImageSampler::linear_descriptor();
+            descriptor.address_mode_u = AddressMode::Repeat;
+            descriptor.address_mode_v = AddressMode::Repeat;
+            descriptor.address_mode_w = AddressMode::Repeat;
+            image.sampler_descriptor = ImageSampler::Descriptor(descriptor);
+        }
+    }
 }
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    combine::{Combine, PLAYER_COMBINE_ID},
+    events::{BoostControlEvent, SoundSampleEvent, SoundSampleKind},
+    particles::{spawn_particle_burst, ParticleAssets, ParticleBurst},
+};
+
+pub const BOOST_MAX_CHARGE: f32 = 100.0;
+/// Charge needed before a boost can start, so tapping the button on an empty meter does nothing.
+const BOOST_MIN_CHARGE: f32 = 20.0;
+const BOOST_DRAIN_PER_SECOND: f32 = 40.0;
+const BOOST_RECHARGE_PER_SECOND: f32 = 8.0;
+
+/// Multiplier on the wheel target velocity while boosting.
+pub const BOOST_SPEED_FACTOR: f32 = 1.6;
+/// Multiplier on how hard the wheel motors chase their target velocity while boosting.
+pub const BOOST_MOTOR_FACTOR: f32 = 2.5;
+
+const EXHAUST_INTERVAL_SECONDS: f32 = 0.05;
+/// Exhaust position relative to the combine body, above and behind the cab.
+const EXHAUST_OFFSET: Vec3 = Vec3::new(0.0, 5.0, 8.0);
+
+#[derive(Component)]
+pub struct Boost {
+    pub charge: f32,
+    pub active: bool,
+    exhaust_timer: Timer,
+}
+
+impl Default for Boost {
+    fn default() -> Self {
+        Boost {
+            charge: BOOST_MAX_CHARGE,
+            active: false,
+            exhaust_timer: Timer::from_seconds(EXHAUST_INTERVAL_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Boost {
+    pub fn refill(&mut self) {
+        self.charge = BOOST_MAX_CHARGE;
+    }
+
+    /// Starts, keeps or stops boosting and drains or recharges the meter to match.
+    fn update(&mut self, requested: bool, delta_seconds: f32) {
+        self.active = boost_active(self.charge, self.active, requested);
+        self.charge = next_charge(self.charge, self.active, delta_seconds);
+    }
+}
+
+/// Whether a combine boosts this frame. A boost can only start with at least
+/// [`BOOST_MIN_CHARGE`], but once going it carries on until the meter is empty.
+fn boost_active(charge: f32, was_active: bool, requested: bool) -> bool {
+    requested && charge > 0.0 && (was_active || charge >= BOOST_MIN_CHARGE)
+}
+
+/// Gets the charge after a frame of boosting or recharging, kept within the meter.
+fn next_charge(charge: f32, active: bool, delta_seconds: f32) -> f32 {
+    if active {
+        (charge - (BOOST_DRAIN_PER_SECOND * delta_seconds)).max(0.0)
+    } else {
+        (charge + (BOOST_RECHARGE_PER_SECOND * delta_seconds)).min(BOOST_MAX_CHARGE)
+    }
+}
+
+/// Drains the meter of combines asking to boost and recharges the rest.
+pub fn boost_control_events(
+    time: Res<Time>,
+    mut boost_control_events: EventReader<BoostControlEvent>,
+    mut sound_sample_events: EventWriter<SoundSampleEvent>,
+    mut combine_query: Query<(Entity, &Combine, &Transform, &mut Boost)>,
+) {
+    let requested: HashSet<i32> = boost_control_events
+        .iter()
+        .map(|event| event.combine_id)
+        .collect();
+    let delta = time.delta_seconds();
+
+    for (entity, combine, transform, mut boost) in combine_query.iter_mut() {
+        let was_active = boost.active;
+        boost.update(requested.contains(&combine.combine_id), delta);
+
+        if boost.active && !was_active && combine.combine_id == PLAYER_COMBINE_ID {
+            sound_sample_events.send(SoundSampleEvent {
+                sample: SoundSampleKind::EngineSurge,
+                position: transform.translation,
+                source: entity,
+                force: None,
+            });
+        }
+    }
+}
+
+/// Puffs smoke out of the exhaust of boosting combines.
+pub fn boost_exhaust_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    particle_assets: Res<ParticleAssets>,
+    mut combine_query: Query<(&Transform, &mut Boost)>,
+) {
+    for (transform, mut boost) in combine_query.iter_mut() {
+        if !boost.active {
+            continue;
+        }
+        boost.exhaust_timer.tick(time.delta());
+        if !boost.exhaust_timer.just_finished() {
+            continue;
+        }
+
+        commands = spawn_particle_burst(
+            commands,
+            particle_assets.mesh.clone(),
+            ParticleBurst {
+                position: transform.transform_point(EXHAUST_OFFSET),
+                count: 2,
+                direction: transform.back() + Vec3::Y,
+                speed: 6.0,
+                spread: 0.3,
+                lifetime: 1.0,
+                size: 0.8,
+                gravity_scale: -0.5,
+                material: particle_assets.exhaust_material.clone(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boost::{
+        boost_active, next_charge, Boost, BOOST_MAX_CHARGE, BOOST_MIN_CHARGE,
+        BOOST_RECHARGE_PER_SECOND,
+    };
+
+    #[test]
+    fn boost_needs_min_charge_to_start_test() {
+        assert!(boost_active(BOOST_MIN_CHARGE, false, true));
+        assert!(!boost_active(BOOST_MIN_CHARGE - 1.0, false, true));
+        assert!(!boost_active(BOOST_MAX_CHARGE, false, false));
+    }
+
+    #[test]
+    fn boost_keeps_going_until_empty_test() {
+        assert!(boost_active(1.0, true, true));
+        assert!(!boost_active(0.0, true, true));
+        assert!(!boost_active(BOOST_MAX_CHARGE, true, false));
+    }
+
+    #[test]
+    fn charge_stays_on_meter_test() {
+        assert_eq!(0.0, next_charge(1.0, true, 1.0));
+        assert_eq!(
+            BOOST_MAX_CHARGE,
+            next_charge(BOOST_MAX_CHARGE - 1.0, false, 1.0)
+        );
+        assert_eq!(
+            50.0 + BOOST_RECHARGE_PER_SECOND,
+            next_charge(50.0, false, 1.0)
+        );
+    }
+
+    #[test]
+    fn boost_runs_dry_test() {
+        let mut boost = Boost::default();
+        boost.update(true, 1.0);
+        assert!(boost.active);
+        assert!(boost.charge < BOOST_MAX_CHARGE);
+
+        // holding the button drains the meter and then stops the boost.
+        for _ in 0..3 {
+            boost.update(true, 1.0);
+        }
+        assert!(!boost.active);
+
+        // it can't start again until it has recharged enough.
+        boost.update(false, 1.0);
+        boost.update(true, 0.0);
+        assert!(!boost.active);
+    }
+}
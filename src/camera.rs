@@ -1,10 +1,46 @@
 // shamelessly taken from the Bevy cheatbook
 // https://bevy-cheatbook.github.io/cookbook/pan-orbit-camera.html
 
+use std::f32::consts::PI;
+
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
 };
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    combine::{Combine, Wheel},
+    console::Console,
+    obstacle::{Cow, HayBale},
+};
+
+/// Where the chase camera sits relative to the point it looks at, before turning to face the
+/// same way as the combine.
+const CHASE_OFFSET: Vec3 = Vec3::new(0.0, 17.0, 40.0);
+/// Height above the combine the chase camera looks at.
+const CHASE_LOOK_HEIGHT: f32 = 3.0;
+/// How stiffly the chase camera follows the combine. Higher catches up quicker.
+const CHASE_FOLLOW_FREQUENCY: f32 = 5.0;
+/// How quickly the chase camera turns to follow the combine, per second.
+const CHASE_YAW_RESPONSE: f32 = 3.0;
+/// How quickly the chase camera moves back out once it is no longer blocked, per second.
+const CHASE_DISTANCE_RESPONSE: f32 = 2.0;
+/// Gap kept between the chase camera and anything blocking its view.
+const CHASE_CLEARANCE: f32 = 1.5;
+const CHASE_MIN_DISTANCE: f32 = 8.0;
+
+/// How quickly the free camera's focus catches up with the entity it follows, per second.
+const FOLLOW_RESPONSE: f32 = 4.0;
+/// Orbit radius the auto-director frames its shots with.
+const DIRECTOR_RADIUS: f32 = 70.0;
+/// Shortest time the auto-director holds a shot before cutting to a crash.
+const DIRECTOR_MIN_SHOT_SECONDS: f32 = 1.5;
+/// Time the auto-director holds a shot with nothing happening before cutting to the fastest
+/// combine.
+const DIRECTOR_SHOT_SECONDS: f32 = 6.0;
+/// Hits at least this hard are worth cutting to.
+const DIRECTOR_IMPACT_FORCE: f32 = 40000.0;
 
 #[derive(Component)]
 pub struct FreeCamera;
@@ -14,6 +50,39 @@ pub struct CombineCamera {
     pub combine_id: i32,
 }
 
+/// Follows a combine from behind, smoothly and without tipping over with it.
+#[derive(Component)]
+pub struct ChaseCamera {
+    /// Point the camera looks at, lagging behind the combine.
+    focus: Vec3,
+    focus_velocity: Vec3,
+    /// Direction the camera faces around the Y axis, lagging behind the combine's heading.
+    yaw: f32,
+    /// Distance from the focus, shorter than usual while something is in the way.
+    distance: f32,
+}
+
+impl ChaseCamera {
+    pub fn new(combine_transform: &Transform) -> Self {
+        ChaseCamera {
+            focus: combine_transform.translation + (Vec3::Y * CHASE_LOOK_HEIGHT),
+            focus_velocity: Vec3::ZERO,
+            yaw: heading_yaw(combine_transform),
+            distance: CHASE_OFFSET.length(),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform_facing(self.yaw)
+    }
+
+    fn transform_facing(&self, yaw: f32) -> Transform {
+        let direction = Quat::from_rotation_y(yaw) * CHASE_OFFSET.normalize();
+        Transform::from_translation(self.focus + (direction * self.distance))
+            .looking_at(self.focus, Vec3::Y)
+    }
+}
+
 /// Tags an entity as capable of panning and orbiting.
 #[derive(Component)]
 pub struct PanOrbitCamera {
@@ -23,6 +92,26 @@ pub struct PanOrbitCamera {
     pub upside_down: bool,
 }
 
+/// Keeps the free camera's focus on an entity, chosen by hand or by the auto-director.
+#[derive(Component)]
+pub struct FreeCameraFollow {
+    pub target: Option<Entity>,
+    /// Whether the auto-director picks the target.
+    pub director: bool,
+    /// Time since the auto-director last cut to a new target.
+    shot_timer: Timer,
+}
+
+impl Default for FreeCameraFollow {
+    fn default() -> Self {
+        FreeCameraFollow {
+            target: None,
+            director: false,
+            shot_timer: Timer::from_seconds(DIRECTOR_SHOT_SECONDS, TimerMode::Once),
+        }
+    }
+}
+
 pub struct SwitchCameraEvent;
 
 impl Default for PanOrbitCamera {
@@ -155,6 +244,7 @@ pub fn spawn_camera(mut commands: Commands) {
             ..Default::default()
         })
         .insert(pan_orbit)
+        .insert(FreeCameraFollow::default())
         .insert(FreeCamera);
 }
 
@@ -192,3 +282,305 @@ pub fn camera_events(
         }
     }
 }
+
+/// Picks what the auto-director should be looking at: the combine in the hardest hit, or the
+/// fastest combine once the current shot has gone on long enough.
+fn director_pick(
+    impacts: &[(Entity, f32)],
+    speeds: &[(Entity, f32)],
+    current: Option<Entity>,
+    shot_seconds: f32,
+) -> Option<Entity> {
+    if shot_seconds < DIRECTOR_MIN_SHOT_SECONDS && current.is_some() {
+        return current;
+    }
+    let hardest_impact = impacts
+        .iter()
+        .filter(|(_, force)| *force >= DIRECTOR_IMPACT_FORCE)
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((entity, _)) = hardest_impact {
+        return Some(*entity);
+    }
+    if shot_seconds < DIRECTOR_SHOT_SECONDS && current.is_some() {
+        return current;
+    }
+    speeds
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| *entity)
+        .or(current)
+}
+
+/// Moves the free camera's focus after the entity it follows. Tab cycles through combines, cows
+/// and hay bales, Backspace stops following and F5 toggles the auto-director, unless typing in the
+/// console.
+pub fn free_camera_follow_system(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    console: Res<Console>,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    mut camera_query: Query<(&mut PanOrbitCamera, &mut FreeCameraFollow, &mut Transform)>,
+    target_query: Query<(Entity, &GlobalTransform), Or<(With<Combine>, With<Cow>, With<HayBale>)>>,
+    combine_query: Query<(Entity, &Combine)>,
+) {
+    let impacts: Vec<(Entity, f32)> = contact_force_events
+        .iter()
+        .flat_map(|event| {
+            [event.collider1, event.collider2]
+                .into_iter()
+                .filter(|entity| combine_query.contains(*entity))
+                .map(|entity| (entity, event.total_force_magnitude))
+        })
+        .collect();
+
+    for (mut pan_orbit, mut follow, mut transform) in camera_query.iter_mut() {
+        // Tab completes and Backspace deletes while typing in the console.
+        if !console.open {
+            if keys.just_pressed(KeyCode::F5) {
+                follow.director = !follow.director;
+                follow.shot_timer.reset();
+            }
+            if keys.just_pressed(KeyCode::Back) {
+                follow.target = None;
+                follow.director = false;
+            }
+            if keys.just_pressed(KeyCode::Tab) {
+                let mut targets: Vec<Entity> =
+                    target_query.iter().map(|(entity, _)| entity).collect();
+                targets.sort();
+                let next = match follow
+                    .target
+                    .and_then(|target| targets.iter().position(|e| *e == target))
+                {
+                    Some(index) => targets.get(index + 1),
+                    None => targets.first(),
+                };
+                follow.target = next.copied();
+                follow.director = false;
+            }
+        }
+
+        if follow
+            .target
+            .map_or(false, |target| !target_query.contains(target))
+        {
+            follow.target = None;
+        }
+
+        if follow.director {
+            follow.shot_timer.tick(time.delta());
+            let speeds: Vec<(Entity, f32)> = combine_query
+                .iter()
+                .map(|(entity, combine)| (entity, combine.velocity))
+                .collect();
+            let target = director_pick(
+                &impacts,
+                &speeds,
+                follow.target,
+                follow.shot_timer.elapsed_secs(),
+            );
+            if target != follow.target {
+                follow.target = target;
+                follow.shot_timer.reset();
+                pan_orbit.radius = DIRECTOR_RADIUS;
+            } else if follow.shot_timer.finished() {
+                // the same target is still the most interesting, so the shot carries on.
+                follow.shot_timer.reset();
+            }
+        }
+
+        let target_position = match follow
+            .target
+            .and_then(|target| target_query.get(target).ok())
+        {
+            Some((_, target_transform)) => target_transform.translation(),
+            None => continue,
+        };
+        let response = 1.0 - (-FOLLOW_RESPONSE * time.delta_seconds()).exp();
+        pan_orbit.focus = pan_orbit.focus.lerp(target_position, response);
+        let rot_matrix = Mat3::from_quat(transform.rotation);
+        transform.translation =
+            pan_orbit.focus + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, pan_orbit.radius));
+    }
+}
+
+/// Gets the angle around the Y axis that points behind a combine, ignoring its roll and pitch.
+fn heading_yaw(transform: &Transform) -> f32 {
+    let back = transform.back();
+    back.x.atan2(back.z)
+}
+
+/// Gets the smallest turn from one angle to another, between -PI and PI.
+fn shortest_angle(from: f32, to: f32) -> f32 {
+    let difference = (to - from).rem_euclid(2.0 * PI);
+    if difference > PI {
+        difference - (2.0 * PI)
+    } else {
+        difference
+    }
+}
+
+/// Moves a value towards a target like a critically damped spring, so it never overshoots.
+fn spring_step(
+    current: Vec3,
+    velocity: Vec3,
+    target: Vec3,
+    frequency: f32,
+    delta_seconds: f32,
+) -> (Vec3, Vec3) {
+    let x = frequency * delta_seconds;
+    let decay = 1.0 / (1.0 + x + (0.48 * x * x) + (0.235 * x * x * x));
+    let change = current - target;
+    let temp = (velocity + (change * frequency)) * delta_seconds;
+    let velocity = (velocity - (temp * frequency)) * decay;
+    (target + ((change + temp) * decay), velocity)
+}
+
+/// Moves chase cameras after their combines, pulling in when something blocks the view. Hold C
+/// to look back, unless typing in the console.
+pub fn chase_camera_system(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    console: Res<Console>,
+    rapier_context: Res<RapierContext>,
+    combine_query: Query<(Entity, &Combine, &Transform), Without<ChaseCamera>>,
+    wheel_query: Query<&Wheel>,
+    mut camera_query: Query<(&CombineCamera, &mut ChaseCamera, &mut Transform)>,
+) {
+    let delta_seconds = time.delta_seconds();
+    let look_back = keys.pressed(KeyCode::C) && !console.open;
+
+    for (combine_camera, mut chase, mut camera_transform) in camera_query.iter_mut() {
+        let combine = combine_query
+            .iter()
+            .find(|(_, combine, _)| combine.combine_id == combine_camera.combine_id);
+        let (combine_entity, combine_transform) = match combine {
+            Some((entity, _, transform)) => (entity, transform),
+            None => continue,
+        };
+
+        let target = combine_transform.translation + (Vec3::Y * CHASE_LOOK_HEIGHT);
+        let (focus, focus_velocity) = spring_step(
+            chase.focus,
+            chase.focus_velocity,
+            target,
+            CHASE_FOLLOW_FREQUENCY,
+            delta_seconds,
+        );
+        chase.focus = focus;
+        chase.focus_velocity = focus_velocity;
+
+        let target_yaw = heading_yaw(combine_transform);
+        let yaw_response = 1.0 - (-CHASE_YAW_RESPONSE * delta_seconds).exp();
+        chase.yaw += shortest_angle(chase.yaw, target_yaw) * yaw_response;
+        let yaw = if look_back { chase.yaw + PI } else { chase.yaw };
+
+        let full_distance = CHASE_OFFSET.length();
+        let direction = Quat::from_rotation_y(yaw) * CHASE_OFFSET.normalize();
+        let is_own_wheel = |entity| {
+            wheel_query
+                .get(entity)
+                .map_or(false, |wheel| wheel.combine_id == combine_camera.combine_id)
+        };
+        let clear_distance = rapier_context
+            .cast_ray(
+                chase.focus,
+                direction,
+                full_distance,
+                true,
+                QueryFilter::default()
+                    .exclude_sensors()
+                    .exclude_rigid_body(combine_entity)
+                    .predicate(&|entity| !is_own_wheel(entity)),
+            )
+            .map(|(_, distance)| (distance - CHASE_CLEARANCE).max(CHASE_MIN_DISTANCE))
+            .unwrap_or(full_distance);
+
+        if clear_distance < chase.distance {
+            chase.distance = clear_distance;
+        } else {
+            let distance_response = 1.0 - (-CHASE_DISTANCE_RESPONSE * delta_seconds).exp();
+            chase.distance += (clear_distance - chase.distance) * distance_response;
+        }
+
+        *camera_transform = chase.transform_facing(yaw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::prelude::*;
+
+    use crate::camera::{
+        director_pick, heading_yaw, shortest_angle, spring_step, ChaseCamera, CHASE_OFFSET,
+        DIRECTOR_IMPACT_FORCE, DIRECTOR_SHOT_SECONDS,
+    };
+
+    #[test]
+    fn shortest_angle_wraps_test() {
+        assert!((shortest_angle(0.1, -0.1) + 0.2).abs() < 0.001);
+        assert!((shortest_angle(PI - 0.1, -PI + 0.1) - 0.2).abs() < 0.001);
+        assert!((shortest_angle(-PI + 0.1, PI - 0.1) + 0.2).abs() < 0.001);
+    }
+
+    #[test]
+    fn spring_settles_without_overshoot_test() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let mut current = Vec3::ZERO;
+        let mut velocity = Vec3::ZERO;
+
+        for _ in 0..300 {
+            (current, velocity) = spring_step(current, velocity, target, 5.0, 1.0 / 60.0);
+            assert!(current.x <= target.x + 0.001);
+        }
+        assert!((current - target).length() < 0.01);
+    }
+
+    #[test]
+    fn chase_camera_ignores_roll_test() {
+        let level = Transform::from_xyz(5.0, 0.0, 5.0);
+        let rolled = level.with_rotation(Quat::from_rotation_z(0.5));
+
+        assert!((heading_yaw(&level) - heading_yaw(&rolled)).abs() < 0.001);
+
+        let camera = ChaseCamera::new(&rolled).transform();
+        let up = camera.rotation * Vec3::Y;
+        assert!(up.x.abs() < 0.001);
+        assert!((camera.translation.z - (5.0 + CHASE_OFFSET.z)).abs() < 0.001);
+    }
+
+    #[test]
+    fn director_cuts_to_big_hits_test() {
+        let slow = Entity::from_raw(1);
+        let fast = Entity::from_raw(2);
+        let crashed = Entity::from_raw(3);
+        let speeds = [(slow, 5.0), (fast, 30.0), (crashed, 10.0)];
+        let impacts = [(crashed, DIRECTOR_IMPACT_FORCE * 2.0), (slow, 10.0)];
+
+        assert_eq!(Some(fast), director_pick(&[], &speeds, None, 0.0));
+        assert_eq!(
+            Some(crashed),
+            director_pick(&impacts, &speeds, Some(fast), 3.0)
+        );
+        // a shot that has only just started isn't cut short.
+        assert_eq!(
+            Some(fast),
+            director_pick(&impacts, &speeds, Some(fast), 0.5)
+        );
+    }
+
+    #[test]
+    fn director_holds_shot_test() {
+        let slow = Entity::from_raw(1);
+        let fast = Entity::from_raw(2);
+        let speeds = [(slow, 5.0), (fast, 30.0)];
+
+        assert_eq!(Some(slow), director_pick(&[], &speeds, Some(slow), 3.0));
+        assert_eq!(
+            Some(fast),
+            director_pick(&[], &speeds, Some(slow), DIRECTOR_SHOT_SECONDS)
+        );
+    }
+}
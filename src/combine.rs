@@ -8,14 +8,33 @@ use bevy_rapier3d::prelude::*;
 
 use crate::{
     ai::AiState,
-    arena::{PLANE_SIZE, RAMP_HEIGHT},
-    camera::CombineCamera,
+    boost::{Boost, BOOST_MOTOR_FACTOR, BOOST_SPEED_FACTOR},
+    camera::{ChaseCamera, CombineCamera},
+    engine::Engine,
+    impact::CameraShake,
+    layout::ArenaLayout,
+    pickup::PowerUps,
+    sounds::IMPACT_MIN_FORCE,
 };
 
 pub const PLAYER_COMBINE_ID: i32 = 1;
 
 const VELOCITY_QUEUE_LIMIT: usize = 16;
 
+pub const COMBINE_MAX_HEALTH: f32 = 100.0;
+/// Contact force a combine can take without being damaged.
+pub const COMBINE_DAMAGE_FORCE: f32 = 60000.0;
+/// Health lost for each unit of contact force above [`COMBINE_DAMAGE_FORCE`].
+const COMBINE_DAMAGE_PER_FORCE: f32 = 0.0005;
+/// Fraction of its usual drive speed a combine keeps with no health left.
+const WRECKED_SPEED_FACTOR: f32 = 0.5;
+/// How hard the wheel motors chase their target velocity, at peak torque in the reference gear.
+const MOTOR_FACTOR: f32 = 0.1;
+/// Gear ratio where the wheel motors get exactly [`MOTOR_FACTOR`]. Lower gears push harder.
+const REFERENCE_GEAR_RATIO: f32 = 2.1;
+/// How hard the wheel motors hold the wheels still while braking.
+const BRAKE_MOTOR_FACTOR: f32 = 0.1;
+
 #[derive(Component, Default)]
 pub struct Combine {
     pub combine_id: i32,
@@ -23,6 +42,8 @@ pub struct Combine {
     pub velocity_queue: VecDeque<(Vec3, Duration)>,
 
     pub velocity: f32,
+
+    pub health: f32,
 }
 
 impl Combine {
@@ -31,12 +52,23 @@ impl Combine {
             combine_id,
             velocity_queue: VecDeque::new(),
             velocity: 0.0,
+            health: COMBINE_MAX_HEALTH,
         }
     }
 }
 
 #[derive(Component)]
-pub struct Wheel;
+pub struct Wheel {
+    pub combine_id: i32,
+}
+
+/// The small, dense collider that sets a combine's centre of mass.
+#[derive(Component)]
+pub struct Ballast {
+    pub combine_id: i32,
+    /// Density the ballast has when no power-up is changing it.
+    pub density: f32,
+}
 
 #[derive(Component)]
 pub struct SteeringWheel {
@@ -50,6 +82,7 @@ pub struct SteeringWheel {
 pub struct DrivingWheel {
     pub combine_id: i32,
     pub target_velocity: f32,
+    pub motor_factor: f32,
 }
 
 impl DrivingWheel {
@@ -57,47 +90,150 @@ impl DrivingWheel {
         DrivingWheel {
             combine_id,
             target_velocity: 10.0,
+            motor_factor: MOTOR_FACTOR,
         }
     }
 }
 
+/// Gets the colour used to tell a combine apart from the others.
+pub fn combine_colour(combine_id: i32) -> Color {
+    const COLOURS: [Color; 4] = [
+        Color::rgb(0.9, 0.2, 0.15),
+        Color::rgb(0.15, 0.45, 0.9),
+        Color::rgb(0.95, 0.8, 0.1),
+        Color::rgb(0.2, 0.75, 0.3),
+    ];
+    COLOURS[(combine_id - PLAYER_COMBINE_ID).rem_euclid(COLOURS.len() as i32) as usize]
+}
+
+/// Gets the name a combine goes by on the HUD.
+pub fn combine_name(combine_id: i32) -> String {
+    if combine_id == PLAYER_COMBINE_ID {
+        "You".to_string()
+    } else {
+        format!("Combine {}", combine_id)
+    }
+}
+
+/// The handling of a combine, which can be tuned while driving and saved as a spec file.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct CombineSpec {
+    pub wheel_friction: f32,
+    /// Replaces [`MOTOR_FACTOR`] for this combine.
+    pub motor_factor: f32,
+    /// How hard the steering motors turn the rear wheels to the wanted angle.
+    pub steering_stiffness: f32,
+    pub steering_damping: f32,
+    /// Density of the small ballast collider, which pulls the centre of mass towards it.
+    pub ballast_density: f32,
+    /// Where the ballast sits, relative to the middle of the body.
+    pub centre_of_mass: Vec3,
+}
+
+impl Default for CombineSpec {
+    fn default() -> Self {
+        CombineSpec {
+            wheel_friction: 1.8,
+            motor_factor: MOTOR_FACTOR,
+            steering_stiffness: 1.0,
+            steering_damping: 0.5,
+            ballast_density: 500.0,
+            centre_of_mass: Vec3::new(0.0, -3.0, -7.0),
+        }
+    }
+}
+
+impl CombineSpec {
+    /// Reads a spec file written by [`CombineSpec::save`].
+    pub fn load(path: &str) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map(|spec| CombineSpec::from_spec(&spec))
+            .map_err(|error| format!("couldn't read {}: {}", path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_spec())
+            .map_err(|error| format!("couldn't save {}: {}", path, error))
+    }
+
+    fn to_spec(&self) -> String {
+        let centre_of_mass = self.centre_of_mass;
+        format!(
+            "wheel_friction={}\nmotor_factor={}\nsteering_stiffness={}\nsteering_damping={}\n\
+             ballast_density={}\ncentre_of_mass={} {} {}\n",
+            self.wheel_friction,
+            self.motor_factor,
+            self.steering_stiffness,
+            self.steering_damping,
+            self.ballast_density,
+            centre_of_mass.x,
+            centre_of_mass.y,
+            centre_of_mass.z
+        )
+    }
+
+    /// Reads a spec. Anything missing or unreadable keeps its default.
+    fn from_spec(spec: &str) -> Self {
+        let mut combine_spec = CombineSpec::default();
+        for line in spec.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            let numbers: Vec<f32> = value
+                .split_whitespace()
+                .filter_map(|number| number.parse().ok())
+                .collect();
+            match (key, numbers.as_slice()) {
+                ("wheel_friction", [value]) => combine_spec.wheel_friction = *value,
+                ("motor_factor", [value]) => combine_spec.motor_factor = *value,
+                ("steering_stiffness", [value]) => combine_spec.steering_stiffness = *value,
+                ("steering_damping", [value]) => combine_spec.steering_damping = *value,
+                ("ballast_density", [value]) => combine_spec.ballast_density = *value,
+                ("centre_of_mass", [x, y, z]) => {
+                    combine_spec.centre_of_mass = Vec3::new(*x, *y, *z)
+                }
+                _ => (),
+            }
+        }
+        combine_spec
+    }
+}
+
+/// Keeps a combine from taking damage, for the console's god mode.
+#[derive(Component)]
+pub struct Invulnerable;
+
 #[derive(Eq, PartialEq)]
 pub enum SteeringWheelPosition {
     Left,
     Right,
 }
 
-pub fn spawn_combines(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let spawn_position_1 = Transform::from_translation(Vec3::new(
-        PLANE_SIZE - (PLANE_SIZE / 3.0),
-        RAMP_HEIGHT + 4.1,
-        PLANE_SIZE - (PLANE_SIZE / 3.0),
-    ))
-    .with_rotation(Quat::from_rotation_y(45.0_f32.to_radians()));
-
-    commands = create_combine(
-        commands,
-        &asset_server,
-        PLAYER_COMBINE_ID,
-        spawn_position_1,
-        true,
-    );
-
-    let spawn_position_2 = Transform::from_translation(Vec3::new(
-        -(PLANE_SIZE - (PLANE_SIZE / 3.0)),
-        RAMP_HEIGHT + 4.1,
-        -(PLANE_SIZE - (PLANE_SIZE / 3.0)),
-    ))
-    .with_rotation(Quat::from_rotation_y(215.0_f32.to_radians()));
-
-    create_combine(commands, &asset_server, 2, spawn_position_2, false);
+pub fn spawn_combines(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena_layout: Res<ArenaLayout>,
+) {
+    for (i, spawn_point) in arena_layout.spawn_points.iter().enumerate() {
+        let combine_id = PLAYER_COMBINE_ID + i as i32;
+        commands = create_combine(
+            commands,
+            &asset_server,
+            combine_id,
+            spawn_point.transform(),
+            &CombineSpec::default(),
+            combine_id == PLAYER_COMBINE_ID,
+        );
+    }
 }
 
-fn create_combine<'w, 's>(
+pub fn create_combine<'w, 's>(
     mut commands: Commands<'w, 's>,
     asset_server: &Res<AssetServer>,
     combine_id: i32,
     spawn_transform: Transform,
+    spec: &CombineSpec,
     active_camera: bool,
 ) -> Commands<'w, 's> {
     let body_linear_damping = 0.0;
@@ -107,7 +243,7 @@ fn create_combine<'w, 's>(
     let body_density = 10.0;
 
     let wheel_restitution = 0.0;
-    let wheel_friction = 1.8;
+    let wheel_friction = spec.wheel_friction;
     let wheel_density = 8.0;
 
     let wheel_width = 0.2;
@@ -116,13 +252,13 @@ fn create_combine<'w, 's>(
     let max_steer_force = f32::MAX;
     let wheel_factor = 0.7;
 
-    let steering_motor_stiffness = 0.5;
-    let steering_motor_damping = 0.5;
+    let steering_motor_stiffness = spec.steering_stiffness;
+    let steering_motor_damping = spec.steering_damping;
 
     let physics = RigidBody::Dynamic;
 
-    let center_of_mass = Vec3::new(0.0, -3.0, -7.0);
-    let ballast_mass = 500.0;
+    let center_of_mass = spec.centre_of_mass;
+    let ballast_mass = spec.ballast_density;
 
     let body_gltf: Handle<Scene> = asset_server.load("combine-body.glb#Scene0");
     let wheel_gltf: Handle<Scene> = asset_server.load("basic-wheel.glb#Scene0");
@@ -137,8 +273,17 @@ fn create_combine<'w, 's>(
         })
         .insert(Friction::coefficient(body_friction))
         .insert(Combine::new(combine_id))
+        .insert(spec.clone())
+        .insert(PowerUps::default())
+        .insert(Boost::default())
+        .insert(Engine::default())
         .insert(physics)
+        .insert(Velocity::default())
         .insert(Collider::cuboid(3.8, 4.0, 9.0))
+        .insert(ActiveEvents::CONTACT_FORCE_EVENTS)
+        // sounds, cows and the camera react to much softer hits than the damage system, which
+        // filters by COMBINE_DAMAGE_FORCE itself.
+        .insert(ContactForceEventThreshold(IMPACT_MIN_FORCE))
         .insert(ColliderMassProperties::Density(body_density))
         .insert(AdditionalMassProperties::MassProperties(MassProperties {
             local_center_of_mass: center_of_mass,
@@ -160,19 +305,11 @@ fn create_combine<'w, 's>(
             parent
                 .spawn(Collider::cuboid(0.1, 0.1, 0.1))
                 .insert(Transform::from_translation(center_of_mass))
-                .insert(ColliderMassProperties::Density(ballast_mass));
-
-            parent
-                .spawn(Camera3dBundle {
-                    transform: Transform::from_xyz(0.0, 20.0, 40.0)
-                        .with_rotation(Quat::from_rotation_x(-0.4)),
-                    camera: Camera {
-                        is_active: active_camera,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(CombineCamera { combine_id });
+                .insert(ColliderMassProperties::Density(ballast_mass))
+                .insert(Ballast {
+                    combine_id,
+                    density: ballast_mass,
+                });
         });
 
     if combine_id != PLAYER_COMBINE_ID {
@@ -184,12 +321,26 @@ fn create_combine<'w, 's>(
 
     let body_entity = body_commands.id();
 
+    let chase_camera = ChaseCamera::new(&spawn_transform);
+    commands
+        .spawn(Camera3dBundle {
+            transform: chase_camera.transform(),
+            camera: Camera {
+                is_active: active_camera,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(CombineCamera { combine_id })
+        .insert(chase_camera)
+        .insert(CameraShake::default());
+
     let wheel_0_entity = commands
         .spawn(SpatialBundle::from(Transform::from_xyz(0.0, 0.0, 0.0)))
         .insert(physics)
         .with_children(|parent| {
             parent
-                .spawn(Wheel)
+                .spawn(Wheel { combine_id })
                 .insert(Transform::from_rotation(Quat::from_rotation_z(
                     90.0_f32.to_radians(),
                 )))
@@ -213,7 +364,7 @@ fn create_combine<'w, 's>(
         .insert(physics)
         .with_children(|parent| {
             parent
-                .spawn(Wheel)
+                .spawn(Wheel { combine_id })
                 .insert(Transform::from_rotation(Quat::from_rotation_z(
                     90.0_f32.to_radians(),
                 )))
@@ -237,7 +388,7 @@ fn create_combine<'w, 's>(
         .insert(physics)
         .with_children(|parent| {
             parent
-                .spawn(Wheel)
+                .spawn(Wheel { combine_id })
                 .insert(Transform::from_rotation(Quat::from_rotation_z(
                     90.0_f32.to_radians(),
                 )))
@@ -261,7 +412,7 @@ fn create_combine<'w, 's>(
         .insert(physics)
         .with_children(|parent| {
             parent
-                .spawn(Wheel)
+                .spawn(Wheel { combine_id })
                 .insert(Transform::from_rotation(Quat::from_rotation_z(
                     90.0_f32.to_radians(),
                 )))
@@ -407,35 +558,120 @@ pub fn combine_speedometer_system(
     }
 }
 
+/// Takes health from combines that are hit hard, unless they are shielded or invulnerable.
+pub fn combine_damage_system(
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    mut combine_query: Query<(&mut Combine, &PowerUps), Without<Invulnerable>>,
+) {
+    for contact_force_event in contact_force_events.iter() {
+        let damage = (contact_force_event.total_force_magnitude - COMBINE_DAMAGE_FORCE)
+            * COMBINE_DAMAGE_PER_FORCE;
+        if damage <= 0.0 {
+            continue;
+        }
+        for entity in [contact_force_event.collider1, contact_force_event.collider2] {
+            if let Ok((mut combine, power_ups)) = combine_query.get_mut(entity) {
+                if !power_ups.shielded() {
+                    combine.health = (combine.health - damage).max(0.0);
+                }
+            }
+        }
+    }
+}
+
+/// Drives the wheels from the engine. Each gear has a top wheel speed at the redline, and the
+/// wheels are pushed towards it as hard as the engine's torque at its current speed allows. While
+/// braking the wheel motors slow the wheels to a stop instead.
 pub fn transmission_system(
-    combine_query: Query<&Combine>,
-    time: Res<Time>,
+    combine_query: Query<(&Combine, &CombineSpec, &Engine, &Boost)>,
     mut drive_wheel_query: Query<&mut DrivingWheel>,
 ) {
-    let mut combine_map: HashMap<i32, f32> = HashMap::new();
-
-    for combine in combine_query.iter() {
-        let factor = 1.33;
-        let constant = 3.0;
-
-        /*let mut velocity = 2.0;
-        if combine.velocity > 30.0 {
-            velocity = 40.0;
-        } else if combine.velocity > 15.0 {
-            velocity = 20.0;
-        }*/
+    let mut combine_map: HashMap<i32, (f32, f32)> = HashMap::new();
+
+    for (combine, spec, engine, boost) in combine_query.iter() {
+        let mut velocity = engine.redline_wheel_velocity();
+        let mut motor_factor = if engine.braking {
+            BRAKE_MOTOR_FACTOR
+        } else if engine.shifting() {
+            0.0
+        } else {
+            spec.motor_factor * engine.torque_fraction() * engine.gear_ratio()
+                / REFERENCE_GEAR_RATIO
+        };
+
+        // damaged combines drive slower, down to half speed when wrecked.
+        let health_fraction = combine.health / COMBINE_MAX_HEALTH;
+        velocity *= WRECKED_SPEED_FACTOR + ((1.0 - WRECKED_SPEED_FACTOR) * health_fraction);
+        if boost.active && !engine.braking {
+            velocity *= BOOST_SPEED_FACTOR;
+            motor_factor *= BOOST_MOTOR_FACTOR;
+        }
 
-        let velocity = (combine.velocity * factor) + constant;
+        combine_map.insert(combine.combine_id, (velocity, motor_factor));
+    }
 
-        // compensate for 60fps target
+    for mut drive_wheel in drive_wheel_query.iter_mut() {
+        let (velocity, motor_factor) = combine_map.get(&drive_wheel.combine_id).unwrap();
+        drive_wheel.target_velocity = *velocity;
+        drive_wheel.motor_factor = *motor_factor;
+    }
+}
 
-        let adj_velocity = ((time.delta().as_millis() as f32) / (1000.0 / 60.0)) * velocity;
+/// Applies changes to combine specs to the wheels and ballast they were spawned with. The motor
+/// factor and steering are picked up by the systems driving the joints.
+pub fn combine_spec_system(
+    mut combine_query: Query<
+        (&Combine, &CombineSpec, &mut AdditionalMassProperties),
+        Changed<CombineSpec>,
+    >,
+    mut wheel_query: Query<(&Wheel, &mut Friction)>,
+    mut ballast_query: Query<(&mut Ballast, &mut Transform)>,
+) {
+    for (combine, spec, mut mass_properties) in combine_query.iter_mut() {
+        *mass_properties = AdditionalMassProperties::MassProperties(MassProperties {
+            local_center_of_mass: spec.centre_of_mass,
+            ..Default::default()
+        });
+        for (wheel, mut friction) in wheel_query.iter_mut() {
+            if wheel.combine_id == combine.combine_id {
+                friction.coefficient = spec.wheel_friction;
+            }
+        }
+        // the power-up system gives the ballast its new density.
+        for (mut ballast, mut transform) in ballast_query.iter_mut() {
+            if ballast.combine_id == combine.combine_id {
+                ballast.density = spec.ballast_density;
+                transform.translation = spec.centre_of_mass;
+            }
+        }
+    }
+}
 
-        combine_map.insert(combine.combine_id, adj_velocity);
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::combine::CombineSpec;
+
+    #[test]
+    fn spec_round_trip_test() {
+        let spec = CombineSpec {
+            wheel_friction: 2.5,
+            motor_factor: 0.15,
+            steering_stiffness: 0.8,
+            steering_damping: 0.25,
+            ballast_density: 650.0,
+            centre_of_mass: Vec3::new(0.5, -2.0, -6.5),
+        };
+        assert_eq!(spec, CombineSpec::from_spec(&spec.to_spec()));
     }
 
-    for mut drive_wheel in drive_wheel_query.iter_mut() {
-        let velocity = combine_map.get(&drive_wheel.combine_id).unwrap();
-        drive_wheel.target_velocity = *velocity;
+    #[test]
+    fn spec_keeps_defaults_test() {
+        let spec =
+            CombineSpec::from_spec("wheel_friction=1.2\nmotor_factor=fast\ncentre_of_mass=1 2");
+        assert_eq!(1.2, spec.wheel_friction);
+        assert_eq!(CombineSpec::default().motor_factor, spec.motor_factor);
+        assert_eq!(CombineSpec::default().centre_of_mass, spec.centre_of_mass);
     }
 }
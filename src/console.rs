@@ -0,0 +1,599 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    ai::AiControl,
+    combine::{
+        create_combine, Combine, CombineSpec, Invulnerable, SteeringWheel, Wheel,
+        COMBINE_MAX_HEALTH, PLAYER_COMBINE_ID,
+    },
+    impact::{set_physics_time_scale, ImpactEffects},
+    menu::AppState,
+    obstacle::spawn_cow_with_transform,
+};
+
+/// Lines of output kept on screen.
+const OUTPUT_LINES: usize = 12;
+/// How far in front of the player things are spawned when no position is given.
+const SPAWN_DISTANCE: f32 = 30.0;
+/// How far above the player cows are dropped when no position is given.
+const COW_DROP_HEIGHT: f32 = 10.0;
+
+/// Each command's words and the arguments it takes, for help and autocomplete.
+const COMMANDS: [(&str, &str); 9] = [
+    ("spawn cow", "[x y z]"),
+    ("spawn combine", "[spec file] [x y z]"),
+    ("set gravity", "<down> | <x y z>"),
+    ("tp", "<x y z>"),
+    ("god", "[on|off]"),
+    ("timescale", "<scale>"),
+    ("ai", "<on|off>"),
+    ("help", ""),
+    ("clear", ""),
+];
+
+/// Adds the developer console, which drops down from the top of the screen with the ` key.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<AiControl>()
+            .add_event::<ConsoleCommandEvent>()
+            .add_startup_system(setup_console)
+            .add_system_set(
+                SystemSet::on_update(AppState::Match)
+                    .with_system(console_system)
+                    .with_system(console_command_system.after(console_system)),
+            );
+    }
+}
+
+/// What has been typed into the console, what it printed back, and whether it is showing.
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: VecDeque<String>,
+    /// Lines entered before, oldest first.
+    history: Vec<String>,
+    /// Which line of the history is being shown, while stepping through it.
+    history_index: Option<usize>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.output.push_back(line.into());
+        while self.output.len() > OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Takes the typed line, echoing it and adding it to the history.
+    fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input).trim().to_string();
+        self.history_index = None;
+        self.print(format!("> {}", line));
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        line
+    }
+
+    /// Shows the line entered before the one showing, starting from the latest.
+    fn history_back(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Shows the line entered after the one showing, clearing the input after the latest.
+    fn history_forward(&mut self) {
+        if let Some(index) = self.history_index {
+            if index + 1 < self.history.len() {
+                self.history_index = Some(index + 1);
+                self.input = self.history[index + 1].clone();
+            } else {
+                self.history_index = None;
+                self.input.clear();
+            }
+        }
+    }
+
+    /// Completes the typed command as far as it can, listing the choices if there are several.
+    fn autocomplete(&mut self) {
+        let (completed, matches) = complete(&self.input);
+        if matches.len() > 1 {
+            self.print(matches.join("  "));
+        }
+        self.input = completed;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ConsoleCommand {
+    SpawnCow(Option<Vec3>),
+    /// Spawns an AI combine, from a spec file if given.
+    SpawnCombine(Option<String>, Option<Vec3>),
+    SetGravity(Vec3),
+    Teleport(Vec3),
+    /// Switches the player's god mode on or off, or over if not given.
+    God(Option<bool>),
+    TimeScale(f32),
+    Ai(bool),
+    Help,
+    Clear,
+}
+
+/// A command entered in the console, to be run by [`console_command_system`].
+pub struct ConsoleCommandEvent {
+    command: ConsoleCommand,
+}
+
+fn parse_number(word: &str) -> Result<f32, String> {
+    word.parse()
+        .map_err(|_| format!("'{}' is not a number", word))
+}
+
+fn parse_vec3(words: &[&str]) -> Result<Vec3, String> {
+    match words {
+        [x, y, z] => Ok(Vec3::new(
+            parse_number(x)?,
+            parse_number(y)?,
+            parse_number(z)?,
+        )),
+        _ => Err("expected x y z".to_string()),
+    }
+}
+
+/// Gets an optional position, which is either left out or given as x y z.
+fn parse_position(words: &[&str]) -> Result<Option<Vec3>, String> {
+    if words.is_empty() {
+        Ok(None)
+    } else {
+        parse_vec3(words).map(Some)
+    }
+}
+
+fn parse_switch(word: &str) -> Result<bool, String> {
+    match word {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off, not '{}'", word)),
+    }
+}
+
+/// Gets how the commands starting like the line are used, to show when it can't be parsed.
+fn usage(line: &str) -> Option<String> {
+    let first_word = line.split_whitespace().next()?;
+    let usages: Vec<String> = COMMANDS
+        .iter()
+        .filter(|(name, _)| name.split(' ').next() == Some(first_word))
+        .map(|(name, arguments)| format!("usage: {} {}", name, arguments))
+        .collect();
+    if usages.is_empty() {
+        None
+    } else {
+        Some(usages.join("\n"))
+    }
+}
+
+fn parse_command(line: &str) -> Result<ConsoleCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let parsed = match words.as_slice() {
+        ["spawn", "cow", position @ ..] => parse_position(position).map(ConsoleCommand::SpawnCow),
+        ["spawn", "combine", spec, position @ ..] if spec.parse::<f32>().is_err() => {
+            parse_position(position)
+                .map(|position| ConsoleCommand::SpawnCombine(Some(spec.to_string()), position))
+        }
+        ["spawn", "combine", position @ ..] => {
+            parse_position(position).map(|position| ConsoleCommand::SpawnCombine(None, position))
+        }
+        ["set", "gravity", down] => {
+            parse_number(down).map(|down| ConsoleCommand::SetGravity(Vec3::new(0.0, -down, 0.0)))
+        }
+        ["set", "gravity", position @ ..] => parse_vec3(position).map(ConsoleCommand::SetGravity),
+        ["tp", position @ ..] => parse_vec3(position).map(ConsoleCommand::Teleport),
+        ["god"] => Ok(ConsoleCommand::God(None)),
+        ["god", switch] => parse_switch(switch).map(|on| ConsoleCommand::God(Some(on))),
+        ["timescale", scale] => parse_number(scale).and_then(|scale| {
+            if scale > 0.0 {
+                Ok(ConsoleCommand::TimeScale(scale))
+            } else {
+                Err("the time scale must be above 0".to_string())
+            }
+        }),
+        ["ai", switch] => parse_switch(switch).map(ConsoleCommand::Ai),
+        ["help"] => Ok(ConsoleCommand::Help),
+        ["clear"] => Ok(ConsoleCommand::Clear),
+        _ => Err(String::new()),
+    };
+    parsed.map_err(|error| match usage(line) {
+        Some(usage) if error.is_empty() => usage,
+        Some(usage) => format!("{}\n{}", error, usage),
+        None => format!("unknown command '{}', try help", line.trim()),
+    })
+}
+
+/// Gets the commands starting with the typed text, and the typed text completed as far as all of
+/// them agree.
+fn complete(input: &str) -> (String, Vec<&'static str>) {
+    let typed = input.trim_start();
+    let matches: Vec<&'static str> = COMMANDS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| name.starts_with(typed))
+        .collect();
+
+    let completed = match matches.as_slice() {
+        [] => input.to_string(),
+        [name] => format!("{} ", name),
+        [first, rest @ ..] => {
+            let mut prefix = first.to_string();
+            for name in rest {
+                while !name.starts_with(&prefix) {
+                    prefix.pop();
+                }
+            }
+            if prefix.len() > typed.len() {
+                prefix
+            } else {
+                input.to_string()
+            }
+        }
+    };
+    (completed, matches)
+}
+
+fn format_position(position: Vec3) -> String {
+    format!("{:.1} {:.1} {:.1}", position.x, position.y, position.z)
+}
+
+#[derive(Component)]
+pub struct ConsoleUi;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+pub fn setup_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(ConsoleUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: mono_font,
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ConsoleText);
+        });
+}
+
+/// ` opens and closes the console. While it is open, typing goes into it, Enter runs the line, Up
+/// and Down step through earlier lines, Tab completes commands and Escape closes it.
+pub fn console_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+    mut command_events: EventWriter<ConsoleCommandEvent>,
+    mut console_query: Query<&mut Visibility, With<ConsoleUi>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    let typed: String = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|character| !character.is_control() && *character != '`')
+        .collect();
+
+    if keys.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+    } else if console.open && keys.just_pressed(KeyCode::Escape) {
+        // used up here so that closing the console doesn't also quit the game.
+        keys.clear_just_pressed(KeyCode::Escape);
+        console.open = false;
+    } else if console.open {
+        console.input.push_str(&typed);
+        if keys.just_pressed(KeyCode::Back) {
+            console.input.pop();
+        }
+        if keys.just_pressed(KeyCode::Up) {
+            console.history_back();
+        }
+        if keys.just_pressed(KeyCode::Down) {
+            console.history_forward();
+        }
+        if keys.just_pressed(KeyCode::Tab) {
+            console.autocomplete();
+        }
+        if keys.just_pressed(KeyCode::Return) {
+            let line = console.submit();
+            if !line.is_empty() {
+                match parse_command(&line) {
+                    Ok(ConsoleCommand::Help) => {
+                        for (name, arguments) in COMMANDS {
+                            console.print(format!("{} {}", name, arguments));
+                        }
+                    }
+                    Ok(ConsoleCommand::Clear) => console.output.clear(),
+                    Ok(command) => command_events.send(ConsoleCommandEvent { command }),
+                    Err(error) => console.print(error),
+                }
+            }
+        }
+    }
+
+    if !console.is_changed() {
+        return;
+    }
+    for mut visibility in console_query.iter_mut() {
+        visibility.is_visible = console.open;
+    }
+    let mut value = String::new();
+    for line in console.output.iter() {
+        value.push_str(line);
+        value.push('\n');
+    }
+    value.push_str(&format!("> {}_", console.input));
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// Runs the commands entered in the console and prints what they did.
+#[allow(clippy::too_many_arguments)]
+pub fn console_command_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut console: ResMut<Console>,
+    mut command_events: EventReader<ConsoleCommandEvent>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+    mut impact_effects: ResMut<ImpactEffects>,
+    mut ai_control: ResMut<AiControl>,
+    mut combine_query: Query<(Entity, &mut Combine, &mut Velocity, Option<&Invulnerable>)>,
+    mut transform_query: Query<&mut Transform, With<RigidBody>>,
+    steering_wheel_query: Query<(Entity, &SteeringWheel)>,
+    wheel_query: Query<(&Wheel, &Parent)>,
+) {
+    for event in command_events.iter() {
+        let player = combine_query
+            .iter()
+            .find(|(_, combine, ..)| combine.combine_id == PLAYER_COMBINE_ID)
+            .map(|(entity, ..)| entity);
+        let player_transform = player
+            .and_then(|entity| transform_query.get(entity).ok())
+            .copied()
+            .unwrap_or_default();
+        let in_front = player_transform.translation + (player_transform.forward() * SPAWN_DISTANCE);
+
+        match &event.command {
+            ConsoleCommand::SpawnCow(position) => {
+                let position = position.unwrap_or(in_front + (Vec3::Y * COW_DROP_HEIGHT));
+                commands = spawn_cow_with_transform(
+                    commands,
+                    Transform::from_translation(position),
+                    asset_server.load("cow.glb#Scene0"),
+                );
+                console.print(format!("spawned a cow at {}", format_position(position)));
+            }
+            ConsoleCommand::SpawnCombine(spec_path, position) => {
+                let spec = match spec_path {
+                    Some(spec_path) => match CombineSpec::load(spec_path) {
+                        Ok(spec) => spec,
+                        Err(error) => {
+                            console.print(error);
+                            continue;
+                        }
+                    },
+                    None => CombineSpec::default(),
+                };
+                let position = position.unwrap_or(in_front);
+                let combine_id = combine_query
+                    .iter()
+                    .map(|(_, combine, ..)| combine.combine_id)
+                    .max()
+                    .unwrap_or(PLAYER_COMBINE_ID)
+                    + 1;
+                // face the same way as the player.
+                let forward = player_transform.forward();
+                let yaw = (-forward.x).atan2(-forward.z);
+                commands = create_combine(
+                    commands,
+                    &asset_server,
+                    combine_id,
+                    Transform::from_translation(position).with_rotation(Quat::from_rotation_y(yaw)),
+                    &spec,
+                    false,
+                );
+                console.print(format!(
+                    "spawned combine {} at {}",
+                    combine_id,
+                    format_position(position)
+                ));
+            }
+            ConsoleCommand::SetGravity(gravity) => {
+                rapier_configuration.gravity = *gravity;
+                console.print(format!("gravity is {}", format_position(*gravity)));
+            }
+            ConsoleCommand::Teleport(position) => {
+                let player = match player {
+                    Some(player) => player,
+                    None => {
+                        console.print("there is no player combine");
+                        continue;
+                    }
+                };
+                // the wheels and steering racks are bodies of their own, so they move too.
+                let offset = *position - player_transform.translation;
+                let parts = steering_wheel_query
+                    .iter()
+                    .filter(|(_, steering_wheel)| steering_wheel.combine_id == PLAYER_COMBINE_ID)
+                    .map(|(entity, _)| entity)
+                    .chain(
+                        wheel_query
+                            .iter()
+                            .filter(|(wheel, _)| wheel.combine_id == PLAYER_COMBINE_ID)
+                            .map(|(_, parent)| parent.get()),
+                    )
+                    .chain([player]);
+                for part in parts.collect::<Vec<Entity>>() {
+                    if let Ok(mut transform) = transform_query.get_mut(part) {
+                        transform.translation += offset;
+                    }
+                }
+                if let Ok((_, _, mut velocity, _)) = combine_query.get_mut(player) {
+                    *velocity = Velocity::zero();
+                }
+                console.print(format!("teleported to {}", format_position(*position)));
+            }
+            ConsoleCommand::God(switch) => {
+                let player = combine_query
+                    .iter_mut()
+                    .find(|(_, combine, ..)| combine.combine_id == PLAYER_COMBINE_ID);
+                let (entity, mut combine, _, invulnerable) = match player {
+                    Some(player) => player,
+                    None => {
+                        console.print("there is no player combine");
+                        continue;
+                    }
+                };
+                if switch.unwrap_or(invulnerable.is_none()) {
+                    combine.health = COMBINE_MAX_HEALTH;
+                    commands.entity(entity).insert(Invulnerable);
+                    console.print("god mode on");
+                } else {
+                    commands.entity(entity).remove::<Invulnerable>();
+                    console.print("god mode off");
+                }
+            }
+            ConsoleCommand::TimeScale(scale) => {
+                impact_effects.time_scale = *scale;
+                set_physics_time_scale(&mut rapier_configuration, *scale);
+                console.print(format!("physics time scale is {}", scale));
+            }
+            ConsoleCommand::Ai(on) => {
+                ai_control.enabled = *on;
+                console.print(if *on { "ai on" } else { "ai off" });
+            }
+            ConsoleCommand::Help | ConsoleCommand::Clear => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::console::{complete, parse_command, Console, ConsoleCommand};
+
+    #[test]
+    fn parse_command_test() {
+        assert_eq!(
+            Ok(ConsoleCommand::SpawnCow(Some(Vec3::new(1.0, 2.5, -3.0)))),
+            parse_command("spawn cow 1 2.5 -3")
+        );
+        assert_eq!(
+            Ok(ConsoleCommand::SpawnCombine(None, None)),
+            parse_command("  spawn  combine ")
+        );
+        assert_eq!(
+            Ok(ConsoleCommand::SpawnCombine(
+                Some("Heavy.spec".to_string()),
+                Some(Vec3::new(0.0, 5.0, 10.0))
+            )),
+            parse_command("spawn combine Heavy.spec 0 5 10")
+        );
+        assert_eq!(
+            Ok(ConsoleCommand::SetGravity(Vec3::new(0.0, -3.7, 0.0))),
+            parse_command("set gravity 3.7")
+        );
+        assert_eq!(Ok(ConsoleCommand::God(None)), parse_command("god"));
+        assert_eq!(Ok(ConsoleCommand::Ai(false)), parse_command("ai off"));
+        assert_eq!(
+            Ok(ConsoleCommand::TimeScale(0.5)),
+            parse_command("timescale 0.5")
+        );
+    }
+
+    #[test]
+    fn parse_command_errors_test() {
+        assert_eq!(
+            Err("expected x y z\nusage: tp <x y z>".to_string()),
+            parse_command("tp 1 2")
+        );
+        assert_eq!(
+            Err("'fast' is not a number\nusage: timescale <scale>".to_string()),
+            parse_command("timescale fast")
+        );
+        assert!(parse_command("timescale 0").is_err());
+        assert_eq!(
+            Err("unknown command 'fly', try help".to_string()),
+            parse_command("fly")
+        );
+    }
+
+    #[test]
+    fn complete_test() {
+        assert_eq!(
+            ("timescale ".to_string(), vec!["timescale"]),
+            complete("ti")
+        );
+        assert_eq!(
+            ("spawn co".to_string(), vec!["spawn cow", "spawn combine"]),
+            complete("sp")
+        );
+        assert_eq!(("xyz".to_string(), Vec::<&str>::new()), complete("xyz"));
+        assert_eq!(
+            ("god on".to_string(), Vec::<&str>::new()),
+            complete("god on")
+        );
+    }
+
+    #[test]
+    fn history_test() {
+        let mut console = Console::default();
+        for line in ["god", "ai off", "ai off"] {
+            console.input = line.to_string();
+            console.submit();
+        }
+        assert_eq!(vec!["god", "ai off"], console.history);
+
+        console.history_back();
+        assert_eq!("ai off", console.input);
+        console.history_back();
+        console.history_back();
+        assert_eq!("god", console.input);
+        console.history_forward();
+        assert_eq!("ai off", console.input);
+        console.history_forward();
+        assert_eq!("", console.input);
+    }
+}
@@ -1,39 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bevy::prelude::*;
 use bevy_rapier3d::{prelude::*, rapier::prelude::JointAxis};
 
 use crate::{
-    combine::{DrivingWheel, SteeringWheel, SteeringWheelPosition, PLAYER_COMBINE_ID},
-    events::{
-        SoundSampleEvent, SpeedControlAction, SpeedControlEvent, SteerControlAction,
-        SteerControlEvent,
-    },
+    combine::{Combine, CombineSpec, DrivingWheel, SteeringWheel, SteeringWheelPosition},
+    engine::Engine,
+    events::{SpeedControlAction, SpeedControlEvent, SteerControlAction, SteerControlEvent},
 };
 
 pub fn speed_control_events(
     mut speed_control_events: EventReader<SpeedControlEvent>,
-    mut sound_sample_events: EventWriter<SoundSampleEvent>,
     mut query: Query<(&DrivingWheel, &mut MultibodyJoint)>,
+    mut engine_query: Query<(&Combine, &mut Engine, &Velocity, &Transform)>,
 ) {
-    let factor = 0.1;
-
     let mut control_map = HashMap::new();
     for event in speed_control_events.iter() {
         control_map.insert(event.combine_id, event.action.clone());
+    }
 
-        if event.combine_id == PLAYER_COMBINE_ID {
-            if event.action == SpeedControlAction::NoPower {
-                sound_sample_events.send(SoundSampleEvent::NoEnginePower);
-            } else {
-                sound_sample_events.send(SoundSampleEvent::EnginePower);
+    let mut braking_combines = HashSet::new();
+    for (combine, mut engine, velocity, transform) in engine_query.iter_mut() {
+        if let Some(action) = control_map.get(&combine.combine_id) {
+            let forward_speed = velocity.linvel.dot(transform.forward());
+            engine.select_direction(action, forward_speed);
+            if engine.braking {
+                braking_combines.insert(combine.combine_id);
             }
         }
     }
 
     for (driving_wheel, mut joint) in query.iter_mut() {
         let target_velocity = driving_wheel.target_velocity;
-        if let Some(action) = control_map.get(&driving_wheel.combine_id) {
+        let factor = driving_wheel.motor_factor;
+        if braking_combines.contains(&driving_wheel.combine_id) {
+            joint.data.set_motor_velocity(JointAxis::AngX, 0.0, factor);
+        } else if let Some(action) = control_map.get(&driving_wheel.combine_id) {
             match action {
                 SpeedControlAction::Forward => {
                     joint
@@ -44,7 +46,7 @@ pub fn speed_control_events(
                 SpeedControlAction::Back => {
                     joint
                         .data
-                        .set_motor_velocity(JointAxis::AngX, target_velocity / 2.0, factor);
+                        .set_motor_velocity(JointAxis::AngX, target_velocity, factor);
                     joint.data.set_limits(JointAxis::AngX, [f32::MIN, f32::MAX]);
                 }
                 SpeedControlAction::NoPower => {
@@ -58,8 +60,14 @@ pub fn speed_control_events(
 pub fn steer_control_events(
     mut steer_control_events: EventReader<SteerControlEvent>,
     mut query: Query<(&SteeringWheel, &mut MultibodyJoint)>,
+    combine_query: Query<(&Combine, &CombineSpec)>,
 ) {
     let mut control_map = HashMap::new();
+    let specs: HashMap<i32, &CombineSpec> = combine_query
+        .iter()
+        .map(|(combine, spec)| (combine.combine_id, spec))
+        .collect();
+    let default_spec = CombineSpec::default();
 
     let angle = 25.0_f32.to_radians();
     let straight = 0.0_f32.to_radians();
@@ -70,6 +78,11 @@ pub fn steer_control_events(
 
     for (steering_wheel, mut joint) in query.iter_mut() {
         if let Some(action) = control_map.get(&steering_wheel.combine_id) {
+            let spec = specs
+                .get(&steering_wheel.combine_id)
+                .copied()
+                .unwrap_or(&default_spec);
+            let (stiffness, damping) = (spec.steering_stiffness, spec.steering_damping);
             let mut adjusted_angle = angle;
             if steering_wheel.steering_wheel_position == SteeringWheelPosition::Left {
                 adjusted_angle = calc_left_angle(
@@ -88,19 +101,19 @@ pub fn steer_control_events(
                 SteerControlAction::Left => {
                     joint
                         .data
-                        .set_motor_position(JointAxis::AngX, -adjusted_angle, 1.0, 0.5)
+                        .set_motor_position(JointAxis::AngX, -adjusted_angle, stiffness, damping)
                         .set_limits(JointAxis::AngX, [-adjusted_angle, -adjusted_angle]);
                 }
                 SteerControlAction::NoSteer => {
                     joint
                         .data
-                        .set_motor_position(JointAxis::AngX, straight, 1.0, 0.5)
+                        .set_motor_position(JointAxis::AngX, straight, stiffness, damping)
                         .set_limits(JointAxis::AngX, [straight, straight]);
                 }
                 SteerControlAction::Right => {
                     joint
                         .data
-                        .set_motor_position(JointAxis::AngX, adjusted_angle, 1.0, 0.5)
+                        .set_motor_position(JointAxis::AngX, adjusted_angle, stiffness, damping)
                         .set_limits(JointAxis::AngX, [adjusted_angle, adjusted_angle]);
                 }
             }
@@ -0,0 +1,310 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{
+    combine::Combine,
+    events::{GearShiftAction, GearShiftEvent, SpeedControlAction},
+};
+
+pub const IDLE_RPM: f32 = 800.0;
+pub const REDLINE_RPM: f32 = 2600.0;
+const SHIFT_UP_RPM: f32 = 2250.0;
+const SHIFT_DOWN_RPM: f32 = 1200.0;
+/// Engine speed reached with the clutch slipping while pulling away from a standstill.
+const CLUTCH_SLIP_RPM: f32 = 1500.0;
+/// How quickly the engine speed follows the wheels, per second.
+const RPM_RESPONSE: f32 = 8.0;
+/// Time the clutch is disengaged while changing gear.
+const SHIFT_SECONDS: f32 = 0.3;
+/// Ground speed below which the combine counts as stopped, so it can change direction.
+const STOPPED_SPEED: f32 = 0.5;
+
+/// Forward gear ratios, from first gear to top gear.
+const GEAR_RATIOS: [f32; 5] = [5.0, 3.2, 2.1, 1.4, 1.0];
+const REVERSE_RATIO: f32 = 5.0;
+/// Ratio between the gearbox output and the wheels. Gives a top speed of 40 m/s at the redline.
+const FINAL_DRIVE_RATIO: f32 = 13.6;
+const WHEEL_RADIUS: f32 = 2.0;
+
+/// Fraction of peak torque available at engine speeds, as (rpm, fraction) pairs.
+const TORQUE_CURVE: [(f32, f32); 5] = [
+    (IDLE_RPM, 0.6),
+    (1400.0, 1.0),
+    (1900.0, 0.95),
+    (REDLINE_RPM, 0.7),
+    (REDLINE_RPM + 100.0, 0.0),
+];
+/// Fastest the engine can turn, at the end of the torque curve.
+const MAX_RPM: f32 = TORQUE_CURVE[TORQUE_CURVE.len() - 1].0;
+
+/// A diesel engine and gearbox. Engine speed follows the wheels through the current gear.
+#[derive(Component)]
+pub struct Engine {
+    pub rpm: f32,
+    /// Current forward gear, starting from 1.
+    pub gear: usize,
+    pub automatic: bool,
+    /// How hard the accelerator is pressed, from 0 to 1.
+    pub throttle: f32,
+    pub reversing: bool,
+    /// Whether the wheels are being slowed to a stop before changing direction.
+    pub braking: bool,
+    shift_timer: Timer,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        let mut shift_timer = Timer::from_seconds(SHIFT_SECONDS, TimerMode::Once);
+        shift_timer.tick(shift_timer.duration());
+        Engine {
+            rpm: IDLE_RPM,
+            gear: 1,
+            automatic: true,
+            throttle: 0.0,
+            reversing: false,
+            braking: false,
+            shift_timer,
+        }
+    }
+}
+
+impl Engine {
+    pub fn top_gear() -> usize {
+        GEAR_RATIOS.len()
+    }
+
+    /// Whether the clutch is disengaged for a gear change.
+    pub fn shifting(&self) -> bool {
+        !self.shift_timer.finished()
+    }
+
+    pub fn gear_ratio(&self) -> f32 {
+        if self.reversing {
+            REVERSE_RATIO
+        } else {
+            GEAR_RATIOS[self.gear - 1]
+        }
+    }
+
+    /// Fraction of peak torque the engine makes at its current speed.
+    pub fn torque_fraction(&self) -> f32 {
+        torque_fraction(self.rpm)
+    }
+
+    /// Wheel angular velocity, in radians per second, with the engine at the redline in the
+    /// current gear.
+    pub fn redline_wheel_velocity(&self) -> f32 {
+        rpm_to_radians_per_second(REDLINE_RPM) / (self.gear_ratio() * FINAL_DRIVE_RATIO)
+    }
+
+    /// Follows the driver asking to go forwards, backwards or neither. Asking for the other
+    /// direction brakes until the combine has about stopped, and only then changes into or out of
+    /// reverse. Forward speed is negative when rolling backwards.
+    pub fn select_direction(&mut self, action: &SpeedControlAction, forward_speed: f32) {
+        match action {
+            SpeedControlAction::Forward => {
+                self.braking = self.reversing && forward_speed < -STOPPED_SPEED;
+                self.reversing = self.braking;
+            }
+            SpeedControlAction::Back => {
+                self.braking = !self.reversing && forward_speed > STOPPED_SPEED;
+                self.reversing = !self.braking;
+            }
+            SpeedControlAction::NoPower => self.braking = false,
+        }
+        self.throttle = if *action == SpeedControlAction::NoPower || self.braking {
+            0.0
+        } else {
+            1.0
+        };
+    }
+
+    pub fn shift(&mut self, gear: usize) {
+        let gear = gear.clamp(1, Engine::top_gear());
+        if gear != self.gear {
+            self.gear = gear;
+            self.shift_timer.reset();
+        }
+    }
+}
+
+/// Gets the fraction of peak torque at an engine speed by interpolating the torque curve.
+pub fn torque_fraction(rpm: f32) -> f32 {
+    if rpm <= TORQUE_CURVE[0].0 {
+        return TORQUE_CURVE[0].1;
+    }
+    for pair in TORQUE_CURVE.windows(2) {
+        let (low_rpm, low_torque) = pair[0];
+        let (high_rpm, high_torque) = pair[1];
+        if rpm <= high_rpm {
+            let t = (rpm - low_rpm) / (high_rpm - low_rpm);
+            return low_torque + ((high_torque - low_torque) * t);
+        }
+    }
+    0.0
+}
+
+/// Gets the engine speed needed to drive the wheels at a ground speed through a gear ratio.
+pub fn speed_to_rpm(speed: f32, gear_ratio: f32) -> f32 {
+    let wheel_radians_per_second = speed / WHEEL_RADIUS;
+    radians_per_second_to_rpm(wheel_radians_per_second * gear_ratio * FINAL_DRIVE_RATIO)
+}
+
+/// Gets the engine speed the wheels turn the engine at through a gear ratio, kept within the
+/// torque curve.
+fn wheel_rpm(speed: f32, gear_ratio: f32) -> f32 {
+    speed_to_rpm(speed, gear_ratio).clamp(IDLE_RPM, MAX_RPM)
+}
+
+/// Picks the gear an automatic gearbox wants at an engine speed.
+pub fn automatic_gear(gear: usize, rpm: f32) -> usize {
+    if rpm > SHIFT_UP_RPM && gear < Engine::top_gear() {
+        gear + 1
+    } else if rpm < SHIFT_DOWN_RPM && gear > 1 {
+        gear - 1
+    } else {
+        gear
+    }
+}
+
+fn rpm_to_radians_per_second(rpm: f32) -> f32 {
+    rpm * 2.0 * PI / 60.0
+}
+
+fn radians_per_second_to_rpm(radians_per_second: f32) -> f32 {
+    radians_per_second * 60.0 / (2.0 * PI)
+}
+
+/// Handles gear changes and follows the wheels with the engine speed.
+pub fn engine_system(
+    time: Res<Time>,
+    mut gear_shift_events: EventReader<GearShiftEvent>,
+    mut combine_query: Query<(&Combine, &mut Engine)>,
+) {
+    let gear_shift_events: Vec<&GearShiftEvent> = gear_shift_events.iter().collect();
+
+    for (combine, mut engine) in combine_query.iter_mut() {
+        engine.shift_timer.tick(time.delta());
+
+        for event in gear_shift_events.iter() {
+            if event.combine_id != combine.combine_id {
+                continue;
+            }
+            match event.action {
+                GearShiftAction::Up => {
+                    let gear = engine.gear + 1;
+                    engine.shift(gear);
+                }
+                GearShiftAction::Down => {
+                    let gear = engine.gear.saturating_sub(1);
+                    engine.shift(gear);
+                }
+                GearShiftAction::ToggleAutomatic => {
+                    engine.automatic = !engine.automatic;
+                }
+            }
+        }
+
+        let mut target_rpm = wheel_rpm(combine.velocity, engine.gear_ratio());
+        if engine.shifting() {
+            // the clutch is out, so the engine drops back towards idle.
+            target_rpm = IDLE_RPM + ((target_rpm - IDLE_RPM) * 0.5);
+        } else if engine.throttle > 0.0 {
+            target_rpm =
+                target_rpm.max(IDLE_RPM + ((CLUTCH_SLIP_RPM - IDLE_RPM) * engine.throttle));
+        }
+
+        let response = (RPM_RESPONSE * time.delta_seconds()).min(1.0);
+        engine.rpm += (target_rpm - engine.rpm) * response;
+
+        if engine.automatic && !engine.reversing && !engine.shifting() {
+            let gear = automatic_gear(engine.gear, engine.rpm);
+            engine.shift(gear);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::{
+            automatic_gear, speed_to_rpm, torque_fraction, wheel_rpm, Engine, IDLE_RPM, MAX_RPM,
+            REDLINE_RPM, REVERSE_RATIO,
+        },
+        events::SpeedControlAction,
+    };
+
+    #[test]
+    fn torque_curve_peak_test() {
+        assert!((torque_fraction(1400.0) - 1.0).abs() < 0.001);
+        assert_eq!(0.6, torque_fraction(0.0));
+        assert_eq!(0.0, torque_fraction(REDLINE_RPM + 500.0));
+    }
+
+    #[test]
+    fn torque_curve_interpolates_test() {
+        let torque = torque_fraction(1100.0);
+
+        assert!((torque - 0.8).abs() < 0.001);
+    }
+
+    #[test]
+    fn top_gear_redline_speed_test() {
+        let rpm = speed_to_rpm(40.0, 1.0);
+
+        assert!((rpm - REDLINE_RPM).abs() < 10.0);
+    }
+
+    #[test]
+    fn automatic_gear_test() {
+        assert_eq!(2, automatic_gear(1, 2400.0));
+        assert_eq!(1, automatic_gear(1, IDLE_RPM));
+        assert_eq!(2, automatic_gear(3, 1000.0));
+        assert_eq!(
+            Engine::top_gear(),
+            automatic_gear(Engine::top_gear(), 2500.0)
+        );
+    }
+
+    #[test]
+    fn shift_clamps_gear_test() {
+        let mut engine = Engine::default();
+
+        engine.shift(0);
+        assert_eq!(1, engine.gear);
+        assert!(!engine.shifting());
+
+        engine.shift(10);
+        assert_eq!(Engine::top_gear(), engine.gear);
+        assert!(engine.shifting());
+    }
+
+    #[test]
+    fn back_brakes_before_reversing_test() {
+        let mut engine = Engine::default();
+        engine.shift(3);
+
+        engine.select_direction(&SpeedControlAction::Back, 15.0);
+        assert!(engine.braking && !engine.reversing);
+        assert_eq!(0.0, engine.throttle);
+        // still in the forward gear, so the engine isn't thrown past the redline.
+        assert!(torque_fraction(wheel_rpm(15.0, engine.gear_ratio())) > 0.0);
+
+        engine.select_direction(&SpeedControlAction::Back, 0.2);
+        assert!(!engine.braking && engine.reversing);
+        assert_eq!(1.0, engine.throttle);
+        assert!(torque_fraction(wheel_rpm(0.2, engine.gear_ratio())) >= 0.6);
+
+        engine.select_direction(&SpeedControlAction::Forward, -5.0);
+        assert!(engine.braking && engine.reversing);
+        engine.select_direction(&SpeedControlAction::Forward, 0.0);
+        assert!(!engine.braking && !engine.reversing);
+    }
+
+    #[test]
+    fn wheel_rpm_stays_on_torque_curve_test() {
+        assert_eq!(MAX_RPM, wheel_rpm(20.0, REVERSE_RATIO));
+        assert_eq!(IDLE_RPM, wheel_rpm(0.0, REVERSE_RATIO));
+    }
+}
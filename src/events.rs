@@ -1,3 +1,5 @@
+use bevy::prelude::*;
+
 pub struct SpeedControlEvent {
     pub combine_id: i32,
     pub action: SpeedControlAction,
@@ -22,10 +24,41 @@ pub enum SteerControlAction {
     Right,
 }
 
+pub struct GearShiftEvent {
+    pub combine_id: i32,
+    pub action: GearShiftAction,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum GearShiftAction {
+    Up,
+    Down,
+    ToggleAutomatic,
+}
+
+/// Asks for a combine to boost. Sent every frame the boost is wanted.
+pub struct BoostControlEvent {
+    pub combine_id: i32,
+}
+
+/// Asks for a sample to be played at a position in the arena.
+pub struct SoundSampleEvent {
+    pub sample: SoundSampleKind,
+    pub position: Vec3,
+    /// What made the sound. Each source has its own cooldown, so one noisy cow doesn't stop the
+    /// others mooing.
+    pub source: Entity,
+    /// Contact force of the impact that made the sound. Sounds not made by an impact play at
+    /// full volume.
+    pub force: Option<f32>,
+}
+
 #[derive(Clone)]
-pub enum SoundSampleEvent {
+pub enum SoundSampleKind {
     HayBale,
     Cow,
-    EnginePower,
-    NoEnginePower,
+    EngineSurge,
+    /// Two combines crashing into each other.
+    Crash,
+    Fence,
 }
@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+/// The rules the match is played under.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    /// Every combine for itself.
+    Derby,
+    /// Each combine pushes cows into its own pen before time runs out.
+    Herding,
+}
+
+impl GameMode {
+    pub fn parse(value: &str) -> Option<GameMode> {
+        match value.to_lowercase().as_str() {
+            "derby" => Some(GameMode::Derby),
+            "herding" => Some(GameMode::Herding),
+            _ => None,
+        }
+    }
+
+    /// Reads the mode from `--mode <derby|herding>` on the command line, defaulting to derby.
+    pub fn from_args() -> GameMode {
+        let args: Vec<String> = std::env::args().collect();
+        args.iter()
+            .position(|arg| arg == "--mode")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|value| GameMode::parse(value))
+            .unwrap_or(GameMode::Derby)
+    }
+}
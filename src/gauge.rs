@@ -0,0 +1,280 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+/// Angle the needle swings through from zero to the top of the dial, clockwise.
+const SWEEP: f32 = PI * 1.5;
+const FACE_COLOUR: Color = Color::rgba(0.05, 0.05, 0.05, 0.7);
+const TICK_COLOUR: Color = Color::WHITE;
+const RED_ZONE_COLOUR: Color = Color::rgb(0.9, 0.15, 0.1);
+const NEEDLE_COLOUR: Color = Color::rgb(1.0, 0.35, 0.1);
+const MAJOR_TICK_LENGTH: f32 = 12.0;
+const MINOR_TICK_LENGTH: f32 = 6.0;
+const TICK_WIDTH: f32 = 3.0;
+const NEEDLE_WIDTH: f32 = 4.0;
+
+/// How a dial gauge is marked out.
+pub struct GaugeSpec {
+    /// Shown in the middle of the dial.
+    pub title: String,
+    /// Shown after the value in the readout below the needle.
+    pub unit: String,
+    pub max: f32,
+    /// Gap between numbered ticks. A smaller tick sits halfway between each.
+    pub step: f32,
+    /// Numbers on the dial are the value divided by this, to keep them short.
+    pub label_divisor: f32,
+    /// Ticks from this value up are drawn in red.
+    pub red_from: Option<f32>,
+    /// Width and height of the dial, in pixels.
+    pub size: f32,
+}
+
+/// A dial with a needle pointing at its value, and the value written underneath.
+#[derive(Component)]
+pub struct Gauge {
+    pub value: f32,
+    max: f32,
+    unit: String,
+    needle: Entity,
+    readout: Entity,
+}
+
+/// Gets the needle's angle clockwise from straight up, with zero at the bottom left of the dial.
+fn needle_angle(value: f32, max: f32) -> f32 {
+    let fraction = if max > 0.0 {
+        (value / max).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (SWEEP * fraction) - (SWEEP * 0.5)
+}
+
+/// Gets where a point at an angle and distance from the centre of a dial is, from the top left
+/// of the dial.
+fn dial_point(size: f32, angle: f32, radius: f32) -> Vec2 {
+    let centre = size * 0.5;
+    Vec2::new(
+        centre + (radius * angle.sin()),
+        centre - (radius * angle.cos()),
+    )
+}
+
+/// A node covering the whole dial, so rotating it turns its children around the dial's centre.
+fn dial_layer(size: f32, angle: f32) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(0.0),
+                top: Val::Px(0.0),
+                ..default()
+            },
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            ..default()
+        },
+        background_color: Color::NONE.into(),
+        transform: Transform::from_rotation(Quat::from_rotation_z(angle)),
+        ..default()
+    }
+}
+
+/// A line hanging down from the top middle of a dial layer.
+fn dial_line(size: f32, width: f32, top: f32, length: f32, colour: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px((size - width) * 0.5),
+                top: Val::Px(top),
+                ..default()
+            },
+            size: Size::new(Val::Px(width), Val::Px(length)),
+            ..default()
+        },
+        background_color: colour.into(),
+        ..default()
+    }
+}
+
+/// A full width row at a height on the dial, to centre text in.
+fn dial_text_row(size: f32, top: f32) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(0.0),
+                top: Val::Px(top),
+                ..default()
+            },
+            size: Size::new(Val::Px(size), Val::Auto),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        background_color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+/// Adds a dial gauge, with anything else the caller wants on it, such as a marker to find it by.
+pub fn spawn_gauge(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    spec: GaugeSpec,
+    bundle: impl Bundle,
+) {
+    let size = spec.size;
+    let text_style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    let mut needle = Entity::from_raw(0);
+    let mut readout = Entity::from_raw(0);
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(size), Val::Px(size)),
+                margin: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: FACE_COLOUR.into(),
+            ..default()
+        })
+        .with_children(|dial| {
+            let tick_count = (spec.max / (spec.step * 0.5)).round() as i32;
+            for tick in 0..=tick_count {
+                let value = tick as f32 * spec.step * 0.5;
+                let angle = needle_angle(value, spec.max);
+                let major = tick % 2 == 0;
+                let colour = match spec.red_from {
+                    Some(red_from) if value >= red_from => RED_ZONE_COLOUR,
+                    _ => TICK_COLOUR,
+                };
+                let length = if major {
+                    MAJOR_TICK_LENGTH
+                } else {
+                    MINOR_TICK_LENGTH
+                };
+                dial.spawn(dial_layer(size, angle)).with_children(|layer| {
+                    layer.spawn(dial_line(size, TICK_WIDTH, 4.0, length, colour));
+                });
+
+                if major {
+                    let font_size = size * 0.09;
+                    let label_position =
+                        dial_point(size, angle, (size * 0.5) - MAJOR_TICK_LENGTH - font_size);
+                    let label = value / spec.label_divisor;
+                    dial.spawn(
+                        TextBundle::from_section(format!("{}", label), text_style(font_size))
+                            .with_style(Style {
+                                position_type: PositionType::Absolute,
+                                position: UiRect {
+                                    left: Val::Px(label_position.x - (font_size * 0.5)),
+                                    top: Val::Px(label_position.y - (font_size * 0.6)),
+                                    ..default()
+                                },
+                                ..default()
+                            }),
+                    );
+                }
+            }
+
+            dial.spawn(dial_text_row(size, size * 0.3))
+                .with_children(|row| {
+                    row.spawn(TextBundle::from_section(
+                        spec.title.clone(),
+                        text_style(size * 0.08),
+                    ));
+                });
+            dial.spawn(dial_text_row(size, size * 0.7))
+                .with_children(|row| {
+                    readout = row
+                        .spawn(TextBundle::from_section("", text_style(size * 0.12)))
+                        .id();
+                });
+
+            needle = dial
+                .spawn(dial_layer(size, needle_angle(0.0, spec.max)))
+                .with_children(|layer| {
+                    layer.spawn(dial_line(
+                        size,
+                        NEEDLE_WIDTH,
+                        size * 0.1,
+                        size * 0.4,
+                        NEEDLE_COLOUR,
+                    ));
+                })
+                .id();
+            let hub_size = NEEDLE_WIDTH * 3.0;
+            dial.spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px((size - hub_size) * 0.5),
+                        top: Val::Px((size - hub_size) * 0.5),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(hub_size), Val::Px(hub_size)),
+                    ..default()
+                },
+                background_color: NEEDLE_COLOUR.into(),
+                ..default()
+            });
+        })
+        .insert(Gauge {
+            value: 0.0,
+            max: spec.max,
+            unit: spec.unit,
+            needle,
+            readout,
+        })
+        .insert(bundle);
+}
+
+/// Turns the needles of gauges whose value has changed, and updates their readouts.
+pub fn gauge_system(
+    gauge_query: Query<&Gauge, Changed<Gauge>>,
+    mut needle_query: Query<&mut Transform>,
+    mut text_query: Query<&mut Text>,
+) {
+    for gauge in gauge_query.iter() {
+        if let Ok(mut transform) = needle_query.get_mut(gauge.needle) {
+            transform.rotation = Quat::from_rotation_z(needle_angle(gauge.value, gauge.max));
+        }
+        if let Ok(mut text) = text_query.get_mut(gauge.readout) {
+            text.sections[0].value = format!("{:.0} {}", gauge.value, gauge.unit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::gauge::{dial_point, needle_angle, SWEEP};
+
+    #[test]
+    fn needle_sweeps_across_dial_test() {
+        assert!((needle_angle(0.0, 100.0) + (SWEEP * 0.5)).abs() < 0.001);
+        assert!(needle_angle(50.0, 100.0).abs() < 0.001);
+        assert!((needle_angle(100.0, 100.0) - (SWEEP * 0.5)).abs() < 0.001);
+    }
+
+    #[test]
+    fn needle_stops_at_ends_test() {
+        assert_eq!(needle_angle(0.0, 100.0), needle_angle(-20.0, 100.0));
+        assert_eq!(needle_angle(100.0, 100.0), needle_angle(250.0, 100.0));
+        assert_eq!(needle_angle(0.0, 100.0), needle_angle(10.0, 0.0));
+    }
+
+    #[test]
+    fn dial_point_test() {
+        let top = dial_point(100.0, 0.0, 40.0);
+        assert!((top.x - 50.0).abs() < 0.001 && (top.y - 10.0).abs() < 0.001);
+
+        let right = dial_point(100.0, FRAC_PI_2, 40.0);
+        assert!((right.x - 90.0).abs() < 0.001 && (right.y - 50.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,275 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Distance between the centres of neighbouring hay bales.
+pub const HAY_BALE_DIMENSION: f32 = 9.5;
+/// Distance between the centres of stacked hay bales.
+const HAY_LAYER_HEIGHT: f32 = 8.0;
+/// Height of the centre of a bale resting on the ground.
+const HAY_FIRST_LAYER_HEIGHT: f32 = 3.5;
+const HAY_BALE_RADIUS: f32 = 4.5;
+
+/// The shape of a hay bale structure.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HayTemplate {
+    /// A block of bales, `columns` wide along X and `rows` deep along Z.
+    Grid {
+        columns: i32,
+        rows: i32,
+        layers: i32,
+    },
+    /// A square pyramid, with a base `layers` bales wide.
+    Pyramid { layers: i32 },
+    /// Bales spaced evenly around a circle.
+    Ring {
+        count: i32,
+        radius: f32,
+        layers: i32,
+    },
+    /// Steps of bales climbing towards +Z, `width` bales wide.
+    Staircase { steps: i32, width: i32 },
+    /// Single bales dropped at random within a circle.
+    Scattered { count: i32, radius: f32, seed: u64 },
+}
+
+/// A group of hay bales built from a template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HayStructure {
+    pub template: HayTemplate,
+    /// Where the structure stands. The origin is on the ground at the centre of the structure.
+    pub transform: Transform,
+    /// Whether the bales are laid out reflected across the structure's local Z axis, for
+    /// templates that aren't symmetrical.
+    pub mirrored: bool,
+}
+
+impl HayStructure {
+    pub fn new(template: HayTemplate, transform: Transform) -> HayStructure {
+        HayStructure {
+            template,
+            transform,
+            mirrored: false,
+        }
+    }
+
+    /// A single row of bales along X.
+    pub fn wall(length: i32, layers: i32, transform: Transform) -> HayStructure {
+        HayStructure::new(
+            HayTemplate::Grid {
+                columns: length,
+                rows: 1,
+                layers,
+            },
+            transform,
+        )
+    }
+
+    /// A single stack of bales.
+    pub fn tower(layers: i32, transform: Transform) -> HayStructure {
+        HayStructure::new(
+            HayTemplate::Grid {
+                columns: 1,
+                rows: 1,
+                layers,
+            },
+            transform,
+        )
+    }
+
+    pub fn pyramid(layers: i32, transform: Transform) -> HayStructure {
+        HayStructure::new(HayTemplate::Pyramid { layers }, transform)
+    }
+
+    /// Gets the transform of every bale in the structure, relative to the structure.
+    pub fn local_bale_transforms(&self) -> Vec<Transform> {
+        let mut positions = Vec::new();
+
+        match self.template {
+            HayTemplate::Grid {
+                columns,
+                rows,
+                layers,
+            } => {
+                for layer in 0..layers {
+                    positions.append(&mut layer_grid(columns, rows, layer, Vec2::ZERO));
+                }
+            }
+            HayTemplate::Pyramid { layers } => {
+                for layer in 0..layers {
+                    let width = layers - layer;
+                    positions.append(&mut layer_grid(width, width, layer, Vec2::ZERO));
+                }
+            }
+            HayTemplate::Ring {
+                count,
+                radius,
+                layers,
+            } => {
+                for layer in 0..layers {
+                    // offset alternate layers so they sit across the gaps below.
+                    let layer_offset = if layer % 2 == 0 {
+                        0.0
+                    } else {
+                        PI / count as f32
+                    };
+                    for i in 0..count {
+                        let angle = layer_offset + ((2.0 * PI * i as f32) / count as f32);
+                        positions.push(Vec3::new(
+                            radius * angle.cos(),
+                            layer_height(layer),
+                            radius * angle.sin(),
+                        ));
+                    }
+                }
+            }
+            HayTemplate::Staircase { steps, width } => {
+                for step in 0..steps {
+                    let z = (step as f32 - ((steps - 1) as f32 * 0.5)) * HAY_BALE_DIMENSION;
+                    for layer in 0..=step {
+                        positions.append(&mut layer_grid(width, 1, layer, Vec2::new(0.0, z)));
+                    }
+                }
+            }
+            HayTemplate::Scattered {
+                count,
+                radius,
+                seed,
+            } => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                for _ in 0..count {
+                    let angle = rng.gen_range(0.0..(2.0 * PI));
+                    let distance = radius * rng.gen_range(0.0_f32..1.0).sqrt();
+                    positions.push(Vec3::new(
+                        distance * angle.cos(),
+                        layer_height(0),
+                        distance * angle.sin(),
+                    ));
+                }
+            }
+        }
+
+        positions
+            .into_iter()
+            .map(|position| {
+                if self.mirrored {
+                    Vec3::new(-position.x, position.y, position.z)
+                } else {
+                    position
+                }
+            })
+            .map(Transform::from_translation)
+            .collect()
+    }
+
+    /// Gets the transform of every bale in the structure, in the world.
+    pub fn bale_transforms(&self) -> Vec<Transform> {
+        self.local_bale_transforms()
+            .into_iter()
+            .map(|bale_transform| self.transform * bale_transform)
+            .collect()
+    }
+
+    /// Gets the radius of a circle on the ground around the structure origin that covers
+    /// every bale.
+    pub fn footprint_radius(&self) -> f32 {
+        self.local_bale_transforms()
+            .iter()
+            .map(|bale_transform| {
+                Vec2::new(bale_transform.translation.x, bale_transform.translation.z).length()
+            })
+            .fold(0.0, f32::max)
+            + HAY_BALE_RADIUS
+    }
+}
+
+fn layer_height(layer: i32) -> f32 {
+    HAY_FIRST_LAYER_HEIGHT + (layer as f32 * HAY_LAYER_HEIGHT)
+}
+
+/// Gets the positions of a layer of bales laid out in a grid centred on `centre`.
+fn layer_grid(columns: i32, rows: i32, layer: i32, centre: Vec2) -> Vec<Vec3> {
+    let x_offset = centre.x - ((columns - 1) as f32 * 0.5 * HAY_BALE_DIMENSION);
+    let z_offset = centre.y - ((rows - 1) as f32 * 0.5 * HAY_BALE_DIMENSION);
+
+    let mut positions = Vec::new();
+    for x in 0..columns {
+        for z in 0..rows {
+            positions.push(Vec3::new(
+                x_offset + (x as f32 * HAY_BALE_DIMENSION),
+                layer_height(layer),
+                z_offset + (z as f32 * HAY_BALE_DIMENSION),
+            ));
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::hay_structure::{HayStructure, HayTemplate};
+
+    #[test]
+    fn pyramid_bale_count_test() {
+        let pyramid = HayStructure::pyramid(4, Transform::IDENTITY);
+
+        assert_eq!(30, pyramid.bale_transforms().len());
+    }
+
+    #[test]
+    fn grid_is_centred_test() {
+        let grid = HayStructure::new(
+            HayTemplate::Grid {
+                columns: 3,
+                rows: 2,
+                layers: 2,
+            },
+            Transform::IDENTITY,
+        );
+
+        let transforms = grid.bale_transforms();
+        let centre: Vec3 =
+            transforms.iter().map(|t| t.translation).sum::<Vec3>() / transforms.len() as f32;
+
+        assert_eq!(12, transforms.len());
+        assert!(centre.x.abs() < 0.001);
+        assert!(centre.z.abs() < 0.001);
+    }
+
+    #[test]
+    fn staircase_bale_count_test() {
+        let staircase = HayStructure::new(
+            HayTemplate::Staircase { steps: 3, width: 2 },
+            Transform::IDENTITY,
+        );
+
+        assert_eq!(12, staircase.bale_transforms().len());
+    }
+
+    #[test]
+    fn scattered_is_repeatable_test() {
+        let template = HayTemplate::Scattered {
+            count: 8,
+            radius: 30.0,
+            seed: 99,
+        };
+        let a = HayStructure::new(template.clone(), Transform::IDENTITY);
+        let b = HayStructure::new(template, Transform::IDENTITY);
+
+        assert_eq!(a.bale_transforms(), b.bale_transforms());
+        assert!(a.footprint_radius() <= 30.0 + 4.5 + 0.001);
+    }
+
+    #[test]
+    fn transform_moves_bales_test() {
+        let tower = HayStructure::tower(2, Transform::from_xyz(10.0, -2.0, 20.0));
+
+        let transforms = tower.bale_transforms();
+
+        assert_eq!(Vec3::new(10.0, 1.5, 20.0), transforms[0].translation);
+        assert_eq!(Vec3::new(10.0, 9.5, 20.0), transforms[1].translation);
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    arena::{spawn_pen, tiled_quad, PEN_FENCE_HEIGHT},
+    combine::{combine_colour, combine_name, Combine, Wheel, PLAYER_COMBINE_ID},
+    game_mode::GameMode,
+    layout::{ArenaLayout, TEAM_PEN_HALF_SIZE},
+    obstacle::Cow,
+};
+
+const HERDING_MATCH_SECONDS: f32 = 180.0;
+
+/// Contact force between a combine and a cow that counts as injuring the cow.
+const COW_INJURY_FORCE: f32 = 60000.0;
+const COW_INJURY_PENALTY: i32 = 1;
+/// Time before the same cow can cost a team another penalty, so one crash only counts once.
+const COW_INJURY_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// A goal area that one combine has to push cows into.
+#[derive(Component)]
+pub struct HerdingPen {
+    pub combine_id: i32,
+    pub transform: Transform,
+    pub half_size: f32,
+    /// Cows currently inside the pen.
+    pub cow_count: i32,
+    pub penalties: i32,
+}
+
+impl HerdingPen {
+    pub fn contains(&self, position: Vec3) -> bool {
+        let local_position = self
+            .transform
+            .compute_matrix()
+            .inverse()
+            .transform_point3(position);
+        local_position.x.abs() < self.half_size && local_position.z.abs() < self.half_size
+    }
+
+    pub fn score(&self) -> i32 {
+        self.cow_count - self.penalties
+    }
+}
+
+/// Progress of a herding match. Only present when playing [`GameMode::Herding`].
+#[derive(Resource)]
+pub struct HerdingMatch {
+    pub timer: Timer,
+    pub finished: bool,
+    /// When each cow last cost a team a penalty.
+    last_injuries: HashMap<Entity, Duration>,
+}
+
+#[derive(Component)]
+pub struct HerdingResultsUi;
+
+pub fn setup_herding(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    arena_layout: Res<ArenaLayout>,
+    game_mode: Res<GameMode>,
+) {
+    if *game_mode != GameMode::Herding {
+        return;
+    }
+
+    commands.insert_resource(HerdingMatch {
+        timer: Timer::from_seconds(HERDING_MATCH_SECONDS, TimerMode::Once),
+        finished: false,
+        last_injuries: HashMap::new(),
+    });
+
+    let fence_texture = asset_server.load("fence.png");
+    let fence_handle = meshes.add(tiled_quad(
+        Vec2::new(TEAM_PEN_HALF_SIZE * 2.0, PEN_FENCE_HEIGHT),
+        Vec2::new(3.0, 1.0),
+    ));
+    let floor_handle = meshes.add(Mesh::from(shape::Plane {
+        size: TEAM_PEN_HALF_SIZE * 2.0,
+    }));
+
+    for (i, team_pen) in arena_layout.team_pens.iter().enumerate() {
+        let combine_id = PLAYER_COMBINE_ID + i as i32;
+        let colour = combine_colour(combine_id);
+        let [r, g, b, _] = colour.as_rgba_f32();
+
+        let pen_transform = team_pen.transform();
+
+        let fence_material_handle = materials.add(StandardMaterial {
+            base_color: colour,
+            base_color_texture: Some(fence_texture.clone()),
+            perceptual_roughness: 1.0,
+            double_sided: true,
+            cull_mode: None,
+            ..default()
+        });
+        commands = spawn_pen(
+            commands,
+            pen_transform,
+            TEAM_PEN_HALF_SIZE,
+            fence_handle.clone(),
+            fence_material_handle,
+        );
+
+        commands
+            .spawn(PbrBundle {
+                mesh: floor_handle.clone(),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::rgba(r, g, b, 0.4),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                }),
+                transform: pen_transform * Transform::from_xyz(0.0, 0.1, 0.0),
+                ..default()
+            })
+            .insert(HerdingPen {
+                combine_id,
+                transform: pen_transform,
+                half_size: TEAM_PEN_HALF_SIZE,
+                cow_count: 0,
+                penalties: 0,
+            });
+    }
+
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: regular_font,
+                            font_size: 48.0,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_text_alignment(TextAlignment::CENTER),
+                )
+                .insert(HerdingResultsUi);
+        });
+}
+
+/// Counts the cows in each pen and ends the match when time runs out.
+pub fn herding_count_system(
+    time: Res<Time>,
+    herding_match: Option<ResMut<HerdingMatch>>,
+    mut pen_query: Query<&mut HerdingPen>,
+    cow_query: Query<&Transform, With<Cow>>,
+) {
+    let mut herding_match = match herding_match {
+        Some(herding_match) => herding_match,
+        None => return,
+    };
+    if herding_match.finished {
+        return;
+    }
+
+    herding_match.timer.tick(time.delta());
+
+    for mut pen in pen_query.iter_mut() {
+        let cow_count = cow_query
+            .iter()
+            .filter(|transform| pen.contains(transform.translation))
+            .count();
+        pen.cow_count = cow_count as i32;
+    }
+
+    if herding_match.timer.finished() {
+        herding_match.finished = true;
+    }
+}
+
+/// Penalises teams for hitting cows too hard.
+pub fn herding_injury_system(
+    time: Res<Time>,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    herding_match: Option<ResMut<HerdingMatch>>,
+    mut pen_query: Query<&mut HerdingPen>,
+    cow_query: Query<(), With<Cow>>,
+    combine_query: Query<&Combine>,
+    wheel_query: Query<&Wheel>,
+) {
+    let mut herding_match = match herding_match {
+        Some(herding_match) => herding_match,
+        None => return,
+    };
+    if herding_match.finished {
+        return;
+    }
+
+    for contact_force_event in contact_force_events.iter() {
+        if contact_force_event.total_force_magnitude < COW_INJURY_FORCE {
+            continue;
+        }
+        let colliders = [
+            (contact_force_event.collider1, contact_force_event.collider2),
+            (contact_force_event.collider2, contact_force_event.collider1),
+        ];
+        for (cow_entity, other_entity) in colliders {
+            if cow_query.get(cow_entity).is_err() {
+                continue;
+            }
+            let combine_id = combine_query
+                .get(other_entity)
+                .map(|combine| combine.combine_id)
+                .or_else(|_| wheel_query.get(other_entity).map(|wheel| wheel.combine_id));
+            let combine_id = match combine_id {
+                Ok(combine_id) => combine_id,
+                Err(_) => continue,
+            };
+
+            if let Some(last_injury) = herding_match.last_injuries.get(&cow_entity) {
+                if time.elapsed() < *last_injury + COW_INJURY_COOLDOWN {
+                    continue;
+                }
+            }
+            herding_match
+                .last_injuries
+                .insert(cow_entity, time.elapsed());
+
+            for mut pen in pen_query.iter_mut() {
+                if pen.combine_id == combine_id {
+                    pen.penalties += COW_INJURY_PENALTY;
+                }
+            }
+        }
+    }
+}
+
+pub fn herding_ui_system(
+    herding_match: Option<Res<HerdingMatch>>,
+    pen_query: Query<&HerdingPen>,
+    mut results_query: Query<&mut Text, With<HerdingResultsUi>>,
+) {
+    let herding_match = match herding_match {
+        Some(herding_match) => herding_match,
+        None => return,
+    };
+
+    let mut pens: Vec<&HerdingPen> = pen_query.iter().collect();
+    pens.sort_by_key(|pen| pen.combine_id);
+
+    for mut text in results_query.iter_mut() {
+        if !herding_match.finished {
+            text.sections[0].value = String::new();
+            continue;
+        }
+
+        let best_score = pens.iter().map(|pen| pen.score()).max().unwrap_or(0);
+        let winners: Vec<&&HerdingPen> = pens
+            .iter()
+            .filter(|pen| pen.score() == best_score)
+            .collect();
+        let headline = if winners.len() == 1 {
+            format!("{} wins!", combine_name(winners[0].combine_id))
+        } else {
+            "It's a draw!".to_string()
+        };
+
+        let lines: Vec<String> = pens
+            .iter()
+            .map(|pen| {
+                format!(
+                    "{}: {} cows penned, {} penalties, {} points",
+                    combine_name(pen.combine_id),
+                    pen.cow_count,
+                    pen.penalties,
+                    pen.score()
+                )
+            })
+            .collect();
+        text.sections[0].value = format!(
+            "Time up!\n{}\n\n{}\n\nEscape to return to the menu",
+            headline,
+            lines.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::herding::HerdingPen;
+
+    fn pen(transform: Transform) -> HerdingPen {
+        HerdingPen {
+            combine_id: 0,
+            transform,
+            half_size: 30.0,
+            cow_count: 0,
+            penalties: 0,
+        }
+    }
+
+    #[test]
+    fn pen_contains_test() {
+        let pen = pen(Transform::from_xyz(50.0, 0.0, 50.0));
+
+        assert!(pen.contains(Vec3::new(50.0, 0.0, 50.0)));
+        assert!(pen.contains(Vec3::new(75.0, 5.0, 25.0)));
+        assert!(!pen.contains(Vec3::new(85.0, 0.0, 50.0)));
+        assert!(!pen.contains(Vec3::ZERO));
+    }
+
+    #[test]
+    fn rotated_pen_contains_test() {
+        let pen = pen(Transform::from_rotation(Quat::from_rotation_y(
+            45.0_f32.to_radians(),
+        )));
+
+        // the corner of an unrotated pen is outside once it is turned 45 degrees.
+        assert!(!pen.contains(Vec3::new(29.0, 0.0, 29.0)));
+        assert!(pen.contains(Vec3::new(40.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn pen_score_test() {
+        let mut pen = pen(Transform::IDENTITY);
+        pen.cow_count = 3;
+        pen.penalties = 1;
+        assert_eq!(2, pen.score());
+
+        pen.penalties = 5;
+        assert_eq!(-2, pen.score());
+    }
+}
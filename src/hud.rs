@@ -0,0 +1,589 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::{prelude::*, render::camera::RenderTarget};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    boost::{Boost, BOOST_MAX_CHARGE},
+    combine::{
+        combine_colour, combine_name, Combine, COMBINE_DAMAGE_FORCE, COMBINE_MAX_HEALTH,
+        PLAYER_COMBINE_ID,
+    },
+    herding::{HerdingMatch, HerdingPen},
+};
+
+/// Most entries shown in the feed at once.
+const FEED_LENGTH: usize = 5;
+/// Time an entry stays in the feed.
+const FEED_SECONDS: f32 = 6.0;
+/// Hits between combines at least this hard are reported in the feed.
+const BIG_HIT_FORCE: f32 = 150000.0;
+/// Time before the same two combines can be reported for another big hit, as a crash is
+/// reported every frame the combines push against each other.
+const BIG_HIT_COOLDOWN_SECONDS: f32 = 2.0;
+
+const BAR_WIDTH: f32 = 220.0;
+const BAR_HEIGHT: f32 = 14.0;
+const HEALTH_BAR_COLOUR: Color = Color::rgb(0.8, 0.15, 0.1);
+const BOOST_BAR_COLOUR: Color = Color::rgb(0.95, 0.6, 0.1);
+const BAR_BACKGROUND_COLOUR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+/// Height above an opponent its name plate floats at, in metres.
+const NAME_PLATE_HEIGHT: f32 = 9.0;
+const NAME_PLATE_WIDTH: f32 = 110.0;
+
+/// How a combine is doing in the match.
+#[derive(Default)]
+struct CombineRecord {
+    /// Opponents this combine has wrecked.
+    knockouts: i32,
+    /// The combine that last hit this one hard enough to damage it, who is credited with the
+    /// knockout if it is wrecked.
+    last_hit_by: Option<i32>,
+    wrecked: bool,
+}
+
+/// Who has wrecked who, and the feed of eliminations and big hits.
+#[derive(Resource, Default)]
+pub struct MatchStats {
+    elapsed_seconds: f32,
+    records: HashMap<i32, CombineRecord>,
+    /// Feed entries and when they were added.
+    feed: VecDeque<(String, f32)>,
+    /// When each pair of combines was last reported for a big hit.
+    last_big_hits: HashMap<(i32, i32), f32>,
+}
+
+impl MatchStats {
+    fn tick(&mut self, delta_seconds: f32) {
+        self.elapsed_seconds += delta_seconds;
+        let elapsed_seconds = self.elapsed_seconds;
+        self.feed
+            .retain(|(_, added)| elapsed_seconds - added < FEED_SECONDS);
+    }
+
+    fn push_feed(&mut self, entry: String) {
+        self.feed.push_back((entry, self.elapsed_seconds));
+        while self.feed.len() > FEED_LENGTH {
+            self.feed.pop_front();
+        }
+    }
+
+    /// Records a hit between two combines.
+    fn record_hit(&mut self, combine_id: i32, other_id: i32, force: f32) {
+        if force >= COMBINE_DAMAGE_FORCE {
+            self.records.entry(combine_id).or_default().last_hit_by = Some(other_id);
+            self.records.entry(other_id).or_default().last_hit_by = Some(combine_id);
+        }
+
+        let pair = (combine_id.min(other_id), combine_id.max(other_id));
+        let cooled_down = self.last_big_hits.get(&pair).map_or(true, |last| {
+            self.elapsed_seconds - last >= BIG_HIT_COOLDOWN_SECONDS
+        });
+        if force >= BIG_HIT_FORCE && cooled_down {
+            self.last_big_hits.insert(pair, self.elapsed_seconds);
+            self.push_feed(format!(
+                "{} and {} collided",
+                combine_name(pair.0),
+                combine_name(pair.1)
+            ));
+        }
+    }
+
+    /// Notices combines being wrecked, crediting whoever hit them last, and being repaired.
+    fn update_health(&mut self, combine_id: i32, health: f32) {
+        let record = self.records.entry(combine_id).or_default();
+        if health > 0.0 {
+            record.wrecked = false;
+            return;
+        }
+        if record.wrecked {
+            return;
+        }
+        record.wrecked = true;
+
+        let attacker = record.last_hit_by.take();
+        let entry = match attacker {
+            Some(attacker) => {
+                self.records.entry(attacker).or_default().knockouts += 1;
+                format!(
+                    "{} wrecked {}",
+                    combine_name(attacker),
+                    combine_name(combine_id)
+                )
+            }
+            None => format!("{} was wrecked", combine_name(combine_id)),
+        };
+        self.push_feed(entry);
+    }
+
+    fn knockouts(&self, combine_id: i32) -> i32 {
+        self.records
+            .get(&combine_id)
+            .map_or(0, |record| record.knockouts)
+    }
+}
+
+/// Formats a time as minutes and seconds.
+fn format_clock(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as i32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudBar {
+    Health,
+    Boost,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudText {
+    Timer,
+    Scoreboard,
+    Feed,
+}
+
+/// A name and health bar floating above an opponent.
+#[derive(Component)]
+pub struct NamePlate {
+    combine_id: i32,
+}
+
+#[derive(Component)]
+pub struct NamePlateFill {
+    combine_id: i32,
+}
+
+fn spawn_bar(parent: &mut ChildBuilder, font: Handle<Font>, label: &str, bar: HudBar) {
+    let colour = match bar {
+        HudBar::Health => HEALTH_BAR_COLOUR,
+        HudBar::Boost => BOOST_BAR_COLOUR,
+    };
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                margin: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    size: Size::new(Val::Px(70.0), Val::Auto),
+                    ..default()
+                }),
+            );
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                        ..default()
+                    },
+                    background_color: BAR_BACKGROUND_COLOUR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            background_color: colour.into(),
+                            ..default()
+                        })
+                        .insert(bar);
+                });
+        });
+}
+
+pub fn setup_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mono_font: Handle<Font> = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands.insert_resource(MatchStats::default());
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(100.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            spawn_bar(parent, regular_font.clone(), "Health", HudBar::Health);
+            spawn_bar(parent, regular_font.clone(), "Boost", HudBar::Boost);
+        });
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(5.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: regular_font.clone(),
+                        font_size: 36.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(HudText::Timer);
+        });
+
+    let text_bundle = |font: Handle<Font>, font_size: f32, position: UiRect| TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            ..default()
+        },
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size,
+                color: Color::WHITE,
+            },
+        ),
+        ..default()
+    };
+    commands
+        .spawn(text_bundle(
+            mono_font,
+            18.0,
+            UiRect {
+                top: Val::Px(70.0),
+                left: Val::Px(15.0),
+                ..default()
+            },
+        ))
+        .insert(HudText::Scoreboard);
+    commands
+        .spawn(
+            text_bundle(
+                regular_font,
+                20.0,
+                UiRect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_RIGHT),
+        )
+        .insert(HudText::Feed);
+}
+
+/// Keeps track of knockouts and fills the feed with eliminations and big hits.
+pub fn match_stats_system(
+    time: Res<Time>,
+    mut match_stats: ResMut<MatchStats>,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    combine_query: Query<&Combine>,
+) {
+    match_stats.tick(time.delta_seconds());
+
+    for contact_force_event in contact_force_events.iter() {
+        let combines = (
+            combine_query.get(contact_force_event.collider1),
+            combine_query.get(contact_force_event.collider2),
+        );
+        if let (Ok(combine), Ok(other)) = combines {
+            match_stats.record_hit(
+                combine.combine_id,
+                other.combine_id,
+                contact_force_event.total_force_magnitude,
+            );
+        }
+    }
+
+    for combine in combine_query.iter() {
+        match_stats.update_health(combine.combine_id, combine.health);
+    }
+}
+
+/// Shows the player's health and boost, the match clock, the scoreboard and the feed.
+pub fn hud_system(
+    match_stats: Res<MatchStats>,
+    herding_match: Option<Res<HerdingMatch>>,
+    pen_query: Query<&HerdingPen>,
+    combine_query: Query<(&Combine, &Boost)>,
+    mut bar_query: Query<(&HudBar, &mut Style)>,
+    mut text_query: Query<(&HudText, &mut Text)>,
+) {
+    let mut combines: Vec<(&Combine, &Boost)> = combine_query.iter().collect();
+    combines.sort_by_key(|(combine, _)| combine.combine_id);
+
+    if let Some((player, boost)) = combines
+        .iter()
+        .find(|(combine, _)| combine.combine_id == PLAYER_COMBINE_ID)
+    {
+        for (bar, mut style) in bar_query.iter_mut() {
+            let fraction = match bar {
+                HudBar::Health => player.health / COMBINE_MAX_HEALTH,
+                HudBar::Boost => boost.charge / BOOST_MAX_CHARGE,
+            };
+            style.size.width = Val::Percent(100.0 * fraction.clamp(0.0, 1.0));
+        }
+    }
+
+    // herding matches are scored by their pens and count down, derbies by knockouts.
+    let score = |combine_id: i32| match &herding_match {
+        Some(_) => pen_query
+            .iter()
+            .find(|pen| pen.combine_id == combine_id)
+            .map_or(0, |pen| pen.score()),
+        None => match_stats.knockouts(combine_id),
+    };
+    let clock = match &herding_match {
+        Some(herding_match) => format_clock(herding_match.timer.remaining_secs().ceil()),
+        None => format_clock(match_stats.elapsed_seconds),
+    };
+
+    let mut scoreboard = vec![format!("{:<12}{:>6}  {}", "", "Score", "Status")];
+    let mut rows: Vec<(i32, i32, f32)> = combines
+        .iter()
+        .map(|(combine, _)| {
+            (
+                combine.combine_id,
+                score(combine.combine_id),
+                combine.health,
+            )
+        })
+        .collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (combine_id, score, health) in rows {
+        let status = if health <= 0.0 {
+            "Wrecked".to_string()
+        } else {
+            format!("{:.0}%", 100.0 * health / COMBINE_MAX_HEALTH)
+        };
+        scoreboard.push(format!(
+            "{:<12}{:>6}  {}",
+            combine_name(combine_id),
+            score,
+            status
+        ));
+    }
+
+    let feed: Vec<&str> = match_stats
+        .feed
+        .iter()
+        .map(|(entry, _)| entry.as_str())
+        .collect();
+
+    for (hud_text, mut text) in text_query.iter_mut() {
+        text.sections[0].value = match hud_text {
+            HudText::Timer => clock.clone(),
+            HudText::Scoreboard => scoreboard.join("\n"),
+            HudText::Feed => feed.join("\n"),
+        };
+    }
+}
+
+/// Floats a name and health bar over each opponent, seen from whichever camera is showing.
+pub fn name_plate_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    combine_query: Query<(&Combine, &GlobalTransform)>,
+    mut plate_query: Query<(&NamePlate, &mut Style, &mut Visibility), Without<NamePlateFill>>,
+    mut fill_query: Query<(&NamePlateFill, &mut Style), Without<NamePlate>>,
+) {
+    let camera = camera_query
+        .iter()
+        .find(|(camera, _)| camera.is_active && matches!(camera.target, RenderTarget::Window(_)));
+
+    let mut missing_plates: Vec<i32> = combine_query
+        .iter()
+        .map(|(combine, _)| combine.combine_id)
+        .filter(|combine_id| *combine_id != PLAYER_COMBINE_ID)
+        .collect();
+
+    for (plate, mut style, mut visibility) in plate_query.iter_mut() {
+        missing_plates.retain(|combine_id| *combine_id != plate.combine_id);
+        let combine = combine_query
+            .iter()
+            .find(|(combine, _)| combine.combine_id == plate.combine_id);
+        let screen_position = match (camera, combine) {
+            (Some((camera, camera_transform)), Some((_, combine_transform))) => camera
+                .world_to_viewport(
+                    camera_transform,
+                    combine_transform.translation() + (Vec3::Y * NAME_PLATE_HEIGHT),
+                ),
+            _ => None,
+        };
+        match screen_position {
+            Some(screen_position) => {
+                style.position = UiRect {
+                    left: Val::Px(screen_position.x - (NAME_PLATE_WIDTH * 0.5)),
+                    bottom: Val::Px(screen_position.y),
+                    ..default()
+                };
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+
+    for (fill, mut style) in fill_query.iter_mut() {
+        if let Some((combine, _)) = combine_query
+            .iter()
+            .find(|(combine, _)| combine.combine_id == fill.combine_id)
+        {
+            let fraction = (combine.health / COMBINE_MAX_HEALTH).clamp(0.0, 1.0);
+            style.size.width = Val::Percent(100.0 * fraction);
+        }
+    }
+
+    // plates for new opponents are added now and placed from the next frame.
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    for combine_id in missing_plates {
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(NAME_PLATE_WIDTH), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(NamePlate { combine_id })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    combine_name(combine_id),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        color: combine_colour(combine_id),
+                    },
+                ));
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Px(6.0)),
+                            ..default()
+                        },
+                        background_color: BAR_BACKGROUND_COLOUR.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                    ..default()
+                                },
+                                background_color: HEALTH_BAR_COLOUR.into(),
+                                ..default()
+                            })
+                            .insert(NamePlateFill { combine_id });
+                    });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        combine::{COMBINE_DAMAGE_FORCE, PLAYER_COMBINE_ID},
+        hud::{
+            format_clock, MatchStats, BIG_HIT_COOLDOWN_SECONDS, BIG_HIT_FORCE, FEED_LENGTH,
+            FEED_SECONDS,
+        },
+    };
+
+    const OPPONENT: i32 = PLAYER_COMBINE_ID + 1;
+
+    #[test]
+    fn knockout_credited_to_last_hit_test() {
+        let mut stats = MatchStats::default();
+        stats.record_hit(PLAYER_COMBINE_ID, OPPONENT, COMBINE_DAMAGE_FORCE);
+        stats.update_health(OPPONENT, 0.0);
+        // still wrecked, so it isn't counted again.
+        stats.update_health(OPPONENT, 0.0);
+
+        assert_eq!(1, stats.knockouts(PLAYER_COMBINE_ID));
+        assert_eq!(0, stats.knockouts(OPPONENT));
+        assert_eq!("You wrecked Combine 2", stats.feed.back().unwrap().0);
+    }
+
+    #[test]
+    fn repaired_combine_can_be_wrecked_again_test() {
+        let mut stats = MatchStats::default();
+        stats.update_health(OPPONENT, 0.0);
+        stats.update_health(OPPONENT, 25.0);
+        stats.record_hit(OPPONENT, PLAYER_COMBINE_ID, COMBINE_DAMAGE_FORCE);
+        stats.update_health(OPPONENT, 0.0);
+
+        assert_eq!(1, stats.knockouts(PLAYER_COMBINE_ID));
+        assert_eq!("Combine 2 was wrecked", stats.feed[0].0);
+    }
+
+    #[test]
+    fn big_hits_reported_once_test() {
+        let mut stats = MatchStats::default();
+        stats.record_hit(PLAYER_COMBINE_ID, OPPONENT, BIG_HIT_FORCE);
+        stats.tick(0.1);
+        stats.record_hit(OPPONENT, PLAYER_COMBINE_ID, BIG_HIT_FORCE);
+        assert_eq!(1, stats.feed.len());
+
+        stats.tick(BIG_HIT_COOLDOWN_SECONDS);
+        stats.record_hit(OPPONENT, PLAYER_COMBINE_ID, BIG_HIT_FORCE);
+        assert_eq!(2, stats.feed.len());
+    }
+
+    #[test]
+    fn feed_is_trimmed_test() {
+        let mut stats = MatchStats::default();
+        for combine_id in 0..(FEED_LENGTH as i32 + 2) {
+            stats.update_health(combine_id + 10, 0.0);
+        }
+        assert_eq!(FEED_LENGTH, stats.feed.len());
+
+        stats.tick(FEED_SECONDS);
+        assert!(stats.feed.is_empty());
+    }
+
+    #[test]
+    fn clock_test() {
+        assert_eq!("0:00", format_clock(0.0));
+        assert_eq!("1:05", format_clock(65.4));
+        assert_eq!("0:00", format_clock(-3.0));
+    }
+}
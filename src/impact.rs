@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    camera::CombineCamera,
+    combine::{Combine, PLAYER_COMBINE_ID},
+};
+
+/// Hits softer than this don't shake the camera.
+const SHAKE_MIN_FORCE: f32 = 20000.0;
+/// Hits this hard or harder give the most trauma a single hit can.
+const SHAKE_FULL_FORCE: f32 = 200000.0;
+/// Trauma lost per second, so even the biggest hit stops shaking after a second.
+const TRAUMA_DECAY_PER_SECOND: f32 = 1.0;
+/// Furthest the camera is moved by a shake at full trauma, in metres.
+const SHAKE_MAX_OFFSET: f32 = 0.8;
+/// Furthest the camera is turned by a shake at full trauma, in radians.
+const SHAKE_MAX_ANGLE: f32 = 0.06;
+/// How fast the shake wobbles.
+const SHAKE_FREQUENCY: f32 = 18.0;
+
+/// Hits at least this hard slow the physics down for a moment.
+const SLOW_MOTION_FORCE: f32 = 150000.0;
+const SLOW_MOTION_SECONDS: f32 = 0.6;
+const SLOW_MOTION_TIME_SCALE: f32 = 0.25;
+
+/// Which effects play when the player's combine is hit.
+#[derive(Resource)]
+pub struct ImpactEffects {
+    pub camera_shake: bool,
+    pub slow_motion: bool,
+    /// How fast the physics runs outside of slow motion, set from the console.
+    pub time_scale: f32,
+    /// Time left in the current slow motion, if there is one.
+    slow_motion_timer: Option<Timer>,
+}
+
+impl ImpactEffects {
+    /// Puts the physics back to normal speed, ending any slow motion.
+    pub fn reset_time_scale(&mut self) {
+        self.time_scale = 1.0;
+        self.slow_motion_timer = None;
+    }
+}
+
+impl Default for ImpactEffects {
+    fn default() -> Self {
+        ImpactEffects {
+            camera_shake: true,
+            slow_motion: false,
+            time_scale: 1.0,
+            slow_motion_timer: None,
+        }
+    }
+}
+
+/// Shakes a combine camera after hits, more the harder the hit. Trauma builds up with each hit
+/// and wears off over time, and the shake grows with its square so small knocks stay subtle.
+#[derive(Component, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    /// Time since the camera last started shaking, used to move through the wobble.
+    elapsed: f32,
+}
+
+impl CameraShake {
+    fn add_trauma(&mut self, force: f32) {
+        if self.trauma == 0.0 {
+            self.elapsed = 0.0;
+        }
+        self.trauma = (self.trauma + trauma_from_force(force)).min(1.0);
+    }
+
+    /// Gets how far the camera is moved and turned by the shake at the moment.
+    fn offset(&self) -> (Vec3, Quat) {
+        let shake = self.trauma * self.trauma;
+        if shake == 0.0 {
+            return (Vec3::ZERO, Quat::IDENTITY);
+        }
+        let t = self.elapsed * SHAKE_FREQUENCY;
+        let translation =
+            Vec3::new(wobble(t, 0.0), wobble(t, 1.0), wobble(t, 2.0)) * SHAKE_MAX_OFFSET * shake;
+        let rotation = Quat::from_euler(
+            EulerRot::YXZ,
+            wobble(t, 3.0) * SHAKE_MAX_ANGLE * shake,
+            wobble(t, 4.0) * SHAKE_MAX_ANGLE * shake,
+            wobble(t, 5.0) * SHAKE_MAX_ANGLE * shake,
+        );
+        (translation, rotation)
+    }
+}
+
+/// Gets the trauma one hit adds, from nothing for soft knocks up to 1 for the hardest hits.
+fn trauma_from_force(force: f32) -> f32 {
+    ((force - SHAKE_MIN_FORCE) / (SHAKE_FULL_FORCE - SHAKE_MIN_FORCE)).clamp(0.0, 1.0)
+}
+
+/// Smooth, irregular movement between -1 and 1. Each seed wobbles differently, so the axes
+/// don't move together.
+fn wobble(t: f32, seed: f32) -> f32 {
+    let phase = seed * 1.7;
+    ((t + phase).sin() * 0.5)
+        + ((t * 2.3 + phase * 3.1).sin() * 0.3)
+        + ((t * 4.7 + phase).sin() * 0.2)
+}
+
+pub fn set_physics_time_scale(rapier_configuration: &mut RapierConfiguration, scale: f32) {
+    match &mut rapier_configuration.timestep_mode {
+        TimestepMode::Variable { time_scale, .. }
+        | TimestepMode::Interpolated { time_scale, .. } => {
+            *time_scale = scale;
+        }
+        TimestepMode::Fixed { .. } => (),
+    }
+}
+
+/// Adds trauma to the player's camera when their combine is hit, and slows the physics down for
+/// the biggest hits. F9 toggles camera shake and F10 toggles slow motion.
+pub fn impact_system(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut impact_effects: ResMut<ImpactEffects>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    combine_query: Query<&Combine>,
+    mut camera_query: Query<(&CombineCamera, &mut CameraShake)>,
+) {
+    if keys.just_pressed(KeyCode::F9) {
+        impact_effects.camera_shake = !impact_effects.camera_shake;
+    }
+    if keys.just_pressed(KeyCode::F10) {
+        impact_effects.slow_motion = !impact_effects.slow_motion;
+    }
+
+    let is_player = |entity| {
+        combine_query
+            .get(entity)
+            .map_or(false, |combine| combine.combine_id == PLAYER_COMBINE_ID)
+    };
+    let hardest_hit = contact_force_events
+        .iter()
+        .filter(|event| is_player(event.collider1) || is_player(event.collider2))
+        .map(|event| event.total_force_magnitude)
+        .reduce(f32::max);
+
+    if let Some(force) = hardest_hit {
+        if impact_effects.camera_shake {
+            for (combine_camera, mut shake) in camera_query.iter_mut() {
+                if combine_camera.combine_id == PLAYER_COMBINE_ID {
+                    shake.add_trauma(force);
+                }
+            }
+        }
+        if impact_effects.slow_motion
+            && force >= SLOW_MOTION_FORCE
+            && impact_effects.slow_motion_timer.is_none()
+        {
+            impact_effects.slow_motion_timer =
+                Some(Timer::from_seconds(SLOW_MOTION_SECONDS, TimerMode::Once));
+            set_physics_time_scale(
+                &mut rapier_configuration,
+                SLOW_MOTION_TIME_SCALE * impact_effects.time_scale,
+            );
+        }
+    }
+
+    let impact_effects = &mut *impact_effects;
+    if let Some(timer) = &mut impact_effects.slow_motion_timer {
+        timer.tick(time.delta());
+        if timer.finished() || !impact_effects.slow_motion {
+            impact_effects.slow_motion_timer = None;
+            set_physics_time_scale(&mut rapier_configuration, impact_effects.time_scale);
+        }
+    }
+}
+
+/// Shakes the active combine camera. Runs after the chase camera has placed it for the frame.
+pub fn camera_shake_system(
+    time: Res<Time>,
+    impact_effects: Res<ImpactEffects>,
+    mut camera_query: Query<(&Camera, &mut CameraShake, &mut Transform)>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (camera, mut shake, mut transform) in camera_query.iter_mut() {
+        if !impact_effects.camera_shake {
+            shake.trauma = 0.0;
+        }
+        if camera.is_active {
+            let (translation, rotation) = shake.offset();
+            let offset = transform.rotation * translation;
+            transform.translation += offset;
+            transform.rotation *= rotation;
+        }
+        shake.elapsed += delta_seconds;
+        shake.trauma = (shake.trauma - (TRAUMA_DECAY_PER_SECOND * delta_seconds)).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::impact::{
+        trauma_from_force, wobble, CameraShake, SHAKE_FULL_FORCE, SHAKE_MAX_ANGLE,
+        SHAKE_MAX_OFFSET, SHAKE_MIN_FORCE,
+    };
+
+    #[test]
+    fn soft_hits_do_not_shake_test() {
+        assert_eq!(0.0, trauma_from_force(SHAKE_MIN_FORCE * 0.5));
+        assert_eq!(1.0, trauma_from_force(SHAKE_FULL_FORCE * 2.0));
+        let halfway = trauma_from_force((SHAKE_MIN_FORCE + SHAKE_FULL_FORCE) * 0.5);
+        assert!((halfway - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn trauma_builds_up_to_full_test() {
+        let mut shake = CameraShake::default();
+        let force = (SHAKE_MIN_FORCE + SHAKE_FULL_FORCE) * 0.5;
+
+        shake.add_trauma(force);
+        assert!((shake.trauma - 0.5).abs() < 0.001);
+        for _ in 0..3 {
+            shake.add_trauma(force);
+        }
+        assert_eq!(1.0, shake.trauma);
+    }
+
+    #[test]
+    fn shake_stays_within_limits_test() {
+        let mut shake = CameraShake {
+            trauma: 1.0,
+            elapsed: 0.0,
+        };
+        for step in 0..200 {
+            shake.elapsed = step as f32 * 0.01;
+            let (translation, rotation) = shake.offset();
+            assert!(translation.abs().max_element() <= SHAKE_MAX_OFFSET + 0.001);
+            assert!(rotation.angle_between(Quat::IDENTITY) <= SHAKE_MAX_ANGLE * 2.0);
+        }
+        for step in 0..200 {
+            assert!(wobble(step as f32 * 0.1, 2.0).abs() <= 1.0);
+        }
+    }
+}
@@ -3,31 +3,43 @@ use bevy::prelude::*;
 use crate::{
     camera::SwitchCameraEvent,
     combine::PLAYER_COMBINE_ID,
-    events::{SpeedControlAction, SpeedControlEvent, SteerControlAction, SteerControlEvent},
-    ui::DebugInfo, config::DEFAULT_VOLUME,
+    events::{
+        BoostControlEvent, GearShiftAction, GearShiftEvent, SpeedControlAction,
+        SpeedControlEvent, SteerControlAction, SteerControlEvent,
+    },
+    ui::DebugInfo, mixer::{AudioBus, AudioMixer},
+    photo_mode::PhotoMode,
+    console::Console,
 };
 
-#[derive(Resource)]
-pub struct Settings {
-    pub volume: f32,
-}
-
-impl Default for Settings {
-    fn default() -> Self {
-        Self { volume: DEFAULT_VOLUME }
-    }
-}
-
 pub fn keyboard_input(
     keys: Res<Input<KeyCode>>,
     mut speed_control_events: ResMut<Events<SpeedControlEvent>>,
     mut steer_control_events: ResMut<Events<SteerControlEvent>>,
+    mut boost_control_events: ResMut<Events<BoostControlEvent>>,
+    mut gear_shift_events: ResMut<Events<GearShiftEvent>>,
     mut camera_events: ResMut<Events<SwitchCameraEvent>>,
     mut debug_info: ResMut<DebugInfo>,
-    mut settings: ResMut<Settings>,
+    mut mixer: ResMut<AudioMixer>,
+    photo_mode: Res<PhotoMode>,
+    console: Res<Console>,
 ) {
     let combine_id = PLAYER_COMBINE_ID;
 
+    // the keys fly the photo camera instead while in photo mode, and type while the console is
+    // open. the wheel motors keep their last command, so let go of the controls first.
+    if photo_mode.active || console.open {
+        speed_control_events.send(SpeedControlEvent {
+            combine_id,
+            action: SpeedControlAction::NoPower,
+        });
+        steer_control_events.send(SteerControlEvent {
+            combine_id,
+            action: SteerControlAction::NoSteer,
+        });
+        return;
+    }
+
     if keys.pressed(KeyCode::W) {
         speed_control_events.send(SpeedControlEvent {
             combine_id,
@@ -62,6 +74,29 @@ pub fn keyboard_input(
         });
     }
 
+    if keys.any_pressed([KeyCode::Space, KeyCode::LShift]) {
+        boost_control_events.send(BoostControlEvent { combine_id });
+    }
+
+    if keys.just_pressed(KeyCode::E) {
+        gear_shift_events.send(GearShiftEvent {
+            combine_id,
+            action: GearShiftAction::Up,
+        });
+    }
+    if keys.just_pressed(KeyCode::Q) {
+        gear_shift_events.send(GearShiftEvent {
+            combine_id,
+            action: GearShiftAction::Down,
+        });
+    }
+    if keys.just_pressed(KeyCode::M) {
+        gear_shift_events.send(GearShiftEvent {
+            combine_id,
+            action: GearShiftAction::ToggleAutomatic,
+        });
+    }
+
     if keys.just_pressed(KeyCode::F4) {
         camera_events.send(SwitchCameraEvent);
     }
@@ -69,6 +104,6 @@ pub fn keyboard_input(
         debug_info.enabled = !debug_info.enabled;
     }
     if keys.just_pressed(KeyCode::F6) {
-        settings.volume = if settings.volume > 0.0 { 0.0 } else { 1.0 };
+        mixer.toggle_mute(AudioBus::Master);
     }
 }
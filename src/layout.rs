@@ -0,0 +1,707 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    arena::{GROUND_Y_POSITION, PLANE_SIZE, RAMP_HEIGHT},
+    hay_structure::{HayStructure, HayTemplate},
+};
+
+/// Height above the driving surface that a combine body is spawned at.
+const COMBINE_SPAWN_CLEARANCE: f32 = 6.1;
+
+/// Space kept clear around a spawn point so combines do not start inside an obstacle.
+const SPAWN_POINT_RADIUS: f32 = 25.0;
+
+/// Half the width of a team's pen in the herding game mode.
+pub const TEAM_PEN_HALF_SIZE: f32 = 30.0;
+/// How far along the line from the arena centre to a spawn point that team's pen is placed.
+const TEAM_PEN_DISTANCE_FACTOR: f32 = 0.45;
+
+/// Number of attempts made to place a feature before giving up on it.
+const PLACEMENT_ATTEMPTS: i32 = 32;
+
+/// How a generated arena is mirrored so every spawn point gets the same surroundings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Reflected across the X axis.
+    Mirror,
+    /// Rotated 180 degrees around the arena centre.
+    Rotational,
+    /// Rotated in 90 degree steps around the arena centre.
+    Quad,
+}
+
+impl Symmetry {
+    pub fn parse(value: &str) -> Option<Symmetry> {
+        match value.to_lowercase().as_str() {
+            "none" => Some(Symmetry::None),
+            "mirror" => Some(Symmetry::Mirror),
+            "rotational" => Some(Symmetry::Rotational),
+            "quad" => Some(Symmetry::Quad),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Mirror => "mirror",
+            Symmetry::Rotational => "rotational",
+            Symmetry::Quad => "quad",
+        }
+    }
+
+    /// Gets every copy of the given feature, including the feature itself.
+    fn images<T: ArenaFeature>(&self, feature: &T) -> Vec<T> {
+        match self {
+            Symmetry::None => vec![feature.clone()],
+            Symmetry::Mirror => vec![feature.clone(), feature.mirrored()],
+            Symmetry::Rotational => vec![feature.clone(), feature.rotated(PI)],
+            Symmetry::Quad => vec![
+                feature.clone(),
+                feature.rotated(FRAC_PI_2),
+                feature.rotated(PI),
+                feature.rotated(-FRAC_PI_2),
+            ],
+        }
+    }
+
+    /// Gets a random point in the part of the arena that is copied by this symmetry.
+    fn random_point(&self, rng: &mut StdRng, limit: f32) -> Vec2 {
+        let x = rng.gen_range(-limit..limit);
+        let z = rng.gen_range(-limit..limit);
+        match self {
+            Symmetry::None => Vec2::new(x, z),
+            Symmetry::Mirror | Symmetry::Rotational => Vec2::new(x, z.abs()),
+            Symmetry::Quad => Vec2::new(x.abs(), z.abs()),
+        }
+    }
+}
+
+/// Something placed in the arena that can be copied by a [`Symmetry`].
+trait ArenaFeature: Clone {
+    /// Rotates the feature around the arena centre.
+    fn rotated(&self, angle: f32) -> Self;
+
+    /// Reflects the feature across the X axis.
+    fn mirrored(&self) -> Self;
+
+    /// Gets a circle on the ground that the feature fits within.
+    fn bounds(&self) -> (Vec2, f32);
+}
+
+/// Rotates a ground position the same way as `Quat::from_rotation_y`.
+fn rotate_ground_point(point: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(
+        point.x * cos + point.y * sin,
+        -point.x * sin + point.y * cos,
+    )
+}
+
+/// A raised plinth with slopes running down its local -X and -Z sides.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RampLayout {
+    pub position: Vec2,
+    pub yaw: f32,
+    /// Width of the plinth and length of each slope.
+    pub size: f32,
+    pub height: f32,
+}
+
+impl RampLayout {
+    pub fn transform(&self) -> Transform {
+        Transform::from_xyz(self.position.x, GROUND_Y_POSITION, self.position.y)
+            .with_rotation(Quat::from_rotation_y(self.yaw))
+    }
+}
+
+impl ArenaFeature for RampLayout {
+    fn rotated(&self, angle: f32) -> Self {
+        RampLayout {
+            position: rotate_ground_point(self.position, angle),
+            yaw: self.yaw + angle,
+            ..self.clone()
+        }
+    }
+
+    fn mirrored(&self) -> Self {
+        // the ramp is symmetrical along its diagonal, so a reflection is also a rotation.
+        RampLayout {
+            position: Vec2::new(self.position.x, -self.position.y),
+            yaw: FRAC_PI_2 - self.yaw,
+            ..self.clone()
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, f32) {
+        let centre = rotate_ground_point(Vec2::splat(-0.5 * self.size), self.yaw);
+        (self.position + centre, self.size * 1.5)
+    }
+}
+
+impl ArenaFeature for HayStructure {
+    fn rotated(&self, angle: f32) -> Self {
+        HayStructure {
+            transform: Transform::from_rotation(Quat::from_rotation_y(angle)) * self.transform,
+            ..self.clone()
+        }
+    }
+
+    fn mirrored(&self) -> Self {
+        // a reflection across Z is a half turn after a reflection across the local X axis, and
+        // the bales themselves are reflected across X.
+        let (yaw, _, _) = self.transform.rotation.to_euler(EulerRot::YXZ);
+        let translation = self.transform.translation;
+        HayStructure {
+            transform: Transform::from_xyz(translation.x, translation.y, -translation.z)
+                .with_rotation(Quat::from_rotation_y(PI - yaw)),
+            mirrored: !self.mirrored,
+            ..self.clone()
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, f32) {
+        let translation = self.transform.translation;
+        (
+            Vec2::new(translation.x, translation.z),
+            self.footprint_radius(),
+        )
+    }
+}
+
+/// A fenced square with its local +Z side left open, holding a few cows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CowPenLayout {
+    pub position: Vec2,
+    pub yaw: f32,
+    pub half_size: f32,
+    pub cow_count: i32,
+}
+
+impl CowPenLayout {
+    pub fn transform(&self) -> Transform {
+        Transform::from_xyz(self.position.x, GROUND_Y_POSITION, self.position.y)
+            .with_rotation(Quat::from_rotation_y(self.yaw))
+    }
+
+    /// Gets the starting positions of the cows inside the pen.
+    pub fn cow_positions(&self) -> Vec<Vec3> {
+        let transform = self.transform();
+        let spacing = (2.0 * self.half_size) / (self.cow_count + 1) as f32;
+        (0..self.cow_count)
+            .map(|i| {
+                let x = -self.half_size + (spacing * (i + 1) as f32);
+                transform * Vec3::new(x, 14.0, 0.0)
+            })
+            .collect()
+    }
+}
+
+impl ArenaFeature for CowPenLayout {
+    fn rotated(&self, angle: f32) -> Self {
+        CowPenLayout {
+            position: rotate_ground_point(self.position, angle),
+            yaw: self.yaw + angle,
+            ..self.clone()
+        }
+    }
+
+    fn mirrored(&self) -> Self {
+        CowPenLayout {
+            position: Vec2::new(self.position.x, -self.position.y),
+            yaw: PI - self.yaw,
+            ..self.clone()
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, f32) {
+        (self.position, self.half_size * 1.5)
+    }
+}
+
+/// A team's goal pen in the herding game mode. It sits between the team's spawn point and the
+/// arena centre, open towards the centre.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TeamPenLayout {
+    pub position: Vec2,
+}
+
+impl TeamPenLayout {
+    fn for_spawn_point(spawn_point: &SpawnPoint) -> TeamPenLayout {
+        TeamPenLayout {
+            position: Vec2::new(spawn_point.position.x, spawn_point.position.z)
+                * TEAM_PEN_DISTANCE_FACTOR,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_xyz(self.position.x, GROUND_Y_POSITION, self.position.y).with_rotation(
+            Quat::from_rotation_y((-self.position.x).atan2(-self.position.y)),
+        )
+    }
+}
+
+impl ArenaFeature for TeamPenLayout {
+    fn rotated(&self, angle: f32) -> Self {
+        TeamPenLayout {
+            position: rotate_ground_point(self.position, angle),
+        }
+    }
+
+    fn mirrored(&self) -> Self {
+        TeamPenLayout {
+            position: Vec2::new(self.position.x, -self.position.y),
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, f32) {
+        (self.position, TEAM_PEN_HALF_SIZE * 1.5)
+    }
+}
+
+/// Where a combine starts. Combines face their local -Z axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub position: Vec3,
+    pub yaw: f32,
+}
+
+impl SpawnPoint {
+    /// Creates a spawn point on the ground facing the arena centre.
+    fn facing_centre(position: Vec2) -> SpawnPoint {
+        SpawnPoint {
+            position: Vec3::new(
+                position.x,
+                GROUND_Y_POSITION + COMBINE_SPAWN_CLEARANCE,
+                position.y,
+            ),
+            yaw: position.x.atan2(position.y),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.position).with_rotation(Quat::from_rotation_y(self.yaw))
+    }
+}
+
+impl ArenaFeature for SpawnPoint {
+    fn rotated(&self, angle: f32) -> Self {
+        let position = rotate_ground_point(Vec2::new(self.position.x, self.position.z), angle);
+        SpawnPoint {
+            position: Vec3::new(position.x, self.position.y, position.y),
+            yaw: self.yaw + angle,
+        }
+    }
+
+    fn mirrored(&self) -> Self {
+        SpawnPoint {
+            position: Vec3::new(self.position.x, self.position.y, -self.position.z),
+            yaw: PI - self.yaw,
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, f32) {
+        (
+            Vec2::new(self.position.x, self.position.z),
+            SPAWN_POINT_RADIUS,
+        )
+    }
+}
+
+/// Describes where everything in the arena is placed.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ArenaLayout {
+    /// Seed the layout was generated from. The hand built arena has no seed.
+    pub seed: Option<u64>,
+    pub symmetry: Symmetry,
+    pub ramps: Vec<RampLayout>,
+    pub hay_structures: Vec<HayStructure>,
+    pub cow_pens: Vec<CowPenLayout>,
+    /// Cows that are not kept in a pen.
+    pub loose_cows: Vec<Vec3>,
+    /// The first spawn point is used by the player.
+    pub spawn_points: Vec<SpawnPoint>,
+    /// One pen for each spawn point, used by the herding game mode.
+    pub team_pens: Vec<TeamPenLayout>,
+}
+
+impl ArenaLayout {
+    /// The original hand built arena.
+    pub fn classic() -> ArenaLayout {
+        let ramp_offset = PLANE_SIZE - (PLANE_SIZE / 4.0);
+        let spawn_offset = PLANE_SIZE - (PLANE_SIZE / 3.0);
+        let spawn_points = vec![
+            SpawnPoint {
+                position: Vec3::new(spawn_offset, RAMP_HEIGHT + 4.1, spawn_offset),
+                yaw: 45.0_f32.to_radians(),
+            },
+            SpawnPoint {
+                position: Vec3::new(-spawn_offset, RAMP_HEIGHT + 4.1, -spawn_offset),
+                yaw: 215.0_f32.to_radians(),
+            },
+        ];
+        ArenaLayout {
+            seed: None,
+            symmetry: Symmetry::Rotational,
+            ramps: vec![
+                RampLayout {
+                    position: Vec2::splat(ramp_offset),
+                    yaw: 0.0,
+                    size: PLANE_SIZE / 2.0,
+                    height: RAMP_HEIGHT,
+                },
+                RampLayout {
+                    position: Vec2::splat(-ramp_offset),
+                    yaw: PI,
+                    size: PLANE_SIZE / 2.0,
+                    height: RAMP_HEIGHT,
+                },
+            ],
+            hay_structures: vec![HayStructure::pyramid(
+                4,
+                Transform::from_xyz(0.0, GROUND_Y_POSITION, 0.0),
+            )],
+            cow_pens: vec![],
+            loose_cows: vec![
+                Vec3::new(150.0, 12.0, 50.0),
+                Vec3::new(50.0, 12.0, 150.0),
+                Vec3::new(-200.0, 12.0, -100.0),
+                Vec3::new(-50.0, 12.0, -150.0),
+                Vec3::new(PLANE_SIZE - 100.0, 12.0, -100.0),
+                Vec3::new(-100.0, 12.0, PLANE_SIZE - 100.0),
+                Vec3::new(-(PLANE_SIZE - 100.0), 12.0, 100.0),
+                Vec3::new(100.0, 12.0, -(PLANE_SIZE - 100.0)),
+            ],
+            team_pens: spawn_points
+                .iter()
+                .map(TeamPenLayout::for_spawn_point)
+                .collect(),
+            spawn_points,
+        }
+    }
+
+    /// Generates a random arena. The same seed and symmetry always give the same layout.
+    pub fn generate(seed: u64, symmetry: Symmetry) -> ArenaLayout {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut occupied: Vec<(Vec2, f32)> = Vec::new();
+
+        let mut layout = ArenaLayout {
+            seed: Some(seed),
+            symmetry,
+            ramps: vec![],
+            hay_structures: vec![],
+            cow_pens: vec![],
+            loose_cows: vec![],
+            spawn_points: vec![],
+            team_pens: vec![],
+        };
+
+        // spawn points and their team pens go first so they are never crowded out.
+        let spawn_limit = PLANE_SIZE * 0.75;
+        let mut spawned = None;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let point = SpawnPoint::facing_centre(symmetry.random_point(&mut rng, spawn_limit));
+            let spawn_points = if symmetry == Symmetry::None {
+                // without symmetry there is nothing to copy, so put the opponent opposite.
+                vec![point.clone(), point.rotated(PI)]
+            } else {
+                symmetry.images(&point)
+            };
+            let team_pens = spawn_points
+                .iter()
+                .map(TeamPenLayout::for_spawn_point)
+                .collect();
+            let mut candidate = occupied.clone();
+            let placed = place(spawn_points, &mut candidate)
+                .and_then(|spawn_points| Some((spawn_points, place(team_pens, &mut candidate)?)));
+            if placed.is_some() {
+                spawned = placed;
+                occupied = candidate;
+                break;
+            }
+        }
+        let (spawn_points, team_pens) = spawned.unwrap_or_else(|| {
+            let spawn_points = fallback_spawn_points();
+            let team_pens: Vec<TeamPenLayout> = spawn_points
+                .iter()
+                .map(TeamPenLayout::for_spawn_point)
+                .collect();
+            occupied.extend(spawn_points.iter().map(|spawn_point| spawn_point.bounds()));
+            occupied.extend(team_pens.iter().map(|team_pen| team_pen.bounds()));
+            (spawn_points, team_pens)
+        });
+        layout.spawn_points = spawn_points;
+        layout.team_pens = team_pens;
+
+        // a centre pyramid copies onto itself under every symmetry.
+        if rng.gen_bool(0.5) {
+            let pyramid = HayStructure::pyramid(
+                rng.gen_range(3..=5),
+                Transform::from_xyz(0.0, GROUND_Y_POSITION, 0.0),
+            );
+            if let Some(mut structures) = place(vec![pyramid], &mut occupied) {
+                layout.hay_structures.append(&mut structures);
+            }
+        }
+
+        let ramp_count = rng.gen_range(1..=2);
+        for _ in 0..ramp_count {
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let ramp = RampLayout {
+                    position: symmetry.random_point(&mut rng, PLANE_SIZE),
+                    yaw: rng.gen_range(0..4) as f32 * FRAC_PI_2,
+                    size: rng.gen_range(30.0..70.0),
+                    height: rng.gen_range(RAMP_HEIGHT * 0.5..RAMP_HEIGHT * 2.0),
+                };
+                if let Some(mut ramps) = place(symmetry.images(&ramp), &mut occupied) {
+                    layout.ramps.append(&mut ramps);
+                    break;
+                }
+            }
+        }
+
+        let structure_count = rng.gen_range(1..=3);
+        for _ in 0..structure_count {
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let position = symmetry.random_point(&mut rng, PLANE_SIZE);
+                let transform = Transform::from_xyz(position.x, GROUND_Y_POSITION, position.y)
+                    .with_rotation(Quat::from_rotation_y(rng.gen_range(0.0..(2.0 * PI))));
+                let structure = HayStructure::new(random_hay_template(&mut rng), transform);
+                if let Some(mut structures) = place(symmetry.images(&structure), &mut occupied) {
+                    layout.hay_structures.append(&mut structures);
+                    break;
+                }
+            }
+        }
+
+        let pen_count = rng.gen_range(1..=2);
+        for _ in 0..pen_count {
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let half_size = rng.gen_range(22.0..32.0);
+                let pen = CowPenLayout {
+                    position: symmetry.random_point(&mut rng, PLANE_SIZE),
+                    yaw: rng.gen_range(0.0..(2.0 * PI)),
+                    half_size,
+                    cow_count: (half_size / 12.0) as i32,
+                };
+                if let Some(mut pens) = place(symmetry.images(&pen), &mut occupied) {
+                    layout.cow_pens.append(&mut pens);
+                    break;
+                }
+            }
+        }
+
+        layout
+    }
+
+    /// Builds the layout from the command line.
+    ///
+    /// `--seed <n>` generates an arena from the given seed, `--random-arena` generates one from a
+    /// random seed and `--symmetry <none|mirror|rotational|quad>` picks how it is mirrored.
+    /// Otherwise the classic arena is used.
+    pub fn from_args() -> ArenaLayout {
+        let args: Vec<String> = std::env::args().collect();
+        let arg_value = |name: &str| {
+            args.iter()
+                .position(|arg| arg == name)
+                .and_then(|i| args.get(i + 1))
+        };
+
+        let symmetry = arg_value("--symmetry")
+            .and_then(|value| Symmetry::parse(value))
+            .unwrap_or(Symmetry::Rotational);
+
+        if let Some(seed) = arg_value("--seed").and_then(|value| value.parse().ok()) {
+            ArenaLayout::generate(seed, symmetry)
+        } else if args.iter().any(|arg| arg == "--random-arena") {
+            ArenaLayout::generate(rand::random(), symmetry)
+        } else {
+            ArenaLayout::classic()
+        }
+    }
+
+    /// A short description of the layout for the debug overlay.
+    pub fn description(&self) -> String {
+        match self.seed {
+            Some(seed) => format!("Arena seed: {} ({})", seed, self.symmetry.name()),
+            None => "Arena: classic".to_string(),
+        }
+    }
+}
+
+fn random_hay_template(rng: &mut StdRng) -> HayTemplate {
+    match rng.gen_range(0..6) {
+        0 => HayTemplate::Pyramid {
+            layers: rng.gen_range(2..=4),
+        },
+        1 => HayTemplate::Grid {
+            columns: rng.gen_range(3..=6),
+            rows: 1,
+            layers: rng.gen_range(1..=3),
+        },
+        2 => HayTemplate::Grid {
+            columns: 1,
+            rows: 1,
+            layers: rng.gen_range(3..=6),
+        },
+        3 => HayTemplate::Ring {
+            count: rng.gen_range(6..=10),
+            radius: rng.gen_range(15.0..25.0),
+            layers: rng.gen_range(1..=2),
+        },
+        4 => HayTemplate::Staircase {
+            steps: rng.gen_range(2..=4),
+            width: rng.gen_range(2..=3),
+        },
+        _ => HayTemplate::Scattered {
+            count: rng.gen_range(4..=8),
+            radius: rng.gen_range(20.0..35.0),
+            seed: rng.gen(),
+        },
+    }
+}
+
+/// Spawn points used when a generated arena has no room for its own, at the classic arena's
+/// corners but on the ground.
+fn fallback_spawn_points() -> Vec<SpawnPoint> {
+    ArenaLayout::classic()
+        .spawn_points
+        .iter()
+        .map(|spawn_point| {
+            SpawnPoint::facing_centre(Vec2::new(spawn_point.position.x, spawn_point.position.z))
+        })
+        .collect()
+}
+
+/// Checks the features fit in the arena without overlapping anything, marking their space as
+/// occupied if they do.
+fn place<T: ArenaFeature>(features: Vec<T>, occupied: &mut Vec<(Vec2, f32)>) -> Option<Vec<T>> {
+    let bounds: Vec<(Vec2, f32)> = features.iter().map(|feature| feature.bounds()).collect();
+
+    for (i, (centre, radius)) in bounds.iter().enumerate() {
+        if centre.x.abs() + radius > PLANE_SIZE || centre.y.abs() + radius > PLANE_SIZE {
+            return None;
+        }
+        let overlaps = |(other_centre, other_radius): &(Vec2, f32)| {
+            centre.distance(*other_centre) < radius + other_radius
+        };
+        if occupied.iter().any(overlaps) || bounds[..i].iter().any(overlaps) {
+            return None;
+        }
+    }
+
+    occupied.extend(bounds);
+    Some(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        hay_structure::{HayStructure, HayTemplate},
+        layout::{
+            fallback_spawn_points, place, ArenaFeature, ArenaLayout, Symmetry, TeamPenLayout,
+        },
+    };
+
+    #[test]
+    fn same_seed_same_layout_test() {
+        let a = ArenaLayout::generate(1234, Symmetry::Rotational);
+        let b = ArenaLayout::generate(1234, Symmetry::Rotational);
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn rotational_spawn_points_test() {
+        let layout = ArenaLayout::generate(42, Symmetry::Rotational);
+
+        assert_eq!(2, layout.spawn_points.len());
+        let first = layout.spawn_points[0].position;
+        let second = layout.spawn_points[1].position;
+        assert!(Vec2::new(first.x + second.x, first.z + second.z).length() < 0.01);
+    }
+
+    #[test]
+    fn quad_feature_count_test() {
+        let layout = ArenaLayout::generate(7, Symmetry::Quad);
+
+        assert_eq!(4, layout.spawn_points.len());
+        assert_eq!(0, layout.ramps.len() % 4);
+        assert_eq!(0, layout.cow_pens.len() % 4);
+    }
+
+    #[test]
+    fn fallback_spawn_points_fit_test() {
+        let spawn_points = fallback_spawn_points();
+        let team_pens: Vec<TeamPenLayout> = spawn_points
+            .iter()
+            .map(TeamPenLayout::for_spawn_point)
+            .collect();
+        let mut occupied = Vec::new();
+
+        assert!(place(spawn_points, &mut occupied).is_some());
+        assert!(place(team_pens, &mut occupied).is_some());
+    }
+
+    #[test]
+    fn team_pens_are_clear_test() {
+        for symmetry in [
+            Symmetry::None,
+            Symmetry::Mirror,
+            Symmetry::Rotational,
+            Symmetry::Quad,
+        ] {
+            let layout = ArenaLayout::generate(99, symmetry);
+            assert_eq!(layout.spawn_points.len(), layout.team_pens.len());
+
+            let mut others: Vec<(Vec2, f32)> = layout
+                .spawn_points
+                .iter()
+                .map(|spawn_point| spawn_point.bounds())
+                .collect();
+            others.extend(layout.ramps.iter().map(|ramp| ramp.bounds()));
+            others.extend(layout.hay_structures.iter().map(|hay| hay.bounds()));
+            others.extend(layout.cow_pens.iter().map(|cow_pen| cow_pen.bounds()));
+            for team_pen in layout.team_pens.iter() {
+                let (centre, radius) = team_pen.bounds();
+                for (other_centre, other_radius) in others.iter() {
+                    assert!(centre.distance(*other_centre) >= radius + other_radius);
+                }
+                others.push((centre, radius));
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_hay_structure_test() {
+        let structure = HayStructure::new(
+            HayTemplate::Scattered {
+                count: 6,
+                radius: 20.0,
+                seed: 7,
+            },
+            Transform::from_xyz(30.0, 0.0, 50.0).with_rotation(Quat::from_rotation_y(0.6)),
+        );
+        let mirrored = structure.mirrored();
+
+        for (bale, mirrored_bale) in structure
+            .bale_transforms()
+            .iter()
+            .zip(mirrored.bale_transforms().iter())
+        {
+            let reflected = bale.translation * Vec3::new(1.0, 1.0, -1.0);
+            assert!(reflected.distance(mirrored_bale.translation) < 0.001);
+        }
+        assert_eq!(
+            structure.transform.translation,
+            mirrored.mirrored().transform.translation
+        );
+        assert!(!mirrored.mirrored().mirrored);
+    }
+}
@@ -1,30 +1,83 @@
 mod ai;
 mod arena;
+mod boost;
 mod camera;
 mod combine;
 mod config;
+mod console;
 mod control;
+mod engine;
 mod events;
+mod game_mode;
+mod gauge;
+mod hay_structure;
+mod herding;
+mod hud;
+mod impact;
 mod input;
+mod layout;
+mod menu;
+mod minimap;
+mod mixer;
+mod music;
 mod obstacle;
+mod particles;
+mod photo_mode;
+mod pickup;
 mod sounds;
+mod spatial_audio;
+mod tuning;
 mod ui;
 
 use ai::combine_ai_system;
-use arena::setup_arena;
+use arena::{repeating_texture_system, setup_arena};
 use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
 use bevy_rapier3d::prelude::*;
+use boost::{boost_control_events, boost_exhaust_system};
 use camera::{camera_events, SwitchCameraEvent};
-use combine::{combine_speedometer_system, spawn_combines, transmission_system};
+use combine::{
+    combine_damage_system, combine_spec_system, combine_speedometer_system, spawn_combines,
+    transmission_system,
+};
 
+use console::{console_system, ConsolePlugin};
 use control::{speed_control_events, steer_control_events};
-use events::{SoundSampleEvent, SpeedControlEvent, SteerControlEvent};
-use input::{keyboard_input, Settings};
+use engine::engine_system;
+use events::{
+    BoostControlEvent, GearShiftEvent, SoundSampleEvent, SpeedControlEvent, SteerControlEvent,
+};
+use herding::{herding_count_system, herding_injury_system, herding_ui_system, setup_herding};
+use gauge::gauge_system;
+use hud::{hud_system, match_stats_system, name_plate_system, setup_hud};
+use impact::{camera_shake_system, impact_system, ImpactEffects};
+use input::keyboard_input;
+use menu::{
+    close_match, close_menu, leave_match_system, menu_system, remember_persistent_entities,
+    setup_menu, AppState, MatchSetup, Menu, PersistentEntities,
+};
+use minimap::{minimap_system, setup_minimap};
+use mixer::{
+    mixer_fade_system, mixer_panel_system, mixer_save_system, setup_mixer_panel, AudioMixer,
+    MixerPanel,
+};
+use music::{music_mood_system, music_system, setup_music};
 
-use obstacle::{collision_check_system, cow_ai_system, spawn_cows, spawn_hay_bales};
-use sounds::{play_sample, setup_sounds};
+use obstacle::{
+    collision_check_system, cow_ai_system, cow_knockdown_system, hay_bale_break_system,
+    spawn_cows, spawn_hay_bales, straw_debris_system,
+};
+use particles::{particle_system, setup_particles};
+use photo_mode::PhotoModePlugin;
+use pickup::{
+    pickup_collect_system, pickup_spawn_system, power_up_system, power_up_ui_system,
+    setup_pickups,
+};
+use sounds::{engine_sound_system, play_sample, setup_sounds, spawn_engine_sounds, Voices};
+use spatial_audio::{stop_sound_emitters, SpatialAudioPlugin};
+use tuning::TuningPlugin;
 use ui::{
-    change_text_system, combine_ui_system, infotext_system, update_debug_ui_system, DebugInfo,
+    change_text_system, combine_ui_system, infotext_system, speedometer_system,
+    update_debug_ui_system, DebugInfo, SpeedUnit,
 };
 
 fn main() {
@@ -45,34 +98,104 @@ fn main() {
             ..default()
         })
         .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_plugin(SpatialAudioPlugin)
+        .add_plugin(PhotoModePlugin)
+        .add_plugin(ConsolePlugin)
+        .add_plugin(TuningPlugin)
         .insert_resource(ClearColor(Color::rgb(0.53, 0.80, 0.92)))
         .insert_resource(debug_info)
-        .insert_resource(Settings::default())
+        .insert_resource(AudioMixer::load())
+        .insert_resource(MixerPanel::default())
+        .insert_resource(Voices::default())
+        .insert_resource(ImpactEffects::default())
+        .insert_resource(SpeedUnit::default())
+        .insert_resource(MatchSetup::from_args())
+        .insert_resource(Menu::default())
+        .insert_resource(PersistentEntities::default())
+        .add_state(AppState::Menu)
         .add_event::<SpeedControlEvent>()
         .add_event::<SteerControlEvent>()
+        .add_event::<BoostControlEvent>()
+        .add_event::<GearShiftEvent>()
         .add_event::<SwitchCameraEvent>()
         .add_event::<SoundSampleEvent>()
-        .add_startup_system(setup_arena)
         .add_startup_system(setup_sounds)
-        .add_startup_system(spawn_combines)
-        .add_startup_system(camera::spawn_camera)
-        .add_startup_system(infotext_system)
-        .add_startup_system(spawn_hay_bales)
-        .add_startup_system(spawn_cows)
-        .add_system(camera::pan_orbit_camera)
-        .add_system(bevy::window::close_on_esc)
-        .add_system(keyboard_input)
-        .add_system(speed_control_events)
-        .add_system(steer_control_events)
-        .add_system(camera_events)
-        .add_system(update_debug_ui_system)
-        .add_system(change_text_system)
-        .add_system(collision_check_system)
+        .add_startup_system(setup_music)
+        .add_startup_system(setup_particles)
+        .add_startup_system(setup_mixer_panel)
         .add_system(play_sample)
-        .add_system(combine_ui_system)
-        .add_system(combine_speedometer_system)
-        .add_system(transmission_system)
-        .add_system(cow_ai_system)
-        .add_system(combine_ai_system)
+        .add_system(music_mood_system)
+        .add_system(music_system)
+        .add_system(mixer_fade_system)
+        .add_system(mixer_panel_system)
+        .add_system(mixer_save_system)
+        .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(setup_menu))
+        .add_system_set(SystemSet::on_update(AppState::Menu).with_system(menu_system))
+        .add_system_set(SystemSet::on_exit(AppState::Menu).with_system(close_menu))
+        .add_system_set(
+            SystemSet::on_exit(AppState::Match)
+                .with_system(close_match)
+                .with_system(stop_sound_emitters),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Match)
+                .with_system(remember_persistent_entities)
+                .with_system(setup_arena)
+                .with_system(spawn_combines)
+                .with_system(camera::spawn_camera)
+                .with_system(infotext_system)
+                .with_system(spawn_hay_bales)
+                .with_system(spawn_cows)
+                .with_system(setup_herding)
+                .with_system(setup_pickups)
+                .with_system(setup_minimap)
+                .with_system(setup_hud),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Match)
+                .with_system(camera::pan_orbit_camera)
+                .with_system(camera::chase_camera_system)
+                .with_system(camera::free_camera_follow_system)
+                .with_system(impact_system)
+                .with_system(camera_shake_system.after(camera::chase_camera_system))
+                .with_system(leave_match_system.after(console_system))
+                .with_system(keyboard_input)
+                .with_system(speed_control_events)
+                .with_system(steer_control_events)
+                .with_system(boost_control_events)
+                .with_system(boost_exhaust_system)
+                .with_system(camera_events)
+                .with_system(update_debug_ui_system)
+                .with_system(change_text_system)
+                .with_system(collision_check_system)
+                .with_system(spawn_engine_sounds)
+                .with_system(engine_sound_system)
+                .with_system(combine_ui_system)
+                .with_system(speedometer_system)
+                .with_system(gauge_system.after(combine_ui_system))
+                .with_system(combine_speedometer_system)
+                .with_system(engine_system)
+                .with_system(transmission_system)
+                .with_system(cow_ai_system)
+                .with_system(cow_knockdown_system)
+                .with_system(repeating_texture_system)
+                .with_system(herding_count_system)
+                .with_system(herding_injury_system)
+                .with_system(herding_ui_system)
+                .with_system(hay_bale_break_system)
+                .with_system(straw_debris_system)
+                .with_system(particle_system)
+                .with_system(combine_ai_system)
+                .with_system(combine_damage_system)
+                .with_system(combine_spec_system)
+                .with_system(pickup_spawn_system)
+                .with_system(pickup_collect_system)
+                .with_system(power_up_system)
+                .with_system(power_up_ui_system)
+                .with_system(minimap_system)
+                .with_system(match_stats_system)
+                .with_system(hud_system)
+                .with_system(name_plate_system),
+        )
         .run()
 }
@@ -0,0 +1,818 @@
+use std::{collections::HashSet, marker::PhantomData};
+
+use bevy::{
+    app::AppExit,
+    ecs::system::SystemParam,
+    prelude::*,
+    window::{PresentMode, WindowMode},
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    game_mode::GameMode,
+    herding::HerdingMatch,
+    impact::ImpactEffects,
+    layout::{ArenaLayout, Symmetry},
+    mixer::{AudioBus, AudioMixer},
+    music::{Music, MusicMood},
+    ui::{DebugInfo, SpeedUnit},
+};
+
+/// The readme, which lists the assets the credits page thanks.
+const README: &str = include_str!("../readme.md");
+const CREDITS_HEADING: &str = "## Asset credits";
+/// How much the volume changes with each press in the audio settings.
+const VOLUME_STEP: f32 = 0.1;
+
+const BUTTON_COLOUR: Color = Color::rgba(0.1, 0.1, 0.1, 0.8);
+const SELECTED_BUTTON_COLOUR: Color = Color::rgb(0.45, 0.6, 0.2);
+
+/// Whether the game is sitting in the menu or playing a match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AppState {
+    Menu,
+    Match,
+}
+
+/// Arenas that can be picked in the menu.
+const ARENAS: [Option<Symmetry>; 5] = [
+    None,
+    Some(Symmetry::Rotational),
+    Some(Symmetry::Mirror),
+    Some(Symmetry::Quad),
+    Some(Symmetry::None),
+];
+
+/// What the next match is played with, as picked in the menu. Starts from the command line
+/// arguments.
+#[derive(Resource)]
+pub struct MatchSetup {
+    /// Index into [`ARENAS`].
+    arena: usize,
+    /// The arena the match will be played in, generated as soon as it is picked so the number of
+    /// combines it has room for is known.
+    layout: ArenaLayout,
+    mode: GameMode,
+    opponents: usize,
+}
+
+impl MatchSetup {
+    pub fn from_args() -> MatchSetup {
+        let layout = ArenaLayout::from_args();
+        let arena = match layout.seed {
+            Some(_) => ARENAS
+                .iter()
+                .position(|arena| *arena == Some(layout.symmetry))
+                .unwrap_or(0),
+            None => 0,
+        };
+        MatchSetup {
+            arena,
+            opponents: max_opponents(&layout),
+            layout,
+            mode: GameMode::from_args(),
+        }
+    }
+
+    fn set_arena(&mut self, arena: usize) {
+        self.arena = arena;
+        self.layout = match ARENAS[arena] {
+            Some(symmetry) => ArenaLayout::generate(rand::random(), symmetry),
+            None => ArenaLayout::classic(),
+        };
+        self.opponents = self.opponents.min(max_opponents(&self.layout));
+    }
+
+    /// Gets the arena for the match, with spawn points only for the combines taking part.
+    fn match_layout(&self) -> ArenaLayout {
+        let mut layout = self.layout.clone();
+        layout.spawn_points.truncate(self.opponents + 1);
+        layout.team_pens.truncate(self.opponents + 1);
+        layout
+    }
+}
+
+fn max_opponents(layout: &ArenaLayout) -> usize {
+    layout.spawn_points.len().saturating_sub(1)
+}
+
+fn arena_name(arena: Option<Symmetry>) -> String {
+    match arena {
+        Some(symmetry) => format!("Random ({})", symmetry.name()),
+        None => "Classic".to_string(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuPage {
+    Main,
+    MatchSetup,
+    Settings,
+    Audio,
+    Graphics,
+    Controls,
+    Credits,
+}
+
+impl MenuPage {
+    fn title(&self) -> &'static str {
+        match self {
+            MenuPage::Main => "Combine Derby",
+            MenuPage::MatchSetup => "Match setup",
+            MenuPage::Settings => "Settings",
+            MenuPage::Audio => "Audio",
+            MenuPage::Graphics => "Graphics",
+            MenuPage::Controls => "Controls",
+            MenuPage::Credits => "Credits",
+        }
+    }
+
+    /// Gets the page Back returns to.
+    fn parent(&self) -> Option<MenuPage> {
+        match self {
+            MenuPage::Main => None,
+            MenuPage::MatchSetup | MenuPage::Settings | MenuPage::Credits => Some(MenuPage::Main),
+            MenuPage::Audio | MenuPage::Graphics | MenuPage::Controls => Some(MenuPage::Settings),
+        }
+    }
+
+    fn items(&self) -> Vec<MenuItem> {
+        match self {
+            MenuPage::Main => {
+                let mut items = vec![
+                    MenuItem::StartMatch,
+                    MenuItem::Page(MenuPage::MatchSetup),
+                    MenuItem::Page(MenuPage::Settings),
+                    MenuItem::Page(MenuPage::Credits),
+                ];
+                // a browser tab can't close itself.
+                if cfg!(not(target_arch = "wasm32")) {
+                    items.push(MenuItem::Quit);
+                }
+                items
+            }
+            MenuPage::MatchSetup => vec![
+                MenuItem::StartMatch,
+                MenuItem::Arena,
+                MenuItem::Mode,
+                MenuItem::Opponents,
+                MenuItem::Back,
+            ],
+            MenuPage::Settings => vec![
+                MenuItem::Page(MenuPage::Audio),
+                MenuItem::Page(MenuPage::Graphics),
+                MenuItem::Page(MenuPage::Controls),
+                MenuItem::Back,
+            ],
+            MenuPage::Audio => AudioBus::ALL
+                .iter()
+                .map(|bus| MenuItem::Volume(*bus))
+                .chain([MenuItem::Back])
+                .collect(),
+            MenuPage::Graphics => vec![
+                MenuItem::Fullscreen,
+                MenuItem::VSync,
+                MenuItem::SpeedUnit,
+                MenuItem::CameraShake,
+                MenuItem::SlowMotion,
+                MenuItem::DebugInfo,
+                MenuItem::Back,
+            ],
+            MenuPage::Controls | MenuPage::Credits => vec![MenuItem::Back],
+        }
+    }
+
+    /// Gets the text shown above the buttons, if the page has any.
+    fn body(&self) -> Option<String> {
+        match self {
+            MenuPage::Controls => Some(controls_text()),
+            MenuPage::Credits => Some(credits_text(README)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuItem {
+    StartMatch,
+    Page(MenuPage),
+    Quit,
+    Back,
+    Arena,
+    Mode,
+    Opponents,
+    Volume(AudioBus),
+    Fullscreen,
+    VSync,
+    SpeedUnit,
+    CameraShake,
+    SlowMotion,
+    DebugInfo,
+}
+
+/// What the player asked the menu to do this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+/// The page showing in the menu and the button picked on it.
+#[derive(Resource)]
+pub struct Menu {
+    page: MenuPage,
+    selected: usize,
+    /// Whether the buttons need spawning again for a new page.
+    rebuild: bool,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Menu {
+            page: MenuPage::Main,
+            selected: 0,
+            rebuild: true,
+        }
+    }
+}
+
+impl Menu {
+    fn open(&mut self, page: MenuPage) {
+        self.page = page;
+        self.selected = 0;
+        self.rebuild = true;
+    }
+
+    /// Moves the selection up or down, wrapping around the ends of the page.
+    fn move_selection(&mut self, up: bool) {
+        let count = self.page.items().len();
+        self.selected = if up {
+            (self.selected + count - 1) % count
+        } else {
+            (self.selected + 1) % count
+        };
+    }
+
+    /// Goes back a page. Returns false on the main page, which has nowhere to go back to.
+    fn back(&mut self) -> bool {
+        match self.page.parent() {
+            Some(parent) => {
+                let came_from = MenuItem::Page(self.page);
+                self.open(parent);
+                self.selected = parent
+                    .items()
+                    .iter()
+                    .position(|item| *item == came_from)
+                    .unwrap_or(0);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The lines of the controls list in the readme.
+fn controls_text() -> String {
+    let controls = README
+        .split("## Controls")
+        .nth(1)
+        .unwrap_or_default()
+        .split("\n#")
+        .next()
+        .unwrap_or_default();
+    controls
+        .lines()
+        .map(|line| line.trim().trim_start_matches("* "))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.replace('`', ""))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Gets each credit from the readme's asset list, one per line.
+fn credits_text(readme: &str) -> String {
+    let credits = readme
+        .split(CREDITS_HEADING)
+        .nth(1)
+        .unwrap_or_default()
+        .split("\n#")
+        .next()
+        .unwrap_or_default();
+    credits
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[derive(Component)]
+pub struct MenuUi;
+
+#[derive(Component)]
+pub struct MenuCamera;
+
+/// A button on the menu, by its position on the page.
+#[derive(Component)]
+pub struct MenuButton(usize);
+
+#[derive(Component)]
+pub struct MenuButtonText(usize);
+
+pub fn setup_menu(mut commands: Commands, mut menu: ResMut<Menu>, mut music: ResMut<Music>) {
+    *menu = Menu::default();
+    music.mood = MusicMood::Menu;
+
+    commands.spawn(Camera2dBundle::default()).insert(MenuCamera);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(MenuUi);
+}
+
+pub fn close_menu(
+    mut commands: Commands,
+    menu_query: Query<Entity, Or<(With<MenuUi>, With<MenuCamera>)>>,
+) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Entities that were already around when the match started. Everything else is despawned when
+/// it ends.
+#[derive(Resource, Default)]
+pub struct PersistentEntities(HashSet<Entity>);
+
+pub fn remember_persistent_entities(
+    mut persistent: ResMut<PersistentEntities>,
+    entity_query: Query<Entity>,
+) {
+    persistent.0 = entity_query.iter().collect();
+}
+
+/// Escape leaves the match for the menu. While the console is open, Escape closes it instead.
+pub fn leave_match_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        // used up here so the menu doesn't take it as going back too.
+        keys.clear_just_pressed(KeyCode::Escape);
+        let _ = app_state.set(AppState::Menu);
+    }
+}
+
+/// Despawns everything the match spawned and puts the physics back to normal, so another match
+/// can start from scratch.
+pub fn close_match(
+    mut commands: Commands,
+    persistent: Res<PersistentEntities>,
+    mut impact_effects: ResMut<ImpactEffects>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+    root_query: Query<Entity, Without<Parent>>,
+) {
+    for entity in root_query.iter() {
+        if !persistent.0.contains(&entity) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    commands.remove_resource::<HerdingMatch>();
+
+    impact_effects.reset_time_scale();
+    // the console can change gravity and the time scale.
+    *rapier_configuration = RapierConfiguration::default();
+}
+
+/// Reads the menu controls from the keyboard and every connected gamepad.
+fn read_menu_input(
+    keys: &Input<KeyCode>,
+    gamepads: &Gamepads,
+    gamepad_buttons: &Input<GamepadButton>,
+) -> Option<MenuInput> {
+    let key_inputs = [
+        ([KeyCode::Up, KeyCode::W], MenuInput::Up),
+        ([KeyCode::Down, KeyCode::S], MenuInput::Down),
+        ([KeyCode::Left, KeyCode::A], MenuInput::Left),
+        ([KeyCode::Right, KeyCode::D], MenuInput::Right),
+        ([KeyCode::Return, KeyCode::Space], MenuInput::Select),
+        ([KeyCode::Escape, KeyCode::Back], MenuInput::Back),
+    ];
+    for (key_codes, input) in key_inputs {
+        if keys.any_just_pressed(key_codes) {
+            return Some(input);
+        }
+    }
+
+    let button_inputs = [
+        (GamepadButtonType::DPadUp, MenuInput::Up),
+        (GamepadButtonType::DPadDown, MenuInput::Down),
+        (GamepadButtonType::DPadLeft, MenuInput::Left),
+        (GamepadButtonType::DPadRight, MenuInput::Right),
+        (GamepadButtonType::South, MenuInput::Select),
+        (GamepadButtonType::East, MenuInput::Back),
+    ];
+    for gamepad in gamepads.iter() {
+        for (button_type, input) in button_inputs {
+            if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button_type)) {
+                return Some(input);
+            }
+        }
+    }
+    None
+}
+
+/// Steps through a list of options, wrapping around at the ends.
+fn cycle(index: usize, count: usize, input: MenuInput) -> usize {
+    if input == MenuInput::Left {
+        (index + count - 1) % count
+    } else {
+        (index + 1) % count
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// Moves around the menu with the mouse, the arrow keys or WASD, or a gamepad's d-pad, and
+/// changes the settings picked.
+#[allow(clippy::too_many_arguments)]
+/// The settings that can be changed from the menu.
+#[derive(SystemParam)]
+pub struct MenuSettings<'w, 's> {
+    mixer: ResMut<'w, AudioMixer>,
+    impact_effects: ResMut<'w, ImpactEffects>,
+    debug_info: ResMut<'w, DebugInfo>,
+    speed_unit: ResMut<'w, SpeedUnit>,
+    windows: ResMut<'w, Windows>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+pub fn menu_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut menu: ResMut<Menu>,
+    mut match_setup: ResMut<MatchSetup>,
+    mut settings: MenuSettings,
+    mut music: ResMut<Music>,
+    mut app_state: ResMut<State<AppState>>,
+    mut app_exit_events: EventWriter<AppExit>,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    menu_query: Query<Entity, With<MenuUi>>,
+    mut button_query: Query<(&MenuButton, &mut BackgroundColor)>,
+    mut text_query: Query<(&MenuButtonText, &mut Text)>,
+) {
+    let mut input = read_menu_input(&keys, &gamepads, &gamepad_buttons);
+    for (interaction, button) in interaction_query.iter() {
+        match interaction {
+            Interaction::Clicked => {
+                menu.selected = button.0;
+                input = Some(MenuInput::Select);
+            }
+            Interaction::Hovered => menu.selected = button.0,
+            Interaction::None => (),
+        }
+    }
+
+    let items = menu.page.items();
+    let item = items[menu.selected.min(items.len() - 1)];
+    match input {
+        Some(MenuInput::Up) => menu.move_selection(true),
+        Some(MenuInput::Down) => menu.move_selection(false),
+        Some(MenuInput::Back) => {
+            menu.back();
+        }
+        Some(input) => match item {
+            MenuItem::StartMatch if input == MenuInput::Select => {
+                commands.insert_resource(match_setup.match_layout());
+                commands.insert_resource(match_setup.mode);
+                music.mood = MusicMood::Match;
+                let _ = app_state.set(AppState::Match);
+            }
+            MenuItem::Page(page) if input == MenuInput::Select => menu.open(page),
+            MenuItem::Quit if input == MenuInput::Select => app_exit_events.send(AppExit),
+            MenuItem::Back if input == MenuInput::Select => {
+                menu.back();
+            }
+            MenuItem::Arena => {
+                let arena = cycle(match_setup.arena, ARENAS.len(), input);
+                match_setup.set_arena(arena);
+            }
+            MenuItem::Mode => {
+                match_setup.mode = match match_setup.mode {
+                    GameMode::Derby => GameMode::Herding,
+                    GameMode::Herding => GameMode::Derby,
+                };
+            }
+            MenuItem::Opponents => {
+                let count = max_opponents(&match_setup.layout) + 1;
+                match_setup.opponents = cycle(match_setup.opponents, count, input);
+            }
+            MenuItem::Volume(bus) => match input {
+                MenuInput::Left => {
+                    let volume = settings.mixer.volume(bus) - VOLUME_STEP;
+                    settings.mixer.set_volume(bus, volume);
+                }
+                MenuInput::Right => {
+                    let volume = settings.mixer.volume(bus) + VOLUME_STEP;
+                    settings.mixer.set_volume(bus, volume);
+                }
+                _ => settings.mixer.toggle_mute(bus),
+            },
+            MenuItem::Fullscreen => {
+                if let Some(window) = settings.windows.get_primary_mut() {
+                    window.set_mode(match window.mode() {
+                        WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+                        _ => WindowMode::Windowed,
+                    });
+                }
+            }
+            MenuItem::VSync => {
+                if let Some(window) = settings.windows.get_primary_mut() {
+                    window.set_present_mode(match window.present_mode() {
+                        PresentMode::AutoNoVsync => PresentMode::AutoVsync,
+                        _ => PresentMode::AutoNoVsync,
+                    });
+                }
+            }
+            MenuItem::SpeedUnit => {
+                let units = SpeedUnit::ALL;
+                let index = units.iter().position(|unit| *unit == *settings.speed_unit);
+                *settings.speed_unit = units[cycle(index.unwrap_or(0), units.len(), input)];
+            }
+            MenuItem::CameraShake => {
+                settings.impact_effects.camera_shake = !settings.impact_effects.camera_shake
+            }
+            MenuItem::SlowMotion => {
+                settings.impact_effects.slow_motion = !settings.impact_effects.slow_motion
+            }
+            MenuItem::DebugInfo => settings.debug_info.enabled = !settings.debug_info.enabled,
+            _ => (),
+        },
+        None => (),
+    }
+
+    let window = settings.windows.get_primary();
+    let label = |item: MenuItem| match item {
+        MenuItem::StartMatch => "Start match".to_string(),
+        MenuItem::Page(page) => page.title().to_string(),
+        MenuItem::Quit => "Quit".to_string(),
+        MenuItem::Back => "Back".to_string(),
+        MenuItem::Arena => format!("Arena: {}", arena_name(ARENAS[match_setup.arena])),
+        MenuItem::Mode => format!("Mode: {:?}", match_setup.mode),
+        MenuItem::Opponents => format!("Opponents: {}", match_setup.opponents),
+        MenuItem::Volume(bus) => format!(
+            "{}: {:.0}%{}",
+            bus.name(),
+            100.0 * settings.mixer.volume(bus),
+            if settings.mixer.muted(bus) {
+                " (muted)"
+            } else {
+                ""
+            }
+        ),
+        MenuItem::Fullscreen => format!(
+            "Fullscreen: {}",
+            on_off(window.map_or(false, |window| window.mode() != WindowMode::Windowed))
+        ),
+        MenuItem::VSync => format!(
+            "VSync: {}",
+            on_off(window.map_or(true, |window| {
+                window.present_mode() != PresentMode::AutoNoVsync
+            }))
+        ),
+        MenuItem::SpeedUnit => format!("Speed units: {}", settings.speed_unit.name()),
+        MenuItem::CameraShake => format!(
+            "Camera shake: {}",
+            on_off(settings.impact_effects.camera_shake)
+        ),
+        MenuItem::SlowMotion => format!(
+            "Slow motion on big hits: {}",
+            on_off(settings.impact_effects.slow_motion)
+        ),
+        MenuItem::DebugInfo => format!("Debug info: {}", on_off(settings.debug_info.enabled)),
+    };
+
+    if menu.rebuild {
+        menu.rebuild = false;
+        let root = match menu_query.get_single() {
+            Ok(root) => root,
+            Err(_) => return,
+        };
+        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        let text_style = |font_size| TextStyle {
+            font: font.clone(),
+            font_size,
+            color: Color::WHITE,
+        };
+
+        commands.entity(root).despawn_descendants();
+        commands.entity(root).with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(menu.page.title(), text_style(60.0)).with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+            if let Some(body) = menu.page.body() {
+                parent.spawn(
+                    TextBundle::from_section(body, text_style(16.0)).with_style(Style {
+                        max_size: Size::new(Val::Px(900.0), Val::Undefined),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    }),
+                );
+            }
+            for (index, item) in menu.page.items().into_iter().enumerate() {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(420.0), Val::Px(50.0)),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOUR.into(),
+                        ..default()
+                    })
+                    .insert(MenuButton(index))
+                    .with_children(|button| {
+                        button
+                            .spawn(TextBundle::from_section(label(item), text_style(26.0)))
+                            .insert(MenuButtonText(index));
+                    });
+            }
+        });
+        return;
+    }
+
+    for (button, mut background_color) in button_query.iter_mut() {
+        *background_color = if button.0 == menu.selected {
+            SELECTED_BUTTON_COLOUR.into()
+        } else {
+            BUTTON_COLOUR.into()
+        };
+    }
+    for (button_text, mut text) in text_query.iter_mut() {
+        if let Some(item) = items.get(button_text.0) {
+            text.sections[0].value = label(*item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    use crate::{
+        game_mode::GameMode,
+        impact::ImpactEffects,
+        layout::ArenaLayout,
+        menu::{
+            close_match, credits_text, cycle, max_opponents, remember_persistent_entities,
+            MatchSetup, Menu, MenuInput, MenuItem, MenuPage, PersistentEntities, ARENAS, README,
+        },
+    };
+
+    #[test]
+    fn close_match_despawns_match_entities_test() {
+        let mut world = World::new();
+        world.init_resource::<PersistentEntities>();
+        world.init_resource::<ImpactEffects>();
+        world.insert_resource(RapierConfiguration {
+            gravity: Vec3::ZERO,
+            physics_pipeline_active: false,
+            ..default()
+        });
+        let persistent = world.spawn_empty().id();
+        let mut remember = IntoSystem::into_system(remember_persistent_entities);
+        remember.initialize(&mut world);
+        remember.run((), &mut world);
+
+        let match_entity = world.spawn_empty().id();
+        let match_child = world.spawn_empty().id();
+        world.entity_mut(match_entity).push_children(&[match_child]);
+        let mut close = IntoSystem::into_system(close_match);
+        close.initialize(&mut world);
+        close.run((), &mut world);
+        close.apply_buffers(&mut world);
+
+        assert!(world.get_entity(persistent).is_some());
+        assert!(world.get_entity(match_entity).is_none());
+        assert!(world.get_entity(match_child).is_none());
+        let rapier_configuration = world.resource::<RapierConfiguration>();
+        assert!(rapier_configuration.physics_pipeline_active);
+        assert_eq!(
+            RapierConfiguration::default().gravity,
+            rapier_configuration.gravity
+        );
+    }
+
+    const PAGES: [MenuPage; 7] = [
+        MenuPage::Main,
+        MenuPage::MatchSetup,
+        MenuPage::Settings,
+        MenuPage::Audio,
+        MenuPage::Graphics,
+        MenuPage::Controls,
+        MenuPage::Credits,
+    ];
+
+    #[test]
+    fn every_page_leads_back_to_main_test() {
+        for page in PAGES {
+            match page.parent() {
+                Some(parent) => {
+                    assert!(page.items().contains(&MenuItem::Back));
+                    assert!(
+                        parent.items().contains(&MenuItem::Page(page)) || parent == MenuPage::Main
+                    );
+                }
+                None => assert_eq!(MenuPage::Main, page),
+            }
+        }
+    }
+
+    #[test]
+    fn back_selects_page_came_from_test() {
+        let mut menu = Menu::default();
+        menu.open(MenuPage::Settings);
+        menu.open(MenuPage::Graphics);
+
+        assert!(menu.back());
+        assert_eq!(MenuPage::Settings, menu.page);
+        assert_eq!(
+            MenuItem::Page(MenuPage::Graphics),
+            menu.page.items()[menu.selected]
+        );
+        assert!(menu.back());
+        assert!(!menu.back());
+        assert_eq!(MenuPage::Main, menu.page);
+    }
+
+    #[test]
+    fn selection_wraps_test() {
+        let mut menu = Menu::default();
+        menu.move_selection(true);
+
+        assert_eq!(MenuPage::Main.items().len() - 1, menu.selected);
+        menu.move_selection(false);
+        assert_eq!(0, menu.selected);
+
+        assert_eq!(2, cycle(0, 3, MenuInput::Left));
+        assert_eq!(0, cycle(2, 3, MenuInput::Right));
+        assert_eq!(1, cycle(0, 3, MenuInput::Select));
+    }
+
+    #[test]
+    fn opponents_fit_the_arena_test() {
+        let mut match_setup = MatchSetup {
+            arena: 0,
+            layout: ArenaLayout::classic(),
+            mode: GameMode::Derby,
+            opponents: 0,
+        };
+        let generated = ARENAS.iter().position(|arena| arena.is_some()).unwrap();
+        match_setup.set_arena(generated);
+        match_setup.opponents = max_opponents(&match_setup.layout);
+        match_setup.set_arena(0);
+
+        assert!(match_setup.opponents <= max_opponents(&ArenaLayout::classic()));
+        assert_eq!(
+            match_setup.opponents + 1,
+            match_setup.match_layout().spawn_points.len()
+        );
+    }
+
+    #[test]
+    fn credits_come_from_readme_test() {
+        let readme = "# Game\n\n## Asset credits\n\nMoo: http://moo\n\nHay: http://hay\n";
+        assert_eq!("Moo: http://moo\nHay: http://hay", credits_text(readme));
+
+        assert!(credits_text(README).contains("Moo sound"));
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::{
+    combine::{combine_colour, Combine, PLAYER_COMBINE_ID},
+    layout::ArenaLayout,
+    obstacle::{Cow, HayBale},
+    pickup::Pickup,
+};
+
+/// Width and height of the minimap on screen, in pixels.
+const MINIMAP_SIZE: f32 = 200.0;
+/// Distance from the player to the edge of the minimap, in metres.
+const MINIMAP_RANGE: f32 = 200.0;
+
+const COMBINE_DOT_SIZE: f32 = 10.0;
+const COW_DOT_SIZE: f32 = 5.0;
+const HAY_BALE_DOT_SIZE: f32 = 4.0;
+const RAMP_DOT_SIZE: f32 = 12.0;
+const PICKUP_DOT_SIZE: f32 = 7.0;
+
+const COW_DOT_COLOUR: Color = Color::WHITE;
+const HAY_BALE_DOT_COLOUR: Color = Color::rgb(0.85, 0.75, 0.35);
+const RAMP_DOT_COLOUR: Color = Color::rgb(0.55, 0.45, 0.35);
+
+/// The minimap, drawn as dots over a square centred on the player's combine.
+#[derive(Component)]
+pub struct MinimapUi;
+
+/// One dot on the minimap. Dots are reused for whatever needs showing each frame.
+#[derive(Component)]
+pub struct MinimapDot;
+
+pub fn setup_minimap(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(45.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                overflow: Overflow::Hidden,
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.2, 0.05, 0.6).into(),
+            ..default()
+        })
+        .insert(MinimapUi);
+}
+
+/// Gets where a position appears on a minimap centred on the player and turned so they face up,
+/// from -1 to 1 across the map. Returns `None` for positions off the edge of the map.
+fn minimap_point(player_position: Vec3, player_yaw: f32, position: Vec3) -> Option<Vec2> {
+    let offset = Quat::from_rotation_y(-player_yaw) * (position - player_position);
+    let point = Vec2::new(offset.x, offset.z) / MINIMAP_RANGE;
+    if point.x.abs() > 1.0 || point.y.abs() > 1.0 {
+        None
+    } else {
+        Some(point)
+    }
+}
+
+/// Moves the minimap dots to show the combines, cows, hay bales, ramps and pickups around the
+/// player.
+#[allow(clippy::too_many_arguments)]
+pub fn minimap_system(
+    mut commands: Commands,
+    arena_layout: Res<ArenaLayout>,
+    map_query: Query<Entity, With<MinimapUi>>,
+    mut dot_query: Query<(&mut Style, &mut BackgroundColor, &mut Visibility), With<MinimapDot>>,
+    combine_query: Query<(&Combine, &Transform)>,
+    cow_query: Query<&Transform, With<Cow>>,
+    bale_query: Query<&Transform, With<HayBale>>,
+    pickup_query: Query<(&Pickup, &Transform)>,
+) {
+    let map = match map_query.get_single() {
+        Ok(map) => map,
+        Err(_) => return,
+    };
+
+    let (player_position, player_yaw) = combine_query
+        .iter()
+        .find(|(combine, _)| combine.combine_id == PLAYER_COMBINE_ID)
+        .map(|(_, transform)| {
+            let back = transform.back();
+            (transform.translation, back.x.atan2(back.z))
+        })
+        .unwrap_or((Vec3::ZERO, 0.0));
+
+    let mut markers: Vec<(Vec3, Color, f32)> = Vec::new();
+    for ramp in arena_layout.ramps.iter() {
+        markers.push((ramp.transform().translation, RAMP_DOT_COLOUR, RAMP_DOT_SIZE));
+    }
+    for transform in bale_query.iter() {
+        markers.push((
+            transform.translation,
+            HAY_BALE_DOT_COLOUR,
+            HAY_BALE_DOT_SIZE,
+        ));
+    }
+    for transform in cow_query.iter() {
+        markers.push((transform.translation, COW_DOT_COLOUR, COW_DOT_SIZE));
+    }
+    for (pickup, transform) in pickup_query.iter() {
+        markers.push((transform.translation, pickup.kind.colour(), PICKUP_DOT_SIZE));
+    }
+    for (combine, transform) in combine_query.iter() {
+        markers.push((
+            transform.translation,
+            combine_colour(combine.combine_id),
+            COMBINE_DOT_SIZE,
+        ));
+    }
+
+    let mut dots = markers.into_iter().filter_map(|(position, colour, size)| {
+        minimap_point(player_position, player_yaw, position).map(|point| (point, colour, size))
+    });
+
+    for (mut style, mut background_color, mut visibility) in dot_query.iter_mut() {
+        match dots.next() {
+            Some((point, colour, size)) => {
+                style.position = UiRect {
+                    left: Val::Px(((point.x + 1.0) * 0.5 * MINIMAP_SIZE) - (size * 0.5)),
+                    top: Val::Px(((point.y + 1.0) * 0.5 * MINIMAP_SIZE) - (size * 0.5)),
+                    ..default()
+                };
+                style.size = Size::new(Val::Px(size), Val::Px(size));
+                *background_color = colour.into();
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+
+    // dots for anything left over are added now and used from the next frame.
+    let extra_dots = dots.count();
+    commands.entity(map).with_children(|parent| {
+        for _ in 0..extra_dots {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(MinimapDot);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::prelude::*;
+
+    use crate::minimap::{minimap_point, MINIMAP_RANGE};
+
+    #[test]
+    fn ahead_of_player_is_up_test() {
+        // facing -Z, the usual way forward.
+        let point = minimap_point(Vec3::ZERO, 0.0, Vec3::new(0.0, 0.0, -50.0)).unwrap();
+
+        assert!(point.x.abs() < 0.001);
+        assert!(point.y < 0.0);
+    }
+
+    #[test]
+    fn map_turns_with_player_test() {
+        // with its back to +X, the player faces -X.
+        let player = Vec3::new(10.0, 0.0, 10.0);
+        let ahead = minimap_point(player, FRAC_PI_2, Vec3::new(-40.0, 0.0, 10.0)).unwrap();
+        let right = minimap_point(player, FRAC_PI_2, Vec3::new(10.0, 0.0, -40.0)).unwrap();
+
+        assert!(ahead.x.abs() < 0.001 && ahead.y < 0.0);
+        assert!(right.y.abs() < 0.001 && right.x > 0.0);
+    }
+
+    #[test]
+    fn far_away_is_off_the_map_test() {
+        let far = Vec3::new(MINIMAP_RANGE * 1.5, 0.0, 0.0);
+
+        assert_eq!(None, minimap_point(Vec3::ZERO, 0.0, far));
+    }
+}
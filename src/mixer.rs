@@ -0,0 +1,362 @@
+use bevy::prelude::*;
+
+use crate::{config::DEFAULT_VOLUME, console::Console};
+
+/// Where the mixer settings are saved between sessions.
+#[cfg(not(target_arch = "wasm32"))]
+const MIXER_SETTINGS_PATH: &str = "mixer.cfg";
+/// Time for a bus to fade from silent to full volume, or back.
+const FADE_SECONDS: f32 = 0.4;
+/// How much the volume changes with each press in the mixer panel.
+const VOLUME_STEP: f32 = 0.1;
+
+/// A group of sounds whose volume is set together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBus {
+    /// Scales every other bus.
+    Master,
+    Music,
+    Effects,
+    Engine,
+    Ambient,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 5] = [
+        AudioBus::Master,
+        AudioBus::Music,
+        AudioBus::Effects,
+        AudioBus::Engine,
+        AudioBus::Ambient,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioBus::Master => "Master",
+            AudioBus::Music => "Music",
+            AudioBus::Effects => "Effects",
+            AudioBus::Engine => "Engine",
+            AudioBus::Ambient => "Ambient",
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            AudioBus::Master => "master",
+            AudioBus::Music => "music",
+            AudioBus::Effects => "effects",
+            AudioBus::Engine => "engine",
+            AudioBus::Ambient => "ambient",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BusLevel {
+    volume: f32,
+    muted: bool,
+    /// Volume currently heard, fading towards the volume, or silence when muted.
+    level: f32,
+}
+
+impl BusLevel {
+    fn new(volume: f32) -> Self {
+        BusLevel {
+            volume,
+            muted: false,
+            level: volume,
+        }
+    }
+
+    fn target(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+}
+
+/// Volumes of each [`AudioBus`].
+#[derive(Resource)]
+pub struct AudioMixer {
+    buses: [BusLevel; 5],
+    /// Whether the volumes have changed since they were last saved.
+    unsaved: bool,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        let mut buses = [BusLevel::new(1.0); 5];
+        buses[AudioBus::Master.index()] = BusLevel::new(DEFAULT_VOLUME);
+        AudioMixer {
+            buses,
+            unsaved: false,
+        }
+    }
+}
+
+impl AudioMixer {
+    /// Loads the mixer saved by the last session, or the defaults if there isn't one.
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(saved) = std::fs::read_to_string(MIXER_SETTINGS_PATH) {
+            return AudioMixer::from_config(&saved);
+        }
+        AudioMixer::default()
+    }
+
+    fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(error) = std::fs::write(MIXER_SETTINGS_PATH, self.to_config()) {
+            warn!("Couldn't save mixer settings: {}", error);
+        }
+    }
+
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        self.buses[bus.index()].volume
+    }
+
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.buses[bus.index()].volume = volume.clamp(0.0, 1.0);
+        self.unsaved = true;
+    }
+
+    pub fn muted(&self, bus: AudioBus) -> bool {
+        self.buses[bus.index()].muted
+    }
+
+    pub fn toggle_mute(&mut self, bus: AudioBus) {
+        let bus = &mut self.buses[bus.index()];
+        bus.muted = !bus.muted;
+        self.unsaved = true;
+    }
+
+    /// Gets the volume sounds on a bus play at, including the master volume.
+    pub fn output(&self, bus: AudioBus) -> f32 {
+        let master = self.buses[AudioBus::Master.index()].level;
+        if bus == AudioBus::Master {
+            master
+        } else {
+            master * self.buses[bus.index()].level
+        }
+    }
+
+    /// Moves every bus towards its volume, so changes and mutes fade in and out.
+    fn fade(&mut self, delta_seconds: f32) {
+        let step = delta_seconds / FADE_SECONDS;
+        for bus in self.buses.iter_mut() {
+            let difference = bus.target() - bus.level;
+            bus.level += difference.clamp(-step, step);
+        }
+    }
+
+    fn to_config(&self) -> String {
+        let mut config = String::new();
+        for bus in AudioBus::ALL {
+            let level = &self.buses[bus.index()];
+            config.push_str(&format!("{}={}\n", bus.key(), level.volume));
+            config.push_str(&format!("{}_muted={}\n", bus.key(), level.muted));
+        }
+        config
+    }
+
+    /// Reads saved volumes. Anything missing or unreadable keeps its default.
+    fn from_config(config: &str) -> Self {
+        let mut mixer = AudioMixer::default();
+        for line in config.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            for bus in AudioBus::ALL {
+                let level = &mut mixer.buses[bus.index()];
+                if key == bus.key() {
+                    if let Ok(volume) = value.parse::<f32>() {
+                        level.volume = volume.clamp(0.0, 1.0);
+                    }
+                } else if key == format!("{}_muted", bus.key()) {
+                    if let Ok(muted) = value.parse() {
+                        level.muted = muted;
+                    }
+                }
+            }
+        }
+        for level in mixer.buses.iter_mut() {
+            level.level = level.target();
+        }
+        mixer
+    }
+}
+
+pub fn mixer_fade_system(time: Res<Time>, mut mixer: ResMut<AudioMixer>) {
+    mixer.fade(time.delta_seconds());
+}
+
+pub fn mixer_save_system(mut mixer: ResMut<AudioMixer>) {
+    if mixer.unsaved {
+        mixer.unsaved = false;
+        mixer.save();
+    }
+}
+
+/// Which bus is selected in the mixer panel, and whether it is showing.
+#[derive(Resource, Default)]
+pub struct MixerPanel {
+    pub open: bool,
+    selected: usize,
+}
+
+#[derive(Component)]
+pub struct MixerUi;
+
+#[derive(Component)]
+pub struct MixerText;
+
+pub fn setup_mixer_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(60.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(MixerUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: mono_font,
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(MixerText);
+        });
+}
+
+/// Arrow keys pick a bus and change its volume, and Enter mutes it, while the panel is open.
+pub fn mixer_panel_system(
+    keys: Res<Input<KeyCode>>,
+    console: Res<Console>,
+    mut panel: ResMut<MixerPanel>,
+    mut mixer: ResMut<AudioMixer>,
+    mut panel_query: Query<&mut Visibility, With<MixerUi>>,
+    mut text_query: Query<&mut Text, With<MixerText>>,
+) {
+    if keys.just_pressed(KeyCode::F7) {
+        panel.open = !panel.open;
+    }
+    for mut visibility in panel_query.iter_mut() {
+        visibility.is_visible = panel.open;
+    }
+    // the arrow keys go through the console's history while it is open.
+    if !panel.open || console.open {
+        return;
+    }
+
+    let bus_count = AudioBus::ALL.len();
+    if keys.just_pressed(KeyCode::Up) {
+        panel.selected = (panel.selected + bus_count - 1) % bus_count;
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        panel.selected = (panel.selected + 1) % bus_count;
+    }
+    let bus = AudioBus::ALL[panel.selected];
+    if keys.just_pressed(KeyCode::Left) {
+        let volume = mixer.volume(bus) - VOLUME_STEP;
+        mixer.set_volume(bus, volume);
+    }
+    if keys.just_pressed(KeyCode::Right) {
+        let volume = mixer.volume(bus) + VOLUME_STEP;
+        mixer.set_volume(bus, volume);
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        mixer.toggle_mute(bus);
+    }
+
+    let mut value = "Mixer (F7 to close)\n".to_string();
+    for (index, bus) in AudioBus::ALL.iter().enumerate() {
+        value.push_str(&format!(
+            "{} {:<8}{:>4.0}%{}\n",
+            if index == panel.selected { ">" } else { " " },
+            bus.name(),
+            100.0 * mixer.volume(*bus),
+            if mixer.muted(*bus) { " muted" } else { "" }
+        ));
+    }
+    value.push_str("Up/Down select, Left/Right volume, Enter mute");
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mixer::{AudioBus, AudioMixer, FADE_SECONDS};
+
+    #[test]
+    fn master_scales_other_buses_test() {
+        let mut mixer = AudioMixer::from_config("");
+        mixer.set_volume(AudioBus::Master, 0.5);
+        mixer.set_volume(AudioBus::Music, 0.5);
+        mixer.fade(FADE_SECONDS);
+
+        assert!((mixer.output(AudioBus::Master) - 0.5).abs() < 0.001);
+        assert!((mixer.output(AudioBus::Music) - 0.25).abs() < 0.001);
+        assert!((mixer.output(AudioBus::Effects) - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn mute_fades_out_test() {
+        let mut mixer = AudioMixer::from_config("master=1");
+        mixer.toggle_mute(AudioBus::Engine);
+
+        mixer.fade(FADE_SECONDS * 0.5);
+        let halfway = mixer.output(AudioBus::Engine);
+        assert!(halfway > 0.0 && halfway < 1.0);
+
+        mixer.fade(FADE_SECONDS);
+        assert_eq!(0.0, mixer.output(AudioBus::Engine));
+        assert!(mixer.muted(AudioBus::Engine));
+    }
+
+    #[test]
+    fn config_round_trip_test() {
+        let mut mixer = AudioMixer::from_config("");
+        mixer.set_volume(AudioBus::Master, 0.8);
+        mixer.set_volume(AudioBus::Ambient, 0.3);
+        mixer.toggle_mute(AudioBus::Music);
+
+        let loaded = AudioMixer::from_config(&mixer.to_config());
+        for bus in AudioBus::ALL {
+            assert_eq!(mixer.volume(bus), loaded.volume(bus));
+            assert_eq!(mixer.muted(bus), loaded.muted(bus));
+        }
+    }
+
+    #[test]
+    fn config_ignores_bad_values_test() {
+        let mixer = AudioMixer::from_config("music=loud\neffects=2\nnonsense\nengine_muted=true");
+
+        assert_eq!(1.0, mixer.volume(AudioBus::Music));
+        assert_eq!(1.0, mixer.volume(AudioBus::Effects));
+        assert!(mixer.muted(AudioBus::Engine));
+    }
+}
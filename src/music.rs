@@ -0,0 +1,293 @@
+use bevy::{audio::AudioSink, prelude::*};
+
+use crate::{
+    combine::{Combine, COMBINE_MAX_HEALTH, PLAYER_COMBINE_ID},
+    herding::HerdingMatch,
+    mixer::{AudioBus, AudioMixer},
+    spatial_audio::{SpatialAudioPlayer, SpatialSink},
+};
+
+const MUSIC_VOLUME: f32 = 0.2;
+const CROSSFADE_SECONDS: f32 = 3.0;
+/// Played when no playlist folder could be read, which is always the case on the web.
+const FALLBACK_TRACK: &str = "music/match/jazzyfrenchy.ogg";
+const MUSIC_EXTENSIONS: [&str; 4] = ["ogg", "wav", "mp3", "flac"];
+
+/// The music gets intense when an opponent is closer than this to the player.
+const INTENSE_DISTANCE: f32 = 35.0;
+/// The music gets intense when the player has less than this fraction of their health left.
+const INTENSE_HEALTH_FRACTION: f32 = 0.3;
+/// How long things have to stay calm before the intense music stops.
+const CALM_DOWN_SECONDS: f32 = 8.0;
+
+/// What the music should be setting the mood for. Each has its own playlist, read from a folder
+/// under `assets/music`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MusicMood {
+    Menu,
+    Match,
+    /// A match where the player is in the thick of it.
+    Intense,
+    Results,
+}
+
+impl MusicMood {
+    pub const ALL: [MusicMood; 4] = [
+        MusicMood::Menu,
+        MusicMood::Match,
+        MusicMood::Intense,
+        MusicMood::Results,
+    ];
+
+    fn folder(&self) -> &'static str {
+        match self {
+            MusicMood::Menu => "music/menu",
+            MusicMood::Match => "music/match",
+            MusicMood::Intense => "music/intense",
+            MusicMood::Results => "music/results",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// A track from a playlist and the sound playing it.
+struct MusicTrack {
+    mood: MusicMood,
+    index: usize,
+    sink: SpatialSink,
+}
+
+#[derive(Resource)]
+pub struct Music {
+    /// The mood wanted. The music crossfades to the new playlist when this changes.
+    pub mood: MusicMood,
+    playlists: [Vec<Handle<AudioSource>>; 4],
+    playing: Option<MusicTrack>,
+    /// Tracks fading out after being replaced.
+    fading_out: Vec<SpatialSink>,
+    /// Time since the player was last in the thick of it.
+    calm_timer: Timer,
+}
+
+impl Music {
+    /// Gets the mood whose playlist is used for a mood. Moods without any tracks borrow the
+    /// match playlist.
+    fn playlist_mood(&self, mood: MusicMood) -> MusicMood {
+        if self.playlists[mood.index()].is_empty() {
+            MusicMood::Match
+        } else {
+            mood
+        }
+    }
+
+    /// Picks the track to play next, following on from the one playing if it is in the same
+    /// playlist.
+    fn next_track(&self) -> (MusicMood, usize) {
+        let mood = self.playlist_mood(self.mood);
+        let index = match &self.playing {
+            Some(playing) if playing.mood == mood => {
+                (playing.index + 1) % self.playlists[mood.index()].len()
+            }
+            _ => 0,
+        };
+        (mood, index)
+    }
+}
+
+/// Loads a playlist from every music file in a folder, in file name order.
+fn load_playlist(asset_server: &AssetServer, folder: &str) -> Vec<Handle<AudioSource>> {
+    let handles = match asset_server.load_folder(folder) {
+        Ok(handles) => handles,
+        Err(_) => return Vec::new(),
+    };
+    let mut tracks: Vec<(String, Handle<AudioSource>)> = handles
+        .into_iter()
+        .filter_map(|handle| {
+            let path = asset_server.get_handle_path(&handle)?;
+            let path = path.path();
+            let extension = path.extension()?.to_str()?.to_lowercase();
+            if !MUSIC_EXTENSIONS.contains(&extension.as_str()) {
+                return None;
+            }
+            Some((path.to_string_lossy().to_string(), handle.typed()))
+        })
+        .collect();
+    tracks.sort_by(|a, b| a.0.cmp(&b.0));
+    tracks.into_iter().map(|(_, handle)| handle).collect()
+}
+
+pub fn setup_music(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut playlists: [Vec<Handle<AudioSource>>; 4] = Default::default();
+    for mood in MusicMood::ALL {
+        playlists[mood.index()] = load_playlist(&asset_server, mood.folder());
+    }
+    if playlists[MusicMood::Match.index()].is_empty() {
+        playlists[MusicMood::Match.index()] = vec![asset_server.load(FALLBACK_TRACK)];
+    }
+
+    commands.insert_resource(Music {
+        mood: MusicMood::Match,
+        playlists,
+        playing: None,
+        fading_out: Vec::new(),
+        calm_timer: Timer::from_seconds(CALM_DOWN_SECONDS, TimerMode::Once),
+    });
+}
+
+/// Whether the player is close enough to an opponent, or hurt enough, for intense music.
+fn in_thick_of_it(nearest_opponent_distance: Option<f32>, health: f32) -> bool {
+    let opponent_close = nearest_opponent_distance
+        .map(|distance| distance < INTENSE_DISTANCE)
+        .unwrap_or(false);
+    opponent_close || health < COMBINE_MAX_HEALTH * INTENSE_HEALTH_FRACTION
+}
+
+/// Switches the match music between calm and intense, and to the results once the match ends.
+pub fn music_mood_system(
+    time: Res<Time>,
+    mut music: ResMut<Music>,
+    herding_match: Option<Res<HerdingMatch>>,
+    combine_query: Query<(&Combine, &Transform)>,
+) {
+    if !matches!(music.mood, MusicMood::Match | MusicMood::Intense) {
+        return;
+    }
+    if herding_match.map_or(false, |herding_match| herding_match.finished) {
+        music.mood = MusicMood::Results;
+        return;
+    }
+
+    let player = combine_query
+        .iter()
+        .find(|(combine, _)| combine.combine_id == PLAYER_COMBINE_ID);
+    let (player, player_transform) = match player {
+        Some(player) => player,
+        None => return,
+    };
+    let nearest_opponent_distance = combine_query
+        .iter()
+        .filter(|(combine, _)| combine.combine_id != PLAYER_COMBINE_ID)
+        .map(|(_, transform)| transform.translation.distance(player_transform.translation))
+        .reduce(f32::min);
+
+    if in_thick_of_it(nearest_opponent_distance, player.health) {
+        music.calm_timer.reset();
+        music.mood = MusicMood::Intense;
+    } else {
+        music.calm_timer.tick(time.delta());
+        if music.calm_timer.finished() {
+            music.mood = MusicMood::Match;
+        }
+    }
+}
+
+/// Plays through the playlist for the current mood, crossfading between tracks.
+pub fn music_system(
+    time: Res<Time>,
+    mixer: Res<AudioMixer>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut spatial_audio: SpatialAudioPlayer,
+    mut music: ResMut<Music>,
+) {
+    let music = &mut *music;
+    let (mood, index) = music.next_track();
+    let change_track = match &music.playing {
+        Some(playing) => playing.mood != mood || playing.sink.finished(),
+        None => true,
+    };
+    if change_track {
+        let source = &music.playlists[mood.index()][index];
+        let sink = spatial_audio.play(
+            source,
+            AudioBus::Music,
+            PlaybackSettings {
+                volume: 0.0,
+                ..default()
+            },
+        );
+        // the track is still loading if there's no sink, so it is tried again next frame.
+        if let Some(sink) = sink {
+            let replaced = music.playing.replace(MusicTrack { mood, index, sink });
+            if let Some(replaced) = replaced {
+                music.fading_out.push(replaced.sink);
+            }
+        }
+    }
+
+    let step = time.delta_seconds() / CROSSFADE_SECONDS;
+    let volume = MUSIC_VOLUME * mixer.output(AudioBus::Music);
+    if let Some(playing) = &mut music.playing {
+        playing.sink.volume = (playing.sink.volume + step).min(1.0);
+        if let Some(sink) = audio_sinks.get(&playing.sink.sink) {
+            sink.set_volume(playing.sink.volume * volume);
+        }
+    }
+    music.fading_out.retain_mut(|fading| {
+        fading.volume = (fading.volume - step).max(0.0);
+        match audio_sinks.get(&fading.sink) {
+            Some(sink) if fading.volume > 0.0 => {
+                sink.set_volume(fading.volume * volume);
+                true
+            }
+            Some(sink) => {
+                sink.stop();
+                false
+            }
+            None => false,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        combine::COMBINE_MAX_HEALTH,
+        music::{in_thick_of_it, Music, MusicMood, CALM_DOWN_SECONDS, INTENSE_DISTANCE},
+    };
+
+    fn music_with_playlists(track_counts: [usize; 4]) -> Music {
+        Music {
+            mood: MusicMood::Match,
+            playlists: track_counts.map(|count| vec![Handle::default(); count]),
+            playing: None,
+            fading_out: Vec::new(),
+            calm_timer: Timer::from_seconds(CALM_DOWN_SECONDS, TimerMode::Once),
+        }
+    }
+
+    #[test]
+    fn empty_playlist_borrows_match_music_test() {
+        let music = music_with_playlists([0, 2, 0, 1]);
+
+        assert_eq!(MusicMood::Match, music.playlist_mood(MusicMood::Menu));
+        assert_eq!(MusicMood::Match, music.playlist_mood(MusicMood::Intense));
+        assert_eq!(MusicMood::Results, music.playlist_mood(MusicMood::Results));
+    }
+
+    #[test]
+    fn first_track_of_new_playlist_test() {
+        let mut music = music_with_playlists([0, 2, 3, 0]);
+        music.mood = MusicMood::Intense;
+
+        assert_eq!((MusicMood::Intense, 0), music.next_track());
+    }
+
+    #[test]
+    fn thick_of_it_test() {
+        assert!(in_thick_of_it(
+            Some(INTENSE_DISTANCE * 0.5),
+            COMBINE_MAX_HEALTH
+        ));
+        assert!(in_thick_of_it(None, COMBINE_MAX_HEALTH * 0.1));
+        assert!(!in_thick_of_it(
+            Some(INTENSE_DISTANCE * 2.0),
+            COMBINE_MAX_HEALTH
+        ));
+        assert!(!in_thick_of_it(None, COMBINE_MAX_HEALTH));
+    }
+}
@@ -1,18 +1,137 @@
+use std::collections::HashSet;
+
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use rand::Rng;
 
 use crate::{
     arena::PLANE_SIZE,
-    combine::{Combine, Wheel, PLAYER_COMBINE_ID},
-    events::SoundSampleEvent,
+    combine::{Combine, Wheel},
+    events::{SoundSampleEvent, SoundSampleKind},
+    layout::ArenaLayout,
+    particles::{spawn_particle_burst, ParticleAssets, ParticleBurst},
     sounds::SoundCollider,
 };
 
-const HAY_BALE_DIMENSION: f32 = 9.5;
+/// Contact force that bursts a hay bale apart.
+const HAY_BALE_BREAK_FORCE: f32 = 50000.0;
+const STRAW_DEBRIS_COUNT: i32 = 10;
+const STRAW_DEBRIS_SECONDS: f32 = 4.0;
+
+const COW_WALK_SPEED: f32 = 4.0;
+const COW_FLEE_SPEED: f32 = 12.0;
+/// How quickly a cow matches the velocity it wants, per second.
+const COW_STEERING_RESPONSE: f32 = 2.0;
+const COW_MAX_ACCELERATION: f32 = 8.0;
+
+const COW_UPRIGHT_STIFFNESS: f32 = 20.0;
+const COW_UPRIGHT_DAMPING: f32 = 5.0;
+const COW_TURN_STIFFNESS: f32 = 4.0;
+const COW_TURN_DAMPING: f32 = 3.0;
+
+const COW_WANDER_DISTANCE: f32 = 80.0;
+const COW_WANDER_SECONDS: f32 = 15.0;
+const COW_ARRIVE_DISTANCE: f32 = 10.0;
+/// Wander targets are kept this far inside the arena fences.
+const COW_FENCE_MARGIN: f32 = 20.0;
+
+const COW_FLEE_RADIUS: f32 = 60.0;
+/// How fast a combine must be closing in for a cow to run away from it.
+const COW_FLEE_CLOSING_SPEED: f32 = 8.0;
+const COW_FLEE_SECONDS: f32 = 3.0;
+
+const COW_FLOCK_RADIUS: f32 = 50.0;
+const COW_SEPARATION_RADIUS: f32 = 20.0;
+const COW_COHESION: f32 = 0.05;
+const COW_ALIGNMENT: f32 = 0.3;
+const COW_SEPARATION_SPEED: f32 = 4.0;
+
+/// Contact force from a combine that knocks a cow off its feet.
+const COW_KNOCKDOWN_FORCE: f32 = 20000.0;
+const COW_KNOCKED_OVER_SECONDS: f32 = 4.0;
+/// A cow whose up axis has dropped below this is lying on its side.
+const COW_FALLEN_UP_Y: f32 = 0.5;
+
+#[derive(Component)]
+pub struct HayBale;
+
+/// A loose clump of straw left behind by a broken hay bale.
+#[derive(Component)]
+pub struct StrawDebris {
+    pub lifetime: Timer,
+}
 
 #[derive(Component)]
 pub struct Cow;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CowState {
+    Grazing,
+    Wandering,
+    Fleeing,
+    KnockedOver,
+}
+
+#[derive(Component)]
+pub struct CowBehaviour {
+    pub state: CowState,
+    /// Time until the cow reconsiders what it is doing.
+    pub state_timer: Timer,
+    pub wander_target: Vec3,
+    pub flee_direction: Vec3,
+}
+
+impl CowBehaviour {
+    pub fn set_state(&mut self, state: CowState, seconds: f32) {
+        self.state = state;
+        self.state_timer = Timer::from_seconds(seconds, TimerMode::Once);
+    }
+
+    /// Knocks the cow over if it has tipped onto its side.
+    fn check_fallen(&mut self, up: Vec3) {
+        if self.state != CowState::KnockedOver && up.y < COW_FALLEN_UP_Y {
+            self.set_state(CowState::KnockedOver, COW_KNOCKED_OVER_SECONDS);
+        }
+    }
+
+    /// Starts running away from the first combine closing in on the cow, given as positions and
+    /// velocities. Returns true if the cow started fleeing.
+    fn flee_from_combines(
+        &mut self,
+        position: Vec3,
+        combines: impl Iterator<Item = (Vec3, Vec3)>,
+    ) -> bool {
+        if self.state == CowState::Fleeing || self.state == CowState::KnockedOver {
+            return false;
+        }
+        for (combine_position, combine_velocity) in combines {
+            let offset = horizontal(position - combine_position);
+            let distance = offset.length();
+            if distance > COW_FLEE_RADIUS || distance < 0.1 {
+                continue;
+            }
+            let closing_speed = combine_velocity.dot(offset / distance);
+            if closing_speed > COW_FLEE_CLOSING_SPEED {
+                self.flee_direction = offset / distance;
+                self.set_state(CowState::Fleeing, COW_FLEE_SECONDS);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for CowBehaviour {
+    fn default() -> Self {
+        CowBehaviour {
+            state: CowState::Grazing,
+            state_timer: Timer::from_seconds(2.0, TimerMode::Once),
+            wander_target: Vec3::ZERO,
+            flee_direction: Vec3::ZERO,
+        }
+    }
+}
+
 fn spawn_hay_bale_with_transform<'w, 's>(
     mut commands: Commands<'w, 's>,
     transform: Transform,
@@ -21,11 +140,12 @@ fn spawn_hay_bale_with_transform<'w, 's>(
     commands
         .spawn(SpatialBundle::from(transform))
         .insert(RigidBody::Dynamic)
+        .insert(HayBale)
         .insert(Collider::cylinder(3.72, 4.5))
         .insert(Restitution::coefficient(0.7))
         .insert(ColliderMassProperties::Density(0.1))
         .insert(SoundCollider {
-            sound_sample: SoundSampleEvent::HayBale,
+            sound_sample: SoundSampleKind::HayBale,
         })
         .insert(ActiveEvents::CONTACT_FORCE_EVENTS)
         .with_children(|parent| {
@@ -41,97 +161,343 @@ fn spawn_hay_bale_with_transform<'w, 's>(
     commands
 }
 
-pub fn spawn_hay_bales(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let bale_gltf: Handle<Scene> = asset_server.load("hay-bale.glb#Scene0");
+/// Breaks hay bales that are hit hard enough into loose straw.
+pub fn hay_bale_break_system(
+    mut commands: Commands,
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    mut sound_sample_events: EventWriter<SoundSampleEvent>,
+    particle_assets: Res<ParticleAssets>,
+    bale_query: Query<&Transform, With<HayBale>>,
+) {
+    let mut broken_bales = HashSet::new();
 
-    let layer_count = 4;
-    let layer_height = 8.0;
-    let layer_start_height = 1.5;
-    // the hay pyramid
-    for layer in 0..layer_count {
-        let current_width = layer_count - layer;
-        let offset = -current_width as f32 * 0.5 * HAY_BALE_DIMENSION;
-        for x in 0..current_width {
-            for y in 0..current_width {
-                let transform = Transform::from_translation(Vec3::new(
-                    offset + ((x as f32) * HAY_BALE_DIMENSION),
-                    (layer_height * layer as f32) + layer_start_height,
-                    offset + ((y as f32) * HAY_BALE_DIMENSION),
-                ));
-                commands = spawn_hay_bale_with_transform(commands, transform, bale_gltf.clone());
+    for contact_force_event in contact_force_events.iter() {
+        if !breaks_hay_bale(contact_force_event.total_force_magnitude) {
+            continue;
+        }
+        for bale_entity in [contact_force_event.collider1, contact_force_event.collider2] {
+            if broken_bales.contains(&bale_entity) {
+                continue;
+            }
+            if let Ok(transform) = bale_query.get(bale_entity) {
+                broken_bales.insert(bale_entity);
+                commands.entity(bale_entity).despawn_recursive();
+                sound_sample_events.send(SoundSampleEvent {
+                    sample: SoundSampleKind::HayBale,
+                    position: transform.translation,
+                    source: bale_entity,
+                    force: Some(contact_force_event.total_force_magnitude),
+                });
+                commands = spawn_straw(commands, &particle_assets, transform.translation);
             }
         }
     }
 }
 
-pub fn spawn_cows(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let cow_gltf: Handle<Scene> = asset_server.load("cow.glb#Scene0");
+/// Whether a hit with this much contact force bursts a hay bale apart.
+fn breaks_hay_bale(force: f32) -> bool {
+    force >= HAY_BALE_BREAK_FORCE
+}
 
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(150.0, 12.0, 50.0)),
-        cow_gltf.clone(),
-    );
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(50.0, 12.0, 150.0)),
-        cow_gltf.clone(),
-    );
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(-200.0, 12.0, -100.0)),
-        cow_gltf.clone(),
-    );
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(-50.0, 12.0, -150.0)),
-        cow_gltf.clone(),
-    );
+fn spawn_straw<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    particle_assets: &ParticleAssets,
+    position: Vec3,
+) -> Commands<'w, 's> {
+    let mut rng = rand::thread_rng();
 
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(PLANE_SIZE - 100.0, 12.0, -100.0)),
-        cow_gltf.clone(),
-    );
+    for _ in 0..STRAW_DEBRIS_COUNT {
+        let offset = Vec3::new(
+            rng.gen_range(-3.0..3.0),
+            rng.gen_range(-2.0..2.0),
+            rng.gen_range(-3.0..3.0),
+        );
+        commands
+            .spawn(SpatialBundle::from(
+                Transform::from_translation(position + offset)
+                    .with_rotation(Quat::from_rotation_y(rng.gen_range(0.0..6.3))),
+            ))
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::cuboid(1.5, 0.25, 0.25))
+            .insert(ColliderMassProperties::Density(0.1))
+            .insert(Velocity {
+                linvel: (offset.normalize_or_zero() * rng.gen_range(5.0..15.0)) + (Vec3::Y * 5.0),
+                angvel: Vec3::new(
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-5.0..5.0),
+                ),
+            })
+            .insert(StrawDebris {
+                lifetime: Timer::from_seconds(
+                    STRAW_DEBRIS_SECONDS * rng.gen_range(0.75..1.25),
+                    TimerMode::Once,
+                ),
+            })
+            .with_children(|parent| {
+                parent.spawn(PbrBundle {
+                    mesh: particle_assets.mesh.clone(),
+                    material: particle_assets.straw_material.clone(),
+                    transform: Transform::from_scale(Vec3::new(3.0, 0.5, 0.5)),
+                    ..default()
+                });
+            });
+    }
 
-    commands = spawn_cow_with_transform(
+    spawn_particle_burst(
         commands,
-        Transform::from_translation(Vec3::new(-100.0, 12.0, PLANE_SIZE - 100.0)),
-        cow_gltf.clone(),
-    );
+        particle_assets.mesh.clone(),
+        ParticleBurst {
+            position,
+            count: 30,
+            direction: Vec3::Y * 0.5,
+            speed: 15.0,
+            spread: 1.0,
+            lifetime: 1.5,
+            size: 0.6,
+            gravity_scale: 1.0,
+            material: particle_assets.straw_material.clone(),
+        },
+    )
+}
 
-    commands = spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(-(PLANE_SIZE - 100.0), 12.0, 100.0)),
-        cow_gltf.clone(),
-    );
+pub fn straw_debris_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut debris_query: Query<(Entity, &mut StrawDebris)>,
+) {
+    for (entity, mut debris) in debris_query.iter_mut() {
+        debris.lifetime.tick(time.delta());
+        if debris.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
 
-    spawn_cow_with_transform(
-        commands,
-        Transform::from_translation(Vec3::new(100.0, 12.0, -(PLANE_SIZE - 100.0))),
-        cow_gltf,
-    );
+pub fn spawn_hay_bales(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena_layout: Res<ArenaLayout>,
+) {
+    let bale_gltf: Handle<Scene> = asset_server.load("hay-bale.glb#Scene0");
+
+    for structure in arena_layout.hay_structures.iter() {
+        for transform in structure.bale_transforms() {
+            commands = spawn_hay_bale_with_transform(commands, transform, bale_gltf.clone());
+        }
+    }
+}
+
+pub fn spawn_cows(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena_layout: Res<ArenaLayout>,
+) {
+    let cow_gltf: Handle<Scene> = asset_server.load("cow.glb#Scene0");
+
+    let pen_cows = arena_layout
+        .cow_pens
+        .iter()
+        .flat_map(|pen| pen.cow_positions());
+
+    for position in arena_layout.loose_cows.iter().copied().chain(pen_cows) {
+        commands = spawn_cow_with_transform(
+            commands,
+            Transform::from_translation(position),
+            cow_gltf.clone(),
+        );
+    }
 }
 
+/// Applies steering forces so cows graze, wander, flee and keep together as a herd.
 pub fn cow_ai_system(
-    mut cow_query: Query<&mut Transform, With<Cow>>,
-    combine_query: Query<(&Combine, &Transform), Without<Cow>>,
+    time: Res<Time>,
+    mut cow_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &ReadMassProperties,
+            &mut CowBehaviour,
+            &mut ExternalForce,
+        ),
+        With<Cow>,
+    >,
+    combine_query: Query<(&Transform, &Velocity), (With<Combine>, Without<Cow>)>,
+    mut sound_sample_events: EventWriter<SoundSampleEvent>,
 ) {
-    let mut player_combine_opt = Option::None;
+    let mut rng = rand::thread_rng();
 
-    for (combine, transform) in combine_query.iter() {
-        if combine.combine_id == PLAYER_COMBINE_ID {
-            player_combine_opt = Some(transform);
-            break;
+    let herd: Vec<(Entity, Vec3, Vec3)> = cow_query
+        .iter()
+        .map(|(entity, transform, velocity, ..)| (entity, transform.translation, velocity.linvel))
+        .collect();
+
+    for (entity, transform, velocity, mass_properties, mut behaviour, mut external_force) in
+        cow_query.iter_mut()
+    {
+        let position = transform.translation;
+        let up = transform.rotation * Vec3::Y;
+        behaviour.state_timer.tick(time.delta());
+
+        behaviour.check_fallen(up);
+
+        // a knocked over cow lies where it fell until it gets its feet back.
+        if behaviour.state == CowState::KnockedOver {
+            external_force.force = Vec3::ZERO;
+            external_force.torque = Vec3::ZERO;
+            if behaviour.state_timer.finished() {
+                behaviour.set_state(CowState::Grazing, rng.gen_range(2.0..5.0));
+            }
+            continue;
+        }
+
+        let combines = combine_query
+            .iter()
+            .map(|(combine_transform, combine_velocity)| {
+                (combine_transform.translation, combine_velocity.linvel)
+            });
+        if behaviour.flee_from_combines(position, combines) {
+            sound_sample_events.send(SoundSampleEvent {
+                sample: SoundSampleKind::Cow,
+                position,
+                source: entity,
+                force: None,
+            });
+        }
+
+        let mut desired_velocity = Vec3::ZERO;
+        match behaviour.state {
+            CowState::Grazing => {
+                if behaviour.state_timer.finished() {
+                    let target = position
+                        + Vec3::new(
+                            rng.gen_range(-COW_WANDER_DISTANCE..COW_WANDER_DISTANCE),
+                            0.0,
+                            rng.gen_range(-COW_WANDER_DISTANCE..COW_WANDER_DISTANCE),
+                        );
+                    let limit = PLANE_SIZE - COW_FENCE_MARGIN;
+                    behaviour.wander_target = Vec3::new(
+                        target.x.clamp(-limit, limit),
+                        0.0,
+                        target.z.clamp(-limit, limit),
+                    );
+                    behaviour.set_state(CowState::Wandering, COW_WANDER_SECONDS);
+                }
+            }
+            CowState::Wandering => {
+                let to_target = horizontal(behaviour.wander_target - position);
+                if to_target.length() < COW_ARRIVE_DISTANCE || behaviour.state_timer.finished() {
+                    behaviour.set_state(CowState::Grazing, rng.gen_range(3.0..8.0));
+                } else {
+                    desired_velocity = to_target.normalize() * COW_WALK_SPEED;
+                }
+            }
+            CowState::Fleeing => {
+                if behaviour.state_timer.finished() {
+                    behaviour.set_state(CowState::Grazing, rng.gen_range(1.0..3.0));
+                } else {
+                    desired_velocity = behaviour.flee_direction * COW_FLEE_SPEED;
+                }
+            }
+            CowState::KnockedOver => {}
+        }
+        desired_velocity += flocking_velocity(entity, position, &herd);
+
+        let mass = mass_properties.0.mass;
+        let inertia = mass_properties.0.principal_inertia;
+        let horizontal_velocity = horizontal(velocity.linvel);
+
+        let acceleration = ((desired_velocity - horizontal_velocity) * COW_STEERING_RESPONSE)
+            .clamp_length_max(COW_MAX_ACCELERATION);
+        external_force.force = acceleration * mass;
+
+        // keep the cow on its feet and turn it towards where it is going.
+        let tilt = up.cross(Vec3::Y);
+        let mut torque = (tilt * COW_UPRIGHT_STIFFNESS)
+            - (Vec3::new(velocity.angvel.x, 0.0, velocity.angvel.z) * COW_UPRIGHT_DAMPING);
+        torque.y = -velocity.angvel.y * COW_TURN_DAMPING;
+        if desired_velocity.length() > 0.5 {
+            let forward = horizontal(transform.rotation * -Vec3::Z).normalize_or_zero();
+            let heading = desired_velocity.normalize();
+            let yaw_error = forward.cross(heading).y.atan2(forward.dot(heading));
+            torque.y += yaw_error * COW_TURN_STIFFNESS;
+        }
+        external_force.torque = torque * inertia;
+    }
+}
+
+/// Knocks cows off their feet when a combine hits them hard enough.
+pub fn cow_knockdown_system(
+    mut contact_force_events: EventReader<ContactForceEvent>,
+    mut cow_query: Query<&mut CowBehaviour>,
+    combine_query: Query<&Combine>,
+    wheel_query: Query<&Wheel>,
+) {
+    for contact_force_event in contact_force_events.iter() {
+        if contact_force_event.total_force_magnitude < COW_KNOCKDOWN_FORCE {
+            continue;
+        }
+        let colliders = [
+            (contact_force_event.collider1, contact_force_event.collider2),
+            (contact_force_event.collider2, contact_force_event.collider1),
+        ];
+        for (cow_entity, other_entity) in colliders {
+            let hit_by_combine =
+                combine_query.get(other_entity).is_ok() || wheel_query.get(other_entity).is_ok();
+            if !hit_by_combine {
+                continue;
+            }
+            if let Ok(mut behaviour) = cow_query.get_mut(cow_entity) {
+                if behaviour.state != CowState::KnockedOver {
+                    behaviour.set_state(CowState::KnockedOver, COW_KNOCKED_OVER_SECONDS);
+                }
+            }
+        }
+    }
+}
+
+/// Gets the velocity that draws a cow towards its neighbours, matching their heading without
+/// getting too close.
+fn flocking_velocity(entity: Entity, position: Vec3, herd: &[(Entity, Vec3, Vec3)]) -> Vec3 {
+    let mut neighbours = 0;
+    let mut centre = Vec3::ZERO;
+    let mut heading = Vec3::ZERO;
+    let mut separation = Vec3::ZERO;
+
+    for (other_entity, other_position, other_velocity) in herd.iter() {
+        if *other_entity == entity {
+            continue;
+        }
+        let offset = horizontal(*other_position - position);
+        let distance = offset.length();
+        if distance > COW_FLOCK_RADIUS {
+            continue;
+        }
+        neighbours += 1;
+        centre += offset;
+        heading += horizontal(*other_velocity);
+        if distance > 0.01 && distance < COW_SEPARATION_RADIUS {
+            separation -=
+                (offset / distance) * ((COW_SEPARATION_RADIUS - distance) / COW_SEPARATION_RADIUS);
         }
     }
 
-    for mut cow_transform in cow_query.iter_mut() {
-        cow_transform.look_at(player_combine_opt.unwrap().translation, Vec3::Y);
+    if neighbours == 0 {
+        return Vec3::ZERO;
     }
+
+    let neighbour_count = neighbours as f32;
+    ((centre / neighbour_count) * COW_COHESION)
+        + ((heading / neighbour_count) * COW_ALIGNMENT)
+        + (separation * COW_SEPARATION_SPEED)
+}
+
+fn horizontal(vec: Vec3) -> Vec3 {
+    Vec3::new(vec.x, 0.0, vec.z)
 }
 
-fn spawn_cow_with_transform<'w, 's>(
+pub fn spawn_cow_with_transform<'w, 's>(
     mut commands: Commands<'w, 's>,
     transform: Transform,
     scene_handle: Handle<Scene>,
@@ -144,8 +510,16 @@ fn spawn_cow_with_transform<'w, 's>(
         .insert(ColliderMassProperties::Density(0.5))
         .insert(ActiveEvents::CONTACT_FORCE_EVENTS)
         .insert(Cow)
+        .insert(CowBehaviour::default())
+        .insert(Velocity::default())
+        .insert(ExternalForce::default())
+        .insert(ReadMassProperties::default())
+        .insert(Damping {
+            linear_damping: 0.5,
+            angular_damping: 1.0,
+        })
         .insert(SoundCollider {
-            sound_sample: SoundSampleEvent::Cow,
+            sound_sample: SoundSampleKind::Cow,
         })
         .with_children(|parent| {
             parent.spawn(SceneBundle {
@@ -160,36 +534,251 @@ fn spawn_cow_with_transform<'w, 's>(
     commands
 }
 
+/// Plays impact sounds when combines hit things, louder the harder the hit.
 pub fn collision_check_system(
     mut contact_force_events: EventReader<ContactForceEvent>,
     mut sound_samples_events: EventWriter<SoundSampleEvent>,
     sound_collider_query: Query<&SoundCollider>,
     combine_query: Query<&Combine>,
     wheel_query: Query<&Wheel>,
+    transform_query: Query<&GlobalTransform>,
 ) {
+    let combine_id = |entity| {
+        combine_query
+            .get(entity)
+            .map(|combine| combine.combine_id)
+            .or_else(|_| wheel_query.get(entity).map(|wheel| wheel.combine_id))
+            .ok()
+    };
+
     for contact_force_event in contact_force_events.iter() {
-        let mut hits = 0;
-        let mut sound_event_sample = Option::None;
-        if let Ok(a) = sound_collider_query.get(contact_force_event.collider1) {
-            hits += 1;
-            sound_event_sample = Some(a.sound_sample.clone());
-        } else if let Ok(b) = sound_collider_query.get(contact_force_event.collider2) {
-            hits += 1;
-            sound_event_sample = Some(b.sound_sample.clone());
-        }
+        let colliders = [
+            (contact_force_event.collider1, contact_force_event.collider2),
+            (contact_force_event.collider2, contact_force_event.collider1),
+        ];
+        for (combine_entity, other_entity) in colliders {
+            let this_combine_id = match combine_id(combine_entity) {
+                Some(combine_id) => combine_id,
+                None => continue,
+            };
 
-        if combine_query.get(contact_force_event.collider1).is_ok() {
-            hits += 1;
-        } else if combine_query.get(contact_force_event.collider2).is_ok() {
-            hits += 1;
-        }
-        if wheel_query.get(contact_force_event.collider1).is_ok() {
-            hits += 1;
-        } else if wheel_query.get(contact_force_event.collider2).is_ok() {
-            hits += 1;
-        }
-        if hits > 1 {
-            sound_samples_events.send(sound_event_sample.unwrap());
+            // crashes are throttled per combine, everything else per thing that was hit.
+            let sample = match combine_id(other_entity) {
+                Some(other_combine_id) if other_combine_id != this_combine_id => {
+                    Some((SoundSampleKind::Crash, combine_entity))
+                }
+                Some(_) => None,
+                None => sound_collider_query
+                    .get(other_entity)
+                    .ok()
+                    .map(|sound_collider| (sound_collider.sound_sample.clone(), other_entity)),
+            };
+
+            if let Some((sample, source)) = sample {
+                // the combine is always close to the impact, unlike the centre of a long fence.
+                let position = transform_query
+                    .get(combine_entity)
+                    .map(|transform| transform.translation())
+                    .unwrap_or_default();
+                sound_samples_events.send(SoundSampleEvent {
+                    sample,
+                    position,
+                    source,
+                    force: Some(contact_force_event.total_force_magnitude),
+                });
+            }
+            // one sound per contact, even when both sides are combines.
+            break;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    use crate::{
+        events::{SoundSampleEvent, SoundSampleKind},
+        obstacle::{
+            breaks_hay_bale, flocking_velocity, hay_bale_break_system, straw_debris_system,
+            CowBehaviour, CowState, HayBale, StrawDebris, HAY_BALE_BREAK_FORCE, STRAW_DEBRIS_COUNT,
+            STRAW_DEBRIS_SECONDS,
+        },
+        particles::ParticleAssets,
+    };
+
+    /// Makes an app with a single hay bale and the systems that break it.
+    fn hay_bale_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_event::<ContactForceEvent>()
+            .add_event::<SoundSampleEvent>()
+            .insert_resource(Time::default())
+            .insert_resource(ParticleAssets {
+                mesh: Handle::default(),
+                straw_material: Handle::default(),
+                exhaust_material: Handle::default(),
+            })
+            .add_system(hay_bale_break_system)
+            .add_system(straw_debris_system);
+        let bale = app
+            .world
+            .spawn((Transform::from_xyz(10.0, 2.0, 10.0), HayBale))
+            .id();
+        (app, bale)
+    }
+
+    fn hit(app: &mut App, bale: Entity, force: f32) {
+        let other = app.world.spawn_empty().id();
+        app.world
+            .resource_mut::<Events<ContactForceEvent>>()
+            .send(ContactForceEvent {
+                collider1: other,
+                collider2: bale,
+                total_force: Vec3::Y * force,
+                total_force_magnitude: force,
+                max_force_direction: Vec3::Y,
+                max_force_magnitude: force,
+            });
+        app.update();
+    }
+
+    fn straw_count(app: &mut App) -> usize {
+        app.world.query::<&StrawDebris>().iter(&app.world).count()
+    }
+
+    fn hay_bale_sound_count(app: &App) -> usize {
+        let events = app.world.resource::<Events<SoundSampleEvent>>();
+        events
+            .get_reader()
+            .iter(events)
+            .filter(|event| matches!(event.sample, SoundSampleKind::HayBale))
+            .count()
+    }
+
+    #[test]
+    fn hard_hit_breaks_hay_bale_test() {
+        let (mut app, bale) = hay_bale_app();
+        hit(&mut app, bale, HAY_BALE_BREAK_FORCE);
+
+        assert!(app.world.get_entity(bale).is_none());
+        assert_eq!(STRAW_DEBRIS_COUNT as usize, straw_count(&mut app));
+        assert_eq!(1, hay_bale_sound_count(&app));
+    }
+
+    #[test]
+    fn soft_hit_leaves_hay_bale_test() {
+        let (mut app, bale) = hay_bale_app();
+        hit(&mut app, bale, HAY_BALE_BREAK_FORCE - 1.0);
+
+        assert!(app.world.get_entity(bale).is_some());
+        assert_eq!(0, straw_count(&mut app));
+        assert_eq!(0, hay_bale_sound_count(&app));
+    }
+
+    #[test]
+    fn straw_debris_expires_test() {
+        let (mut app, bale) = hay_bale_app();
+        let start = app.world.resource::<Time>().startup();
+        app.world.resource_mut::<Time>().update_with_instant(start);
+        hit(&mut app, bale, HAY_BALE_BREAK_FORCE);
+        assert_eq!(STRAW_DEBRIS_COUNT as usize, straw_count(&mut app));
+
+        // debris lasts up to a quarter longer than STRAW_DEBRIS_SECONDS.
+        let lifetime = Duration::from_secs_f32(STRAW_DEBRIS_SECONDS * 1.5);
+        app.world
+            .resource_mut::<Time>()
+            .update_with_instant(start + lifetime);
+        app.update();
+        assert_eq!(0, straw_count(&mut app));
+    }
+
+    #[test]
+    fn hay_bale_break_force_test() {
+        assert!(!breaks_hay_bale(0.0));
+        assert!(!breaks_hay_bale(49999.0));
+        assert!(breaks_hay_bale(50000.0));
+        assert!(breaks_hay_bale(120000.0));
+    }
+
+    #[test]
+    fn grazing_cow_flees_closing_combine_test() {
+        let mut behaviour = CowBehaviour::default();
+        let combines = [(Vec3::new(0.0, 0.0, 30.0), Vec3::new(0.0, 0.0, -15.0))];
+
+        assert!(behaviour.flee_from_combines(Vec3::ZERO, combines.into_iter()));
+        assert_eq!(CowState::Fleeing, behaviour.state);
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), behaviour.flee_direction);
+    }
+
+    #[test]
+    fn cow_ignores_distant_or_slow_combines_test() {
+        let mut behaviour = CowBehaviour::default();
+        let combines = [
+            (Vec3::new(0.0, 0.0, 100.0), Vec3::new(0.0, 0.0, -15.0)),
+            (Vec3::new(0.0, 0.0, 30.0), Vec3::new(0.0, 0.0, -2.0)),
+            (Vec3::new(0.0, 0.0, 30.0), Vec3::new(0.0, 0.0, 15.0)),
+        ];
+
+        assert!(!behaviour.flee_from_combines(Vec3::ZERO, combines.into_iter()));
+        assert_eq!(CowState::Grazing, behaviour.state);
+    }
+
+    #[test]
+    fn tipped_cow_is_knocked_over_test() {
+        let mut behaviour = CowBehaviour::default();
+
+        behaviour.check_fallen(Quat::from_rotation_x(20.0_f32.to_radians()) * Vec3::Y);
+        assert_eq!(CowState::Grazing, behaviour.state);
+
+        behaviour.check_fallen(Quat::from_rotation_x(80.0_f32.to_radians()) * Vec3::Y);
+        assert_eq!(CowState::KnockedOver, behaviour.state);
+
+        // a knocked over cow does not run from combines.
+        let combines = [(Vec3::new(0.0, 0.0, 30.0), Vec3::new(0.0, 0.0, -15.0))];
+        assert!(!behaviour.flee_from_combines(Vec3::ZERO, combines.into_iter()));
+        assert_eq!(CowState::KnockedOver, behaviour.state);
+    }
+
+    #[test]
+    fn lone_cow_does_not_flock_test() {
+        let cow = Entity::from_raw(0);
+        let herd = [
+            (cow, Vec3::ZERO, Vec3::ZERO),
+            (Entity::from_raw(1), Vec3::new(100.0, 0.0, 0.0), Vec3::X),
+        ];
+
+        assert_eq!(Vec3::ZERO, flocking_velocity(cow, Vec3::ZERO, &herd));
+    }
+
+    #[test]
+    fn cow_moves_towards_herd_and_matches_heading_test() {
+        let cow = Entity::from_raw(0);
+        let herd = [
+            (cow, Vec3::ZERO, Vec3::ZERO),
+            (
+                Entity::from_raw(1),
+                Vec3::new(30.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 4.0),
+            ),
+        ];
+
+        let velocity = flocking_velocity(cow, Vec3::ZERO, &herd);
+        assert!(velocity.x > 0.0);
+        assert!(velocity.z > 0.0);
+        assert_eq!(0.0, velocity.y);
+    }
+
+    #[test]
+    fn cow_keeps_apart_from_close_neighbour_test() {
+        let cow = Entity::from_raw(0);
+        let herd = [
+            (cow, Vec3::ZERO, Vec3::ZERO),
+            (Entity::from_raw(1), Vec3::new(5.0, 0.0, 0.0), Vec3::ZERO),
+        ];
+
+        assert!(flocking_velocity(cow, Vec3::ZERO, &herd).x < 0.0);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Downwards acceleration applied to particles. Much weaker than real gravity so bursts hang in
+/// the air for a moment.
+const PARTICLE_GRAVITY: f32 = 6.0;
+
+/// A short lived visual effect that flies away from where it was spawned and shrinks away.
+#[derive(Component)]
+pub struct Particle {
+    pub velocity: Vec3,
+    pub lifetime: Timer,
+    pub size: f32,
+    /// How strongly gravity pulls the particle. Negative values make it rise.
+    pub gravity_scale: f32,
+}
+
+#[derive(Resource)]
+pub struct ParticleAssets {
+    pub mesh: Handle<Mesh>,
+    pub straw_material: Handle<StandardMaterial>,
+    pub exhaust_material: Handle<StandardMaterial>,
+}
+
+pub struct ParticleBurst {
+    pub position: Vec3,
+    pub count: i32,
+    /// General direction the burst travels in. Zero sprays particles in every direction.
+    pub direction: Vec3,
+    pub speed: f32,
+    pub spread: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub gravity_scale: f32,
+    pub material: Handle<StandardMaterial>,
+}
+
+pub fn setup_particles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+        straw_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.86, 0.74, 0.38),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        exhaust_material: materials.add(StandardMaterial {
+            base_color: Color::rgba(0.2, 0.2, 0.2, 0.7),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+    });
+}
+
+pub fn spawn_particle_burst<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    mesh: Handle<Mesh>,
+    burst: ParticleBurst,
+) -> Commands<'w, 's> {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..burst.count {
+        let random_direction = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let velocity = (burst.direction + (random_direction * burst.spread)).normalize_or_zero()
+            * burst.speed
+            * rng.gen_range(0.5..1.0);
+
+        commands
+            .spawn(PbrBundle {
+                mesh: mesh.clone(),
+                material: burst.material.clone(),
+                transform: Transform::from_translation(burst.position)
+                    .with_rotation(Quat::from_euler(
+                        EulerRot::XYZ,
+                        rng.gen_range(0.0..6.3),
+                        rng.gen_range(0.0..6.3),
+                        rng.gen_range(0.0..6.3),
+                    ))
+                    .with_scale(Vec3::splat(burst.size)),
+                ..default()
+            })
+            .insert(Particle {
+                velocity,
+                lifetime: Timer::from_seconds(
+                    burst.lifetime * rng.gen_range(0.7..1.0),
+                    TimerMode::Once,
+                ),
+                size: burst.size,
+                gravity_scale: burst.gravity_scale,
+            });
+    }
+
+    commands
+}
+
+pub fn particle_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particle_query: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform) in particle_query.iter_mut() {
+        particle.lifetime.tick(time.delta());
+        if particle.lifetime.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= PARTICLE_GRAVITY * particle.gravity_scale * delta;
+        transform.translation += particle.velocity * delta;
+        transform.scale = Vec3::splat(particle.size * particle.lifetime.percent_left());
+    }
+}
@@ -0,0 +1,562 @@
+use std::{num::NonZeroU32, path::PathBuf};
+
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::{BevyDefault, TextureFormatPixelInfo},
+        Extract, RenderApp, RenderStage,
+    },
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{camera::get_primary_window_size, console::Console, menu::AppState};
+
+const FLY_SPEED: f32 = 30.0;
+/// How much faster the photo camera flies with Shift held.
+const FLY_FAST_FACTOR: f32 = 4.0;
+/// Radians the photo camera turns for each pixel the mouse moves.
+const LOOK_SENSITIVITY: f32 = 0.004;
+/// Stops the photo camera looking straight up or down, where its yaw flips over.
+const MAX_PITCH: f32 = 1.55;
+const MIN_FOV_DEGREES: f32 = 10.0;
+const MAX_FOV_DEGREES: f32 = 120.0;
+const FOV_STEP_DEGREES: f32 = 5.0;
+/// Screenshots are this many times the size of the window.
+const SCREENSHOT_SCALE: f32 = 2.0;
+#[cfg(not(target_arch = "wasm32"))]
+const SCREENSHOT_FOLDER: &str = "screenshots";
+const SCREENSHOT_NODE: &str = "photo_mode_screenshot";
+
+/// Adds photo mode, which pauses the physics and lets a free-flying camera take screenshots.
+pub struct PhotoModePlugin;
+
+impl Plugin for PhotoModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhotoMode>()
+            .add_startup_system(spawn_photo_camera)
+            .add_system_set(
+                SystemSet::on_update(AppState::Match)
+                    .with_system(photo_mode_system)
+                    .with_system(photo_camera_system),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Match).with_system(close_photo_mode))
+            .add_system(screenshot_capture_system);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedScreenshots>()
+                .init_resource::<ScreenshotBuffers>()
+                .add_system_to_stage(RenderStage::Extract, extract_screenshots)
+                .add_system_to_stage(RenderStage::Prepare, prepare_screenshots)
+                .add_system_to_stage(RenderStage::Cleanup, save_screenshots);
+
+            let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+            render_graph.add_node(SCREENSHOT_NODE, ScreenshotNode);
+            render_graph
+                .add_node_edge(
+                    bevy::render::main_graph::node::CAMERA_DRIVER,
+                    SCREENSHOT_NODE,
+                )
+                .unwrap();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PhotoMode {
+    pub active: bool,
+    /// Cameras that were active before photo mode started, to switch back to afterwards.
+    previous_cameras: Vec<Entity>,
+}
+
+/// The free-flying camera used in photo mode.
+#[derive(Component, Default)]
+pub struct PhotoCamera {
+    yaw: f32,
+    pitch: f32,
+}
+
+/// A camera rendering a high resolution copy of the photo camera's view, to be saved to disk.
+#[derive(Component, Clone)]
+pub struct ScreenshotCapture {
+    image: Handle<Image>,
+    path: PathBuf,
+    /// Frames left to render before the image is copied, so the image is ready on the GPU.
+    frames_until_capture: u32,
+}
+
+fn spawn_photo_camera(mut commands: Commands) {
+    commands
+        .spawn(Camera3dBundle {
+            camera: Camera {
+                is_active: false,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UiCameraConfig { show_ui: true })
+        .insert(PhotoCamera::default());
+}
+
+/// F8 switches photo mode on and off, pausing the physics and taking over from the active camera.
+pub fn photo_mode_system(
+    keys: Res<Input<KeyCode>>,
+    mut photo_mode: ResMut<PhotoMode>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+    mut camera_query: Query<
+        (Entity, &mut Camera, &GlobalTransform, &Projection),
+        (Without<PhotoCamera>, Without<ScreenshotCapture>),
+    >,
+    mut photo_camera_query: Query<
+        (
+            &mut PhotoCamera,
+            &mut Camera,
+            &mut Transform,
+            &mut Projection,
+        ),
+        Without<ScreenshotCapture>,
+    >,
+) {
+    if !keys.just_pressed(KeyCode::F8) {
+        return;
+    }
+    let (mut photo_camera, mut camera, mut transform, mut projection) =
+        match photo_camera_query.get_single_mut() {
+            Ok(photo_camera) => photo_camera,
+            Err(_) => return,
+        };
+
+    photo_mode.active = !photo_mode.active;
+    rapier_configuration.physics_pipeline_active = !photo_mode.active;
+    camera.is_active = photo_mode.active;
+
+    if photo_mode.active {
+        photo_mode.previous_cameras.clear();
+        for (entity, mut other_camera, other_transform, other_projection) in camera_query.iter_mut()
+        {
+            if !other_camera.is_active {
+                continue;
+            }
+            other_camera.is_active = false;
+            photo_mode.previous_cameras.push(entity);
+
+            // start from where the player was looking.
+            *transform = other_transform.compute_transform();
+            transform.scale = Vec3::ONE;
+            *projection = other_projection.clone();
+            let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+            photo_camera.yaw = yaw;
+            photo_camera.pitch = pitch;
+        }
+    } else {
+        for entity in photo_mode.previous_cameras.drain(..) {
+            if let Ok((_, mut other_camera, ..)) = camera_query.get_mut(entity) {
+                other_camera.is_active = true;
+            }
+        }
+    }
+}
+
+/// Leaves photo mode when the match ends, so the physics isn't left paused.
+fn close_photo_mode(
+    mut photo_mode: ResMut<PhotoMode>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+    mut photo_camera_query: Query<&mut Camera, With<PhotoCamera>>,
+) {
+    photo_mode.active = false;
+    photo_mode.previous_cameras.clear();
+    rapier_configuration.physics_pipeline_active = true;
+    for mut camera in photo_camera_query.iter_mut() {
+        camera.is_active = false;
+    }
+}
+
+/// Flies the photo camera with WASD, R and F, looking around with the right mouse button. The
+/// mouse wheel zooms, H hides the HUD and F12 saves a screenshot. The keys are left alone while
+/// the console is open.
+#[allow(clippy::too_many_arguments)]
+pub fn photo_camera_system(
+    commands: Commands,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    photo_mode: Res<PhotoMode>,
+    console: Res<Console>,
+    mut photo_camera_query: Query<(
+        &mut PhotoCamera,
+        &mut Transform,
+        &mut Projection,
+        &mut UiCameraConfig,
+    )>,
+) {
+    let mouse_motion: Vec2 = mouse_motion_events.iter().map(|event| event.delta).sum();
+    let scroll: f32 = mouse_wheel_events.iter().map(|event| event.y).sum();
+    if !photo_mode.active {
+        return;
+    }
+    let (mut photo_camera, mut transform, mut projection, mut ui_camera_config) =
+        match photo_camera_query.get_single_mut() {
+            Ok(photo_camera) => photo_camera,
+            Err(_) => return,
+        };
+
+    if mouse_buttons.pressed(MouseButton::Right) {
+        photo_camera.yaw -= mouse_motion.x * LOOK_SENSITIVITY;
+        photo_camera.pitch = look_pitch(photo_camera.pitch, mouse_motion.y);
+    }
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, photo_camera.yaw, photo_camera.pitch, 0.0);
+
+    if let Projection::Perspective(perspective) = projection.as_mut() {
+        if scroll.abs() > 0.0 {
+            perspective.fov = zoomed_fov(perspective.fov, scroll);
+        }
+    }
+
+    // the keys type into the console while it is open.
+    if console.open {
+        return;
+    }
+
+    let mut direction = Vec3::ZERO;
+    if keys.pressed(KeyCode::W) {
+        direction += transform.forward();
+    }
+    if keys.pressed(KeyCode::S) {
+        direction += transform.back();
+    }
+    if keys.pressed(KeyCode::A) {
+        direction += transform.left();
+    }
+    if keys.pressed(KeyCode::D) {
+        direction += transform.right();
+    }
+    if keys.pressed(KeyCode::R) {
+        direction += Vec3::Y;
+    }
+    if keys.pressed(KeyCode::F) {
+        direction -= Vec3::Y;
+    }
+    let mut speed = FLY_SPEED;
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        speed *= FLY_FAST_FACTOR;
+    }
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_seconds();
+
+    if keys.just_pressed(KeyCode::H) {
+        ui_camera_config.show_ui = !ui_camera_config.show_ui;
+    }
+
+    if keys.just_pressed(KeyCode::F12) {
+        spawn_screenshot_camera(commands, &mut images, &windows, &transform, &projection);
+    }
+}
+
+/// Gets the photo camera's pitch after the mouse moves, without letting it look straight up or
+/// down.
+fn look_pitch(pitch: f32, mouse_motion_y: f32) -> f32 {
+    (pitch - (mouse_motion_y * LOOK_SENSITIVITY)).clamp(-MAX_PITCH, MAX_PITCH)
+}
+
+/// Gets the field of view, in radians, after scrolling the mouse wheel. Scrolling up zooms in.
+fn zoomed_fov(fov: f32, scroll: f32) -> f32 {
+    let fov = fov.to_degrees() - (scroll * FOV_STEP_DEGREES);
+    fov.clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES).to_radians()
+}
+
+/// Adds a camera that renders the photo camera's view to a large image to be saved.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_screenshot_camera<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    images: &mut Assets<Image>,
+    windows: &Res<Windows>,
+    transform: &Transform,
+    projection: &Projection,
+) -> Commands<'w, 's> {
+    let window_size = get_primary_window_size(&windows) * SCREENSHOT_SCALE;
+    let size = Extent3d {
+        width: window_size.x as u32,
+        height: window_size.y as u32,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    commands
+        .spawn(Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image.clone()),
+                priority: -1,
+                ..default()
+            },
+            transform: *transform,
+            projection: projection.clone(),
+            ..default()
+        })
+        // the HUD is laid out for the window, so it would be in the wrong place.
+        .insert(UiCameraConfig { show_ui: false })
+        .insert(ScreenshotCapture {
+            image,
+            path: screenshot_path(),
+            frames_until_capture: 2,
+        });
+    commands
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_screenshot_camera<'w, 's>(
+    commands: Commands<'w, 's>,
+    _images: &mut Assets<Image>,
+    _windows: &Res<Windows>,
+    _transform: &Transform,
+    _projection: &Projection,
+) -> Commands<'w, 's> {
+    warn!("Screenshots can't be saved in the browser");
+    commands
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn screenshot_path() -> PathBuf {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    PathBuf::from(SCREENSHOT_FOLDER).join(format!("combine-derby-{}.png", seconds))
+}
+
+/// Counts down screenshot cameras until they are captured, then cleans them up.
+pub fn screenshot_capture_system(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut capture_query: Query<(Entity, &mut ScreenshotCapture)>,
+) {
+    for (entity, mut capture) in capture_query.iter_mut() {
+        if capture.frames_until_capture == 0 {
+            commands.entity(entity).despawn();
+            images.remove(&capture.image);
+        } else {
+            capture.frames_until_capture -= 1;
+        }
+    }
+}
+
+/// Screenshots ready to be copied off the GPU this frame.
+#[derive(Resource, Default)]
+struct ExtractedScreenshots(Vec<ScreenshotCapture>);
+
+/// A screenshot being copied into a buffer the CPU can read.
+struct ScreenshotBuffer {
+    image: Handle<Image>,
+    path: PathBuf,
+    buffer: Buffer,
+    size: Extent3d,
+    bytes_per_row: u32,
+    /// Rows in the buffer are padded to the alignment the GPU needs.
+    padded_bytes_per_row: u32,
+}
+
+#[derive(Resource, Default)]
+struct ScreenshotBuffers(Vec<ScreenshotBuffer>);
+
+fn extract_screenshots(
+    mut extracted: ResMut<ExtractedScreenshots>,
+    capture_query: Extract<Query<&ScreenshotCapture>>,
+) {
+    extracted.0 = capture_query
+        .iter()
+        .filter(|capture| capture.frames_until_capture == 0)
+        .cloned()
+        .collect();
+}
+
+fn prepare_screenshots(
+    extracted: Res<ExtractedScreenshots>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    mut buffers: ResMut<ScreenshotBuffers>,
+) {
+    for capture in extracted.0.iter() {
+        let gpu_image = match images.get(&capture.image) {
+            Some(gpu_image) => gpu_image,
+            None => continue,
+        };
+        let size = Extent3d {
+            width: gpu_image.size.x as u32,
+            height: gpu_image.size.y as u32,
+            depth_or_array_layers: 1,
+        };
+        let bytes_per_row = size.width as usize * gpu_image.texture_format.pixel_size();
+        let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(bytes_per_row);
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("screenshot_buffer"),
+            size: (padded_bytes_per_row * size.height as usize) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        buffers.0.push(ScreenshotBuffer {
+            image: capture.image.clone(),
+            path: capture.path.clone(),
+            buffer,
+            size,
+            bytes_per_row: bytes_per_row as u32,
+            padded_bytes_per_row: padded_bytes_per_row as u32,
+        });
+    }
+}
+
+/// Copies screenshot images into buffers once the cameras have rendered them.
+struct ScreenshotNode;
+
+impl Node for ScreenshotNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let images = world.resource::<RenderAssets<Image>>();
+        for screenshot in world.resource::<ScreenshotBuffers>().0.iter() {
+            if let Some(gpu_image) = images.get(&screenshot.image) {
+                render_context.command_encoder.copy_texture_to_buffer(
+                    gpu_image.texture.as_image_copy(),
+                    ImageCopyBuffer {
+                        buffer: &screenshot.buffer,
+                        layout: ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: NonZeroU32::new(screenshot.padded_bytes_per_row),
+                            rows_per_image: None,
+                        },
+                    },
+                    screenshot.size,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads copied screenshots back from the GPU and writes them out as PNG files.
+fn save_screenshots(mut buffers: ResMut<ScreenshotBuffers>, render_device: Res<RenderDevice>) {
+    for screenshot in buffers.0.drain(..) {
+        let slice = screenshot.buffer.slice(..);
+        render_device.map_buffer(&slice, MapMode::Read, |_| {});
+        render_device.poll(wgpu::Maintain::Wait);
+
+        let pixels = strip_row_padding(
+            &slice.get_mapped_range(),
+            screenshot.bytes_per_row as usize,
+            screenshot.padded_bytes_per_row as usize,
+        );
+        screenshot.buffer.unmap();
+
+        let image = Image::new(
+            screenshot.size,
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::bevy_default(),
+        );
+        save_screenshot(image, screenshot.path);
+    }
+}
+
+/// Drops the padding the GPU adds to the end of each row of a copied image.
+fn strip_row_padding(data: &[u8], bytes_per_row: usize, padded_bytes_per_row: usize) -> Vec<u8> {
+    data.chunks(padded_bytes_per_row)
+        .flat_map(|row| row[..bytes_per_row].iter().copied())
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_screenshot(image: Image, path: PathBuf) {
+    // encoding a large PNG takes a while, so it is done off the render thread.
+    std::thread::spawn(move || {
+        let result = image
+            .try_into_dynamic()
+            .map_err(|error| error.to_string())
+            .and_then(|image| {
+                if let Some(folder) = path.parent() {
+                    std::fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+                }
+                image.save(&path).map_err(|error| error.to_string())
+            });
+        match result {
+            Ok(()) => info!("Saved screenshot to {}", path.display()),
+            Err(error) => warn!("Couldn't save screenshot: {}", error),
+        }
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_screenshot(_image: Image, _path: PathBuf) {}
+
+#[cfg(test)]
+mod tests {
+    use crate::photo_mode::{
+        look_pitch, strip_row_padding, zoomed_fov, MAX_FOV_DEGREES, MAX_PITCH, MIN_FOV_DEGREES,
+    };
+
+    #[test]
+    fn zoomed_fov_test() {
+        let fov = zoomed_fov(60.0_f32.to_radians(), 2.0);
+        assert!((fov.to_degrees() - 50.0).abs() < 0.001);
+
+        let fov = zoomed_fov(60.0_f32.to_radians(), -2.0);
+        assert!((fov.to_degrees() - 70.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn zoomed_fov_clamped_test() {
+        let fov = zoomed_fov(MIN_FOV_DEGREES.to_radians(), 10.0);
+        assert!((fov.to_degrees() - MIN_FOV_DEGREES).abs() < 0.001);
+
+        let fov = zoomed_fov(MAX_FOV_DEGREES.to_radians(), -10.0);
+        assert!((fov.to_degrees() - MAX_FOV_DEGREES).abs() < 0.001);
+    }
+
+    #[test]
+    fn look_pitch_clamped_test() {
+        // moving the mouse down looks down.
+        assert!(look_pitch(0.0, 10.0) < 0.0);
+        assert_eq!(MAX_PITCH, look_pitch(1.5, -1000.0));
+        assert_eq!(-MAX_PITCH, look_pitch(-1.5, 1000.0));
+    }
+
+    #[test]
+    fn strip_row_padding_test() {
+        // two rows of three bytes, each padded to eight.
+        let data = [1, 2, 3, 0, 0, 0, 0, 0, 4, 5, 6, 0, 0, 0, 0, 0];
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], strip_row_padding(&data, 3, 8));
+    }
+
+    #[test]
+    fn strip_row_padding_unpadded_test() {
+        let data = [1, 2, 3, 4, 5, 6];
+        assert_eq!(data.to_vec(), strip_row_padding(&data, 3, 3));
+    }
+}
@@ -0,0 +1,468 @@
+use std::{collections::HashSet, time::Duration};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::{
+    arena::{GROUND_Y_POSITION, PLANE_SIZE},
+    boost::Boost,
+    combine::{Ballast, Combine, Wheel, COMBINE_MAX_HEALTH, PLAYER_COMBINE_ID},
+};
+
+const PICKUPS_PER_KIND: i32 = 2;
+/// Delay before the first pickups appear, so the arena colliders are in place to check against.
+const PICKUP_FIRST_SPAWN_SECONDS: f32 = 1.0;
+const PICKUP_RESPAWN_SECONDS: f32 = 20.0;
+/// Time before trying again when no free point could be found.
+const PICKUP_RETRY_SECONDS: f32 = 2.0;
+
+const PICKUP_SIZE: f32 = 3.0;
+const PICKUP_HEIGHT: f32 = 6.0;
+const PICKUP_SPIN_SPEED: f32 = 1.5;
+/// Radius around a pickup that must be clear of other colliders.
+const PICKUP_CLEARANCE: f32 = 5.0;
+const PICKUP_SPACING: f32 = 30.0;
+const PICKUP_FENCE_MARGIN: f32 = 20.0;
+const PICKUP_PLACEMENT_ATTEMPTS: i32 = 20;
+
+const REPAIR_AMOUNT: f32 = 50.0;
+const HEAVY_BALLAST_SECONDS: f32 = 10.0;
+const SHIELD_SECONDS: f32 = 8.0;
+/// Mass of the ballast while heavy ballast is active, roughly half the mass of the combine body.
+const HEAVY_BALLAST_MASS: f32 = 5000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    /// Restores some of the combine's health.
+    Repair,
+    /// Refills the boost meter.
+    Nitro,
+    /// Makes the combine much heavier and harder to push around.
+    HeavyBallast,
+    /// Stops the combine taking damage.
+    Shield,
+}
+
+impl PickupKind {
+    pub const ALL: [PickupKind; 4] = [
+        PickupKind::Repair,
+        PickupKind::Nitro,
+        PickupKind::HeavyBallast,
+        PickupKind::Shield,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PickupKind::Repair => "Repair",
+            PickupKind::Nitro => "Nitro",
+            PickupKind::HeavyBallast => "Heavy",
+            PickupKind::Shield => "Shield",
+        }
+    }
+
+    pub fn colour(&self) -> Color {
+        match self {
+            PickupKind::Repair => Color::rgb(0.2, 0.9, 0.3),
+            PickupKind::Nitro => Color::rgb(1.0, 0.5, 0.1),
+            PickupKind::HeavyBallast => Color::rgb(0.45, 0.45, 0.5),
+            PickupKind::Shield => Color::rgb(0.3, 0.6, 1.0),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+/// Timed effects from pickups held by a combine.
+#[derive(Component, Default)]
+pub struct PowerUps {
+    pub heavy_ballast: Option<Timer>,
+    pub shield: Option<Timer>,
+}
+
+impl PowerUps {
+    pub fn heavy_ballast_active(&self) -> bool {
+        self.heavy_ballast.is_some()
+    }
+
+    pub fn shielded(&self) -> bool {
+        self.shield.is_some()
+    }
+
+    /// Runs down the timers, removing power-ups that have run out.
+    fn tick(&mut self, delta: Duration) {
+        for timer in [&mut self.heavy_ballast, &mut self.shield] {
+            let finished = match timer {
+                Some(timer) => timer.tick(delta).finished(),
+                None => false,
+            };
+            if finished {
+                *timer = None;
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct PickupAssets {
+    mesh: Handle<Mesh>,
+    /// Materials in the same order as [`PickupKind::ALL`].
+    materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl PickupAssets {
+    fn material(&self, kind: PickupKind) -> Handle<StandardMaterial> {
+        self.materials[kind as usize].clone()
+    }
+}
+
+/// Pickups waiting to be placed in the arena.
+#[derive(Resource, Default)]
+pub struct PickupSpawner {
+    pending: Vec<(PickupKind, Timer)>,
+}
+
+#[derive(Component)]
+pub struct PowerUpUi;
+
+pub fn setup_pickups(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let pickup_materials = PickupKind::ALL
+        .iter()
+        .map(|kind| {
+            materials.add(StandardMaterial {
+                base_color: kind.colour(),
+                emissive: kind.colour() * 0.5,
+                ..default()
+            })
+        })
+        .collect();
+    commands.insert_resource(PickupAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: PICKUP_SIZE })),
+        materials: pickup_materials,
+    });
+
+    let mut spawner = PickupSpawner::default();
+    for kind in PickupKind::ALL {
+        for _ in 0..PICKUPS_PER_KIND {
+            spawner.pending.push((
+                kind,
+                Timer::from_seconds(PICKUP_FIRST_SPAWN_SECONDS, TimerMode::Once),
+            ));
+        }
+    }
+    commands.insert_resource(spawner);
+
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(65.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "".to_string(),
+                    style: TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..default()
+        })
+        .insert(PowerUpUi);
+}
+
+/// Places pending pickups at free points in the arena and spins the ones already placed.
+pub fn pickup_spawn_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    pickup_assets: Res<PickupAssets>,
+    mut spawner: ResMut<PickupSpawner>,
+    mut pickup_query: Query<&mut Transform, With<Pickup>>,
+) {
+    for mut transform in pickup_query.iter_mut() {
+        transform.rotate_y(PICKUP_SPIN_SPEED * time.delta_seconds());
+    }
+
+    let mut occupied: Vec<Vec3> = pickup_query
+        .iter()
+        .map(|transform| transform.translation)
+        .collect();
+    let mut rng = rand::thread_rng();
+
+    for (kind, timer) in spawner.pending.iter_mut() {
+        timer.tick(time.delta());
+        if !timer.finished() {
+            continue;
+        }
+
+        let position = match find_free_point(&rapier_context, &occupied, &mut rng) {
+            Some(position) => position,
+            None => {
+                *timer = Timer::from_seconds(PICKUP_RETRY_SECONDS, TimerMode::Once);
+                continue;
+            }
+        };
+        occupied.push(position);
+
+        commands
+            .spawn(PbrBundle {
+                mesh: pickup_assets.mesh.clone(),
+                material: pickup_assets.material(*kind),
+                transform: Transform::from_translation(position)
+                    .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_4)),
+                ..default()
+            })
+            .insert(Collider::ball(PICKUP_SIZE))
+            .insert(Sensor)
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Pickup { kind: *kind });
+    }
+
+    spawner.pending.retain(|(_, timer)| !timer.finished());
+}
+
+/// Finds a point above the ground that is clear of obstacles and other pickups.
+fn find_free_point(
+    rapier_context: &RapierContext,
+    occupied: &[Vec3],
+    rng: &mut impl Rng,
+) -> Option<Vec3> {
+    let limit = PLANE_SIZE - PICKUP_FENCE_MARGIN;
+    let clearance = Collider::ball(PICKUP_CLEARANCE);
+
+    for _ in 0..PICKUP_PLACEMENT_ATTEMPTS {
+        let position = Vec3::new(
+            rng.gen_range(-limit..limit),
+            GROUND_Y_POSITION + PICKUP_HEIGHT,
+            rng.gen_range(-limit..limit),
+        );
+        if occupied
+            .iter()
+            .any(|other| other.distance(position) < PICKUP_SPACING)
+        {
+            continue;
+        }
+        let blocked = rapier_context
+            .intersection_with_shape(
+                position,
+                Quat::IDENTITY,
+                &clearance,
+                QueryFilter::default().exclude_sensors(),
+            )
+            .is_some();
+        if !blocked {
+            return Some(position);
+        }
+    }
+    None
+}
+
+/// Gives pickups to the combines that drive through them.
+pub fn pickup_collect_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut spawner: ResMut<PickupSpawner>,
+    pickup_query: Query<&Pickup>,
+    wheel_query: Query<&Wheel>,
+    mut combine_query: Query<(Entity, &mut Combine, &mut PowerUps, &mut Boost)>,
+) {
+    let mut collected = HashSet::new();
+
+    for collision_event in collision_events.iter() {
+        let (entity1, entity2) = match collision_event {
+            CollisionEvent::Started(entity1, entity2, _) => (*entity1, *entity2),
+            CollisionEvent::Stopped(..) => continue,
+        };
+
+        for (pickup_entity, other_entity) in [(entity1, entity2), (entity2, entity1)] {
+            let pickup = match pickup_query.get(pickup_entity) {
+                Ok(pickup) => pickup,
+                Err(_) => continue,
+            };
+            if collected.contains(&pickup_entity) {
+                continue;
+            }
+
+            let wheel_combine_id = wheel_query
+                .get(other_entity)
+                .ok()
+                .map(|wheel| wheel.combine_id);
+            for (combine_entity, mut combine, mut power_ups, mut boost) in combine_query.iter_mut()
+            {
+                let holder =
+                    combine_entity == other_entity || wheel_combine_id == Some(combine.combine_id);
+                if !holder {
+                    continue;
+                }
+
+                match pickup.kind {
+                    PickupKind::Repair => {
+                        combine.health = (combine.health + REPAIR_AMOUNT).min(COMBINE_MAX_HEALTH);
+                    }
+                    PickupKind::Nitro => boost.refill(),
+                    PickupKind::HeavyBallast => {
+                        power_ups.heavy_ballast =
+                            Some(Timer::from_seconds(HEAVY_BALLAST_SECONDS, TimerMode::Once));
+                    }
+                    PickupKind::Shield => {
+                        power_ups.shield =
+                            Some(Timer::from_seconds(SHIELD_SECONDS, TimerMode::Once));
+                    }
+                }
+
+                collected.insert(pickup_entity);
+                commands.entity(pickup_entity).despawn_recursive();
+                spawner.pending.push((
+                    pickup.kind,
+                    Timer::from_seconds(PICKUP_RESPAWN_SECONDS, TimerMode::Once),
+                ));
+                break;
+            }
+        }
+    }
+}
+
+/// Runs down power-up timers and applies heavy ballast to the ballast collider.
+pub fn power_up_system(
+    time: Res<Time>,
+    mut combine_query: Query<(&Combine, &mut PowerUps)>,
+    mut ballast_query: Query<(&Ballast, &mut ColliderMassProperties)>,
+) {
+    let mut heavy_combines = HashSet::new();
+
+    for (combine, mut power_ups) in combine_query.iter_mut() {
+        power_ups.tick(time.delta());
+
+        if power_ups.heavy_ballast_active() {
+            heavy_combines.insert(combine.combine_id);
+        }
+    }
+
+    for (ballast, mut mass_properties) in ballast_query.iter_mut() {
+        let wanted = if heavy_combines.contains(&ballast.combine_id) {
+            ColliderMassProperties::Mass(HEAVY_BALLAST_MASS)
+        } else {
+            ColliderMassProperties::Density(ballast.density)
+        };
+        // only write on a change so rapier doesn't recompute the mass every frame.
+        if *mass_properties != wanted {
+            *mass_properties = wanted;
+        }
+    }
+}
+
+pub fn power_up_ui_system(
+    combine_query: Query<(&Combine, &PowerUps)>,
+    mut text_query: Query<&mut Text, With<PowerUpUi>>,
+) {
+    let mut text = match text_query.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+
+    for (combine, power_ups) in combine_query.iter() {
+        if combine.combine_id != PLAYER_COMBINE_ID {
+            continue;
+        }
+
+        let mut parts = Vec::new();
+        let timers = [
+            (PickupKind::HeavyBallast, &power_ups.heavy_ballast),
+            (PickupKind::Shield, &power_ups.shield),
+        ];
+        for (kind, timer) in timers {
+            if let Some(timer) = timer {
+                parts.push(format!(
+                    "{} {:.0}s",
+                    kind.name(),
+                    timer.remaining_secs().ceil()
+                ));
+            }
+        }
+        text.sections[0].value = parts.join("    ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        arena::PLANE_SIZE,
+        pickup::{find_free_point, PowerUps, PICKUP_SPACING},
+    };
+
+    #[test]
+    fn find_free_point_test() {
+        let rapier_context = RapierContext::default();
+        let occupied = [Vec3::ZERO, Vec3::new(50.0, 0.0, 50.0)];
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..10 {
+            let position = find_free_point(&rapier_context, &occupied, &mut rng).unwrap();
+            assert!(position.x.abs() < PLANE_SIZE && position.z.abs() < PLANE_SIZE);
+            for other in occupied.iter() {
+                assert!(other.distance(position) >= PICKUP_SPACING);
+            }
+        }
+    }
+
+    #[test]
+    fn no_free_point_in_crowded_arena_test() {
+        let rapier_context = RapierContext::default();
+        let mut occupied = Vec::new();
+        let mut x = -PLANE_SIZE;
+        while x <= PLANE_SIZE {
+            let mut z = -PLANE_SIZE;
+            while z <= PLANE_SIZE {
+                occupied.push(Vec3::new(x, 0.0, z));
+                z += 10.0;
+            }
+            x += 10.0;
+        }
+        let mut rng = StdRng::seed_from_u64(3);
+
+        assert_eq!(None, find_free_point(&rapier_context, &occupied, &mut rng));
+    }
+
+    #[test]
+    fn power_ups_expire_test() {
+        let mut power_ups = PowerUps {
+            heavy_ballast: Some(Timer::from_seconds(10.0, TimerMode::Once)),
+            shield: Some(Timer::from_seconds(8.0, TimerMode::Once)),
+        };
+
+        power_ups.tick(Duration::from_secs(5));
+        assert!(power_ups.heavy_ballast_active());
+        assert!(power_ups.shielded());
+
+        power_ups.tick(Duration::from_secs(4));
+        assert!(power_ups.heavy_ballast_active());
+        assert!(!power_ups.shielded());
+
+        power_ups.tick(Duration::from_secs(1));
+        assert!(!power_ups.heavy_ballast_active());
+    }
+}
@@ -1,134 +1,472 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use bevy::{audio::AudioSink, prelude::*};
+use rand::Rng;
 
-use crate::{events::SoundSampleEvent, input::Settings};
+use crate::{
+    engine::{Engine, IDLE_RPM, REDLINE_RPM},
+    events::{SoundSampleEvent, SoundSampleKind},
+    mixer::AudioBus,
+    spatial_audio::{spawn_one_shot, SoundEmitter, SpatialAudioPlayer, SpatialSink},
+};
 
 const ENGINE_IDLE_VOLUME: f32 = 0.2;
-const ENGINE_NO_IDLE_VOULME: f32 = 0.3;
-const BACKGROUND_SOUND_VOLUME: f32 = 0.2;
+const ENGINE_REV_VOLUME: f32 = 0.35;
+/// Playback speed of the idle layer, from idle to the redline.
+const ENGINE_IDLE_SPEED: (f32, f32) = (0.8, 1.1);
+/// Playback speed of the rev layer, from idle to the redline.
+const ENGINE_REV_SPEED: (f32, f32) = (1.0, 1.9);
+const ENGINE_IDLE_LAYER: usize = 0;
+const ENGINE_REV_LAYER: usize = 1;
+
+/// Impacts softer than this don't make a sound.
+pub const IMPACT_MIN_FORCE: f32 = 2000.0;
+/// Impacts at least this hard play at full volume.
+const IMPACT_FULL_FORCE: f32 = 100000.0;
+/// Volume of the softest impact that still makes a sound.
+const IMPACT_MIN_VOLUME: f32 = 0.15;
+
+/// Most samples that can play at once. Further samples replace the least important one playing.
+const MAX_VOICES: usize = 16;
+/// How far the pitch of a sample is randomly moved each time it plays, as a fraction.
+const DEFAULT_PITCH_VARIATION: f32 = 0.05;
 
 #[derive(Component)]
 pub struct SoundCollider {
-    pub sound_sample: SoundSampleEvent,
+    pub sound_sample: SoundSampleKind,
 }
 
-pub struct SoundSample {
+/// A recording a sample can be played from, and the speed to play it at.
+struct SampleVariation {
     audio_source: Handle<AudioSource>,
-    last_play_time: Duration,
+    speed: f32,
+}
+
+pub struct SoundSample {
+    variations: Vec<SampleVariation>,
+    /// When each source last played the sample.
+    last_play_times: HashMap<Entity, Duration>,
     /**
-     * The time between samples should be started. Use this to stop several samples being played over each other.
+     * The time between samples should be started by the same source. Use this to stop several samples being played over each other.
      */
     interval: Duration,
     volume: f32,
+    bus: AudioBus,
+    /// How important the sample is at full volume, when deciding which voices to keep.
+    priority: f32,
+    pitch_variation: f32,
+}
+
+/// A sample variation ready to be played.
+pub struct SamplePlayback {
+    audio_source: Handle<AudioSource>,
+    bus: AudioBus,
+    settings: PlaybackSettings,
+    priority: f32,
 }
 
 impl SoundSample {
     pub fn new(audio_source: Handle<AudioSource>, interval: Duration, volume: f32) -> Self {
         SoundSample {
-            audio_source,
+            variations: vec![SampleVariation {
+                audio_source,
+                speed: 1.0,
+            }],
             interval,
-            last_play_time: Duration::ZERO,
+            last_play_times: HashMap::new(),
             volume,
+            bus: AudioBus::Effects,
+            priority: 1.0,
+            pitch_variation: DEFAULT_PITCH_VARIATION,
         }
     }
 
-    pub fn play(&mut self, time: &Res<Time>, audio: &Res<Audio>, volume_modifier: f32) {
-        if time.elapsed() > self.last_play_time + self.interval {
-            audio.play_with_settings(
-                self.audio_source.clone(),
-                PlaybackSettings {
-                    volume: self.volume * volume_modifier,
-                    ..default()
-                },
-            );
-            self.last_play_time = time.elapsed();
+    /// Plays the sample on a mixer bus other than effects.
+    pub fn on_bus(mut self, bus: AudioBus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// Plays every variation of the sample faster, raising its pitch.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        for variation in self.variations.iter_mut() {
+            variation.speed = speed;
+        }
+        self
+    }
+
+    /// Adds another recording or pitch the sample is randomly played with.
+    pub fn with_variation(mut self, audio_source: Handle<AudioSource>, speed: f32) -> Self {
+        self.variations.push(SampleVariation {
+            audio_source,
+            speed,
+        });
+        self
+    }
+
+    pub fn with_priority(mut self, priority: f32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_pitch_variation(mut self, pitch_variation: f32) -> Self {
+        self.pitch_variation = pitch_variation;
+        self
+    }
+
+    /// Picks a variation and pitch to play, unless the source played the sample too recently.
+    pub fn start(
+        &mut self,
+        now: Duration,
+        source: Entity,
+        volume_modifier: f32,
+    ) -> Option<SamplePlayback> {
+        let interval = self.interval;
+        self.last_play_times
+            .retain(|_, last_play_time| now <= *last_play_time + interval);
+        if self.last_play_times.contains_key(&source) {
+            return None;
+        }
+        self.last_play_times.insert(source, now);
+
+        let mut rng = rand::thread_rng();
+        let variation = &self.variations[rng.gen_range(0..self.variations.len())];
+        let pitch = if self.pitch_variation > 0.0 {
+            1.0 + rng.gen_range(-self.pitch_variation..self.pitch_variation)
+        } else {
+            1.0
+        };
+        let volume = self.volume * volume_modifier;
+        Some(SamplePlayback {
+            audio_source: variation.audio_source.clone(),
+            bus: self.bus,
+            settings: PlaybackSettings {
+                volume,
+                speed: variation.speed * pitch,
+                ..default()
+            },
+            priority: self.priority * volume,
+        })
+    }
+}
+
+/// A sample playing through the [`Voices`].
+struct Voice {
+    sink: SpatialSink,
+    priority: f32,
+}
+
+/// Limits how many samples play at once.
+#[derive(Resource, Default)]
+pub struct Voices {
+    playing: Vec<Voice>,
+}
+
+impl Voices {
+    /// Plays a sample at a position, stopping a less important one if too many are playing.
+    pub fn play_at<'w, 's>(
+        &mut self,
+        mut commands: Commands<'w, 's>,
+        spatial_audio: &mut SpatialAudioPlayer,
+        audio_sinks: &Assets<AudioSink>,
+        playback: SamplePlayback,
+        position: Vec3,
+    ) -> Commands<'w, 's> {
+        self.playing.retain(|voice| !voice.sink.finished());
+        if self.playing.len() >= MAX_VOICES {
+            let priorities: Vec<f32> = self.playing.iter().map(|voice| voice.priority).collect();
+            match voice_to_steal(&priorities, playback.priority) {
+                Some(index) => {
+                    let stolen = self.playing.swap_remove(index);
+                    if let Some(sink) = audio_sinks.get(&stolen.sink.sink) {
+                        sink.stop();
+                    }
+                }
+                None => return commands,
+            }
         }
+
+        if let Some(sink) =
+            spatial_audio.play(&playback.audio_source, playback.bus, playback.settings)
+        {
+            self.playing.push(Voice {
+                sink: sink.clone(),
+                priority: playback.priority,
+            });
+            commands = spawn_one_shot(commands, sink, position);
+        }
+        commands
     }
 }
 
+/// Gets the voice to stop for a new one, or `None` if every voice is more important.
+fn voice_to_steal(priorities: &[f32], priority: f32) -> Option<usize> {
+    priorities
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .filter(|(_, lowest)| **lowest < priority)
+        .map(|(index, _)| index)
+}
+
 #[derive(Component)]
 pub struct SoundSamples {
     pub moo: SoundSample,
 
     pub hay: SoundSample,
 
-    pub engine_sound_sink: Handle<AudioSink>,
+    pub engine_surge: SoundSample,
+
+    pub crash: SoundSample,
 
-    pub background_sound_sink: Handle<AudioSink>,
+    pub fence: SoundSample,
+
+    pub engine_loop: Handle<AudioSource>,
 }
 
-pub fn setup_sounds(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    audio_sinks: Res<Assets<AudioSink>>,
-    settings: Res<Settings>
-) {
+pub fn setup_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
     let moo: Handle<AudioSource> = asset_server.load("sounds/moo.ogg");
     let hay: Handle<AudioSource> = asset_server.load("sounds/hay1.ogg");
     let engine_sample: Handle<AudioSource> = asset_server.load("sounds/engine_heavy_loop.ogg");
-
-    let background: Handle<AudioSource> = asset_server.load("sounds/jazzyfrenchy.ogg");
-
-    let engine_sound_sink = audio.play_with_settings(
-        engine_sample,
-        PlaybackSettings {
-            repeat: true,
-            volume: ENGINE_IDLE_VOLUME * settings.volume,
-            ..Default::default()
-        },
-    );
-
-    let background_sound_sink = audio.play_with_settings(
-        background,
-        PlaybackSettings {
-            repeat: true,
-            volume: BACKGROUND_SOUND_VOLUME * settings.volume,
-            ..Default::default()
-        },
-    );
+    let crash1: Handle<AudioSource> = asset_server.load("sounds/crash1.wav");
+    let crash2: Handle<AudioSource> = asset_server.load("sounds/crash2.wav");
+    let fence1: Handle<AudioSource> = asset_server.load("sounds/fence1.wav");
+    let fence2: Handle<AudioSource> = asset_server.load("sounds/fence2.wav");
 
     commands.spawn(SoundSamples {
-        moo: SoundSample::new(moo, Duration::from_secs(5), 1.0),
-        hay: SoundSample::new(hay, Duration::from_millis(500), 1.0),
-        engine_sound_sink: audio_sinks.get_handle(engine_sound_sink),
-        background_sound_sink: audio_sinks.get_handle(background_sound_sink),
+        moo: SoundSample::new(moo.clone(), Duration::from_secs(5), 1.0)
+            .with_variation(moo.clone(), 0.85)
+            .with_variation(moo, 1.15)
+            .on_bus(AudioBus::Ambient)
+            .with_priority(0.4),
+        hay: SoundSample::new(hay.clone(), Duration::from_millis(500), 1.0)
+            .with_variation(hay.clone(), 0.8)
+            .with_variation(hay, 1.2)
+            .with_priority(0.6),
+        engine_surge: SoundSample::new(engine_sample.clone(), Duration::from_secs(1), 0.6)
+            .with_speed(1.6)
+            .with_priority(0.8)
+            .with_pitch_variation(0.0),
+        crash: SoundSample::new(crash1.clone(), Duration::from_millis(300), 1.0)
+            .with_variation(crash2.clone(), 1.0)
+            .with_variation(crash1, 0.85)
+            .with_variation(crash2, 0.85),
+        fence: SoundSample::new(fence1, Duration::from_millis(300), 0.8)
+            .with_variation(fence2, 1.0)
+            .with_priority(0.7),
+        engine_loop: engine_sample,
     });
 }
 
 pub fn play_sample(
+    mut commands: Commands,
     mut sound_sample_events: EventReader<SoundSampleEvent>,
     mut sound_samples_query: Query<&mut SoundSamples>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+    mut spatial_audio: SpatialAudioPlayer,
+    mut voices: ResMut<Voices>,
     audio_sinks: Res<Assets<AudioSink>>,
-    settings: Res<Settings>,
+    time: Res<Time>,
 ) {
     let mut sound_samples = sound_samples_query.single_mut();
 
     for sound_sample_event in sound_sample_events.iter() {
-        match sound_sample_event {
-            SoundSampleEvent::Cow => {
-                sound_samples.moo.play(&time, &audio, settings.volume);
-            }
-            SoundSampleEvent::EnginePower => {
-                if let Some(engine) = audio_sinks.get(&sound_samples.engine_sound_sink) {
-                    engine.set_volume(ENGINE_NO_IDLE_VOULME * settings.volume);
-                }
-            }
-            SoundSampleEvent::NoEnginePower => {
-                if let Some(engine) = audio_sinks.get(&sound_samples.engine_sound_sink) {
-                    engine.set_volume(ENGINE_IDLE_VOLUME * settings.volume);
-                }
+        let sample = match sound_sample_event.sample {
+            SoundSampleKind::Cow => &mut sound_samples.moo,
+            SoundSampleKind::HayBale => &mut sound_samples.hay,
+            SoundSampleKind::EngineSurge => &mut sound_samples.engine_surge,
+            SoundSampleKind::Crash => &mut sound_samples.crash,
+            SoundSampleKind::Fence => &mut sound_samples.fence,
+        };
+        let volume = match sound_sample_event.force {
+            Some(force) => match impact_volume(force) {
+                Some(volume) => volume,
+                None => continue,
+            },
+            None => 1.0,
+        };
+        let playback = match sample.start(time.elapsed(), sound_sample_event.source, volume) {
+            Some(playback) => playback,
+            None => continue,
+        };
+        commands = voices.play_at(
+            commands,
+            &mut spatial_audio,
+            &audio_sinks,
+            playback,
+            sound_sample_event.position,
+        );
+    }
+}
+
+/// Gets the volume of an impact with a contact force, or `None` if it is too soft to hear.
+fn impact_volume(force: f32) -> Option<f32> {
+    if force < IMPACT_MIN_FORCE {
+        return None;
+    }
+    let strength = ((force - IMPACT_MIN_FORCE) / (IMPACT_FULL_FORCE - IMPACT_MIN_FORCE)).min(1.0);
+    Some(IMPACT_MIN_VOLUME + ((1.0 - IMPACT_MIN_VOLUME) * strength))
+}
+
+/// Marks a combine whose engine loops are playing. The idle layer fades into the rev layer as
+/// the engine speeds up and works harder.
+#[derive(Component)]
+pub struct EngineSound;
+
+/// Volume and playback speed of both engine layers.
+#[derive(Debug, PartialEq)]
+struct EngineLayerMix {
+    idle_volume: f32,
+    idle_speed: f32,
+    rev_volume: f32,
+    rev_speed: f32,
+}
+
+fn engine_layer_mix(rpm: f32, throttle: f32) -> EngineLayerMix {
+    let rev = ((rpm - IDLE_RPM) / (REDLINE_RPM - IDLE_RPM)).clamp(0.0, 1.0);
+    // an engine under load is louder than one revving freely.
+    let load = 0.5 + (0.5 * throttle.clamp(0.0, 1.0));
+
+    EngineLayerMix {
+        idle_volume: ENGINE_IDLE_VOLUME * (1.0 - (0.7 * rev)),
+        idle_speed: ENGINE_IDLE_SPEED.0 + ((ENGINE_IDLE_SPEED.1 - ENGINE_IDLE_SPEED.0) * rev),
+        rev_volume: ENGINE_REV_VOLUME * rev * load,
+        rev_speed: ENGINE_REV_SPEED.0 + ((ENGINE_REV_SPEED.1 - ENGINE_REV_SPEED.0) * rev),
+    }
+}
+
+/// Starts the engine loops for combines that don't have them yet.
+pub fn spawn_engine_sounds(
+    mut commands: Commands,
+    mut spatial_audio: SpatialAudioPlayer,
+    sound_samples_query: Query<&SoundSamples>,
+    combine_query: Query<Entity, (With<Engine>, Without<EngineSound>)>,
+) {
+    let sound_samples = match sound_samples_query.get_single() {
+        Ok(sound_samples) => sound_samples,
+        Err(_) => return,
+    };
+
+    for entity in combine_query.iter() {
+        let mut sinks = Vec::new();
+        for _ in [ENGINE_IDLE_LAYER, ENGINE_REV_LAYER] {
+            let sink = spatial_audio.play(
+                &sound_samples.engine_loop,
+                AudioBus::Engine,
+                PlaybackSettings {
+                    repeat: true,
+                    volume: 0.0,
+                    ..Default::default()
+                },
+            );
+            match sink {
+                Some(sink) => sinks.push(sink),
+                // the engine sample is still loading, try again next frame.
+                None => return,
             }
-            SoundSampleEvent::HayBale => {
-                sound_samples.hay.play(&time, &audio, settings.volume);
+        }
+        commands
+            .entity(entity)
+            .insert(SoundEmitter { sinks })
+            .insert(EngineSound);
+    }
+}
+
+pub fn engine_sound_system(
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut combine_query: Query<(&Engine, &mut SoundEmitter), With<EngineSound>>,
+) {
+    for (engine, mut emitter) in combine_query.iter_mut() {
+        let mix = engine_layer_mix(engine.rpm, engine.throttle);
+        let layers = [
+            (ENGINE_IDLE_LAYER, mix.idle_volume, mix.idle_speed),
+            (ENGINE_REV_LAYER, mix.rev_volume, mix.rev_speed),
+        ];
+        for (layer, volume, speed) in layers {
+            let spatial_sink = &mut emitter.sinks[layer];
+            spatial_sink.volume = volume;
+            if let Some(sink) = audio_sinks.get(&spatial_sink.sink) {
+                sink.set_speed(speed);
             }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use crate::{
+        engine::{IDLE_RPM, REDLINE_RPM},
+        sounds::{
+            engine_layer_mix, impact_volume, voice_to_steal, SoundSample, IMPACT_FULL_FORCE,
+            IMPACT_MIN_FORCE,
+        },
+    };
+
+    #[test]
+    fn idle_engine_mix_test() {
+        let mix = engine_layer_mix(IDLE_RPM, 0.0);
+
+        assert_eq!(0.0, mix.rev_volume);
+        assert!(mix.idle_volume > 0.0);
+    }
+
+    #[test]
+    fn revving_engine_mix_test() {
+        let idle = engine_layer_mix(IDLE_RPM, 1.0);
+        let coasting = engine_layer_mix(REDLINE_RPM, 0.0);
+        let pulling = engine_layer_mix(REDLINE_RPM, 1.0);
+
+        assert!(pulling.rev_volume > coasting.rev_volume);
+        assert!(pulling.idle_volume < idle.idle_volume);
+        assert!(pulling.rev_speed > idle.rev_speed);
+    }
+
+    #[test]
+    fn impact_volume_test() {
+        assert_eq!(None, impact_volume(IMPACT_MIN_FORCE - 1.0));
+        assert!((impact_volume(IMPACT_FULL_FORCE * 2.0).unwrap() - 1.0).abs() < 0.001);
+
+        let soft = impact_volume(IMPACT_MIN_FORCE * 2.0).unwrap();
+        let hard = impact_volume(IMPACT_FULL_FORCE * 0.5).unwrap();
+        assert!(soft < hard);
+    }
+
+    #[test]
+    fn cooldown_is_per_source_test() {
+        let mut sample = SoundSample::new(Handle::default(), Duration::from_secs(5), 1.0);
+        let cow = Entity::from_raw(1);
+        let other_cow = Entity::from_raw(2);
+
+        assert!(sample.start(Duration::from_secs(10), cow, 1.0).is_some());
+        assert!(sample.start(Duration::from_secs(11), cow, 1.0).is_none());
+        assert!(sample
+            .start(Duration::from_secs(11), other_cow, 1.0)
+            .is_some());
+        assert!(sample.start(Duration::from_secs(16), cow, 1.0).is_some());
+    }
+
+    #[test]
+    fn pitch_variation_test() {
+        let mut sample = SoundSample::new(Handle::default(), Duration::ZERO, 1.0)
+            .with_speed(2.0)
+            .with_pitch_variation(0.1);
+
+        for i in 0..20 {
+            let playback = sample
+                .start(Duration::from_secs(i), Entity::from_raw(1), 1.0)
+                .unwrap();
+            assert!(playback.settings.speed >= 1.8 && playback.settings.speed <= 2.2);
+        }
+    }
+
+    #[test]
+    fn steals_least_important_voice_test() {
+        let priorities = [0.8, 0.2, 0.5];
 
-    if let Some(background_sound) = audio_sinks.get(&sound_samples.background_sound_sink) {
-        background_sound.set_volume(BACKGROUND_SOUND_VOLUME * settings.volume);
+        assert_eq!(Some(1), voice_to_steal(&priorities, 0.6));
+        assert_eq!(None, voice_to_steal(&priorities, 0.1));
     }
 }
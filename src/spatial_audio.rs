@@ -0,0 +1,366 @@
+use std::{
+    f32::consts::{FRAC_PI_4, SQRT_2},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    audio::{AudioOutput, AudioSink, Source},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+};
+
+use crate::mixer::{AudioBus, AudioMixer};
+
+/// Distance within which sounds play at full volume.
+const REFERENCE_DISTANCE: f32 = 20.0;
+/// How quickly sounds fade beyond [`REFERENCE_DISTANCE`]. 1 halves the volume each time the
+/// distance doubles.
+const ROLLOFF: f32 = 1.0;
+/// How far a sound can be panned to one side. 1 would silence the other ear completely.
+const MAX_PAN: f32 = 0.8;
+/// How long one-shot sounds keep following their emitter before it is cleaned up.
+const ONE_SHOT_SECONDS: f32 = 5.0;
+
+/// Adds positional audio, played with [`SpatialAudio`] sources, heard from the active camera.
+pub struct SpatialAudioPlugin;
+
+impl Plugin for SpatialAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_non_send_resource::<AudioOutput<SpatialAudio>>()
+            .add_asset::<SpatialAudio>()
+            .init_resource::<Audio<SpatialAudio>>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                bevy::audio::play_queued_audio_system::<SpatialAudio>,
+            )
+            .add_system(spatial_audio_system)
+            .add_system(one_shot_sound_system);
+    }
+}
+
+/// Left and right channel gains, shared between the game and the audio thread.
+#[derive(Default)]
+pub struct Panning {
+    left: AtomicU32,
+    right: AtomicU32,
+    /// Set by the audio thread once the source has been played to the end.
+    finished: AtomicBool,
+}
+
+impl Panning {
+    fn centred() -> Panning {
+        let panning = Panning::default();
+        panning.set(1.0, 1.0);
+        panning
+    }
+
+    fn set(&self, left: f32, right: f32) {
+        self.left.store(left.to_bits(), Ordering::Relaxed);
+        self.right.store(right.to_bits(), Ordering::Relaxed);
+    }
+
+    fn gains(&self) -> (f32, f32) {
+        (
+            f32::from_bits(self.left.load(Ordering::Relaxed)),
+            f32::from_bits(self.right.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+/// An audio source played in stereo with its own panning.
+#[derive(TypeUuid)]
+#[uuid = "3a6f3c52-5a0e-4f0d-9b1e-6d1f8a0c2e47"]
+pub struct SpatialAudio {
+    source: AudioSource,
+    panning: Arc<Panning>,
+}
+
+impl Decodable for SpatialAudio {
+    type Decoder = PannedDecoder;
+    type DecoderItem = <AudioSource as Decodable>::DecoderItem;
+
+    fn decoder(&self) -> Self::Decoder {
+        PannedDecoder {
+            inner: self.source.decoder(),
+            panning: self.panning.clone(),
+            pending_right: None,
+        }
+    }
+}
+
+/// Decodes a mono or stereo source into stereo, scaling each channel by its panning gain.
+pub struct PannedDecoder {
+    inner: <AudioSource as Decodable>::Decoder,
+    panning: Arc<Panning>,
+    /// Right channel sample to return after the left one.
+    pending_right: Option<i16>,
+}
+
+impl Iterator for PannedDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let channels = self.inner.channels();
+        let left = match self.inner.next() {
+            Some(left) => left,
+            None => {
+                self.panning.finished.store(true, Ordering::Relaxed);
+                return None;
+            }
+        };
+        let right = if channels > 1 {
+            self.inner.next().unwrap_or(left)
+        } else {
+            left
+        };
+        // anything past stereo is dropped.
+        for _ in 2..channels {
+            self.inner.next();
+        }
+
+        let (left_gain, right_gain) = self.panning.gains();
+        self.pending_right = Some(scale_sample(right, right_gain));
+        Some(scale_sample(left, left_gain))
+    }
+}
+
+impl Source for PannedDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
+fn scale_sample(sample: i16, gain: f32) -> i16 {
+    (sample as f32 * gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// One playing sound belonging to a [`SoundEmitter`].
+#[derive(Clone)]
+pub struct SpatialSink {
+    pub sink: Handle<AudioSink>,
+    panning: Arc<Panning>,
+    /// Volume before distance and the mixer are applied.
+    pub volume: f32,
+    pub bus: AudioBus,
+}
+
+impl SpatialSink {
+    /// Whether the sound has played to the end. Repeating sounds finish once the first time
+    /// through has been buffered.
+    pub fn finished(&self) -> bool {
+        self.panning.finished.load(Ordering::Relaxed)
+    }
+}
+
+/// Something in the world making sounds. Its sounds get quieter and pan to the side as it moves
+/// away from the listener.
+#[derive(Component, Default)]
+pub struct SoundEmitter {
+    pub sinks: Vec<SpatialSink>,
+}
+
+/// A short lived emitter for a single sound.
+#[derive(Component)]
+pub struct OneShotSound {
+    lifetime: Timer,
+}
+
+/// Everything needed to start playing spatial sounds.
+#[derive(SystemParam)]
+pub struct SpatialAudioPlayer<'w, 's> {
+    audio_sources: Res<'w, Assets<AudioSource>>,
+    spatial_sources: ResMut<'w, Assets<SpatialAudio>>,
+    audio: Res<'w, Audio<SpatialAudio>>,
+    audio_sinks: Res<'w, Assets<AudioSink>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> SpatialAudioPlayer<'w, 's> {
+    /// Starts playing a sound. Returns `None` if the source hasn't loaded yet.
+    pub fn play(
+        &mut self,
+        source: &Handle<AudioSource>,
+        bus: AudioBus,
+        settings: PlaybackSettings,
+    ) -> Option<SpatialSink> {
+        let source = self.audio_sources.get(source)?.clone();
+        let panning = Arc::new(Panning::centred());
+        let spatial_source = self.spatial_sources.add(SpatialAudio {
+            source,
+            panning: panning.clone(),
+        });
+
+        let volume = settings.volume;
+        let sink = self.audio.play_with_settings(
+            spatial_source,
+            PlaybackSettings {
+                // the real volume is set once the listener position is known.
+                volume: 0.0,
+                ..settings
+            },
+        );
+
+        Some(SpatialSink {
+            sink: self.audio_sinks.get_handle(sink),
+            panning,
+            volume,
+            bus,
+        })
+    }
+}
+
+/// Spawns an emitter at a position for a sound that plays once.
+pub fn spawn_one_shot<'w, 's>(
+    mut commands: Commands<'w, 's>,
+    sink: SpatialSink,
+    position: Vec3,
+) -> Commands<'w, 's> {
+    commands
+        .spawn(TransformBundle::from(Transform::from_translation(position)))
+        .insert(SoundEmitter { sinks: vec![sink] })
+        .insert(OneShotSound {
+            lifetime: Timer::from_seconds(ONE_SHOT_SECONDS, TimerMode::Once),
+        });
+    commands
+}
+
+/// Gets how loud a sound is, and its left and right gains, heard by a listener.
+pub fn spatial_gains(listener: &GlobalTransform, position: Vec3) -> (f32, f32, f32) {
+    let offset = position - listener.translation();
+    let distance = offset.length();
+    let attenuation = (REFERENCE_DISTANCE / distance.max(REFERENCE_DISTANCE)).powf(ROLLOFF);
+
+    let local_offset = listener
+        .compute_transform()
+        .rotation
+        .inverse()
+        .mul_vec3(offset);
+    let pan = if distance > 0.001 {
+        (local_offset.x / distance).clamp(-1.0, 1.0) * MAX_PAN
+    } else {
+        0.0
+    };
+
+    // equal power panning, scaled so a centred sound plays at full volume in both ears.
+    let angle = (pan + 1.0) * FRAC_PI_4;
+    (attenuation, angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+}
+
+/// Updates the volume and panning of every emitter from the active camera.
+pub fn spatial_audio_system(
+    mixer: Res<AudioMixer>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    emitter_query: Query<(&GlobalTransform, &SoundEmitter)>,
+) {
+    let listener = camera_query
+        .iter()
+        .find(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| *transform);
+
+    for (transform, emitter) in emitter_query.iter() {
+        let (attenuation, left, right) = match listener {
+            Some(listener) => spatial_gains(&listener, transform.translation()),
+            None => (1.0, 1.0, 1.0),
+        };
+
+        for spatial_sink in emitter.sinks.iter() {
+            spatial_sink.panning.set(left, right);
+            if let Some(sink) = audio_sinks.get(&spatial_sink.sink) {
+                let bus_volume = mixer.output(spatial_sink.bus);
+                sink.set_volume(spatial_sink.volume * attenuation * bus_volume);
+            }
+        }
+    }
+}
+
+/// Stops every emitter's sounds, which would otherwise carry on after the emitter is despawned.
+pub fn stop_sound_emitters(
+    audio_sinks: Res<Assets<AudioSink>>,
+    emitter_query: Query<&SoundEmitter>,
+) {
+    for emitter in emitter_query.iter() {
+        for spatial_sink in emitter.sinks.iter() {
+            if let Some(sink) = audio_sinks.get(&spatial_sink.sink) {
+                sink.stop();
+            }
+        }
+    }
+}
+
+/// Cleans up one-shot emitters. The sound itself plays on to the end.
+pub fn one_shot_sound_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut one_shot_query: Query<(Entity, &mut OneShotSound)>,
+) {
+    for (entity, mut one_shot) in one_shot_query.iter_mut() {
+        one_shot.lifetime.tick(time.delta());
+        if one_shot.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::spatial_audio::spatial_gains;
+
+    #[test]
+    fn sound_to_the_right_pans_right_test() {
+        let listener = GlobalTransform::from(Transform::IDENTITY);
+
+        let (_, left, right) = spatial_gains(&listener, Vec3::new(10.0, 0.0, 0.0));
+
+        assert!(right > left);
+    }
+
+    #[test]
+    fn pan_follows_listener_rotation_test() {
+        // turned to face +X, a sound further along +X is straight ahead.
+        let listener = GlobalTransform::from(
+            Transform::IDENTITY.looking_at(Vec3::new(1.0, 0.0, 0.0), Vec3::Y),
+        );
+
+        let (_, left, right) = spatial_gains(&listener, Vec3::new(50.0, 0.0, 0.0));
+
+        assert!((left - right).abs() < 0.001);
+        assert!((left - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn distant_sounds_are_quieter_test() {
+        let listener = GlobalTransform::from(Transform::IDENTITY);
+
+        let (near, _, _) = spatial_gains(&listener, Vec3::new(0.0, 0.0, -10.0));
+        let (far, _, _) = spatial_gains(&listener, Vec3::new(0.0, 0.0, -80.0));
+
+        assert_eq!(1.0, near);
+        assert!((far - 0.25).abs() < 0.001);
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+
+use crate::{
+    combine::{Combine, CombineSpec, PLAYER_COMBINE_ID},
+    console::Console,
+    menu::AppState,
+    mixer::{mixer_panel_system, MixerPanel},
+};
+
+/// Where the tuning panel exports the spec of the combine being tuned.
+const COMBINE_SPEC_PATH: &str = "combine.spec";
+
+/// Adds the physics tuning panel, which edits the selected combine's spec while driving.
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TuningPanel>()
+            .add_startup_system(setup_tuning_panel)
+            .add_system_set(
+                SystemSet::on_update(AppState::Match)
+                    .with_system(tuning_panel_system.after(mixer_panel_system)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Match).with_system(close_tuning_panel));
+    }
+}
+
+/// A value in a [`CombineSpec`] that can be changed from the tuning panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tunable {
+    WheelFriction,
+    MotorFactor,
+    SteeringStiffness,
+    SteeringDamping,
+    BallastDensity,
+    CentreOfMassX,
+    CentreOfMassY,
+    CentreOfMassZ,
+}
+
+impl Tunable {
+    const ALL: [Tunable; 8] = [
+        Tunable::WheelFriction,
+        Tunable::MotorFactor,
+        Tunable::SteeringStiffness,
+        Tunable::SteeringDamping,
+        Tunable::BallastDensity,
+        Tunable::CentreOfMassX,
+        Tunable::CentreOfMassY,
+        Tunable::CentreOfMassZ,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Tunable::WheelFriction => "Wheel friction",
+            Tunable::MotorFactor => "Motor factor",
+            Tunable::SteeringStiffness => "Steering stiffness",
+            Tunable::SteeringDamping => "Steering damping",
+            Tunable::BallastDensity => "Ballast density",
+            Tunable::CentreOfMassX => "Centre of mass x",
+            Tunable::CentreOfMassY => "Centre of mass y",
+            Tunable::CentreOfMassZ => "Centre of mass z",
+        }
+    }
+
+    /// How much the value changes with each press.
+    fn step(&self) -> f32 {
+        match self {
+            Tunable::WheelFriction => 0.1,
+            Tunable::MotorFactor => 0.01,
+            Tunable::SteeringStiffness | Tunable::SteeringDamping => 0.1,
+            Tunable::BallastDensity => 50.0,
+            Tunable::CentreOfMassX | Tunable::CentreOfMassY | Tunable::CentreOfMassZ => 0.5,
+        }
+    }
+
+    fn value(&self, spec: &CombineSpec) -> f32 {
+        match self {
+            Tunable::WheelFriction => spec.wheel_friction,
+            Tunable::MotorFactor => spec.motor_factor,
+            Tunable::SteeringStiffness => spec.steering_stiffness,
+            Tunable::SteeringDamping => spec.steering_damping,
+            Tunable::BallastDensity => spec.ballast_density,
+            Tunable::CentreOfMassX => spec.centre_of_mass.x,
+            Tunable::CentreOfMassY => spec.centre_of_mass.y,
+            Tunable::CentreOfMassZ => spec.centre_of_mass.z,
+        }
+    }
+
+    /// Moves the value by a number of steps. Only the centre of mass can go below zero.
+    fn adjust(&self, spec: &mut CombineSpec, steps: f32) {
+        let value = self.value(spec) + (self.step() * steps);
+        let positive = value.max(0.0);
+        match self {
+            Tunable::WheelFriction => spec.wheel_friction = positive,
+            Tunable::MotorFactor => spec.motor_factor = positive,
+            Tunable::SteeringStiffness => spec.steering_stiffness = positive,
+            Tunable::SteeringDamping => spec.steering_damping = positive,
+            Tunable::BallastDensity => spec.ballast_density = positive,
+            Tunable::CentreOfMassX => spec.centre_of_mass.x = value,
+            Tunable::CentreOfMassY => spec.centre_of_mass.y = value,
+            Tunable::CentreOfMassZ => spec.centre_of_mass.z = value,
+        }
+    }
+}
+
+/// Which combine and value are selected in the tuning panel, and whether it is showing.
+#[derive(Resource)]
+pub struct TuningPanel {
+    pub open: bool,
+    combine_id: i32,
+    selected: usize,
+    /// What happened the last time the spec was exported.
+    status: String,
+}
+
+impl Default for TuningPanel {
+    fn default() -> Self {
+        TuningPanel {
+            open: false,
+            combine_id: PLAYER_COMBINE_ID,
+            selected: 0,
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TuningUi;
+
+#[derive(Component)]
+pub struct TuningText;
+
+pub fn setup_tuning_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(60.0),
+                    left: Val::Percent(30.0),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(TuningUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: mono_font,
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(TuningText);
+        });
+}
+
+/// Hides the panel when the match ends, as it only works during a match.
+fn close_tuning_panel(
+    mut panel: ResMut<TuningPanel>,
+    mut panel_query: Query<&mut Visibility, With<TuningUi>>,
+) {
+    panel.open = false;
+    for mut visibility in panel_query.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
+
+/// While the panel is open, arrow keys pick a value and change it, Page Up and Page Down pick the
+/// combine and Enter exports its spec. F2 opens and closes it. Only one of the tuning and mixer
+/// panels is open at a time, as both use the arrow keys.
+pub fn tuning_panel_system(
+    keys: Res<Input<KeyCode>>,
+    console: Res<Console>,
+    mut panel: ResMut<TuningPanel>,
+    mut mixer_panel: ResMut<MixerPanel>,
+    mut combine_query: Query<(&Combine, &mut CombineSpec)>,
+    mut panel_query: Query<&mut Visibility, With<TuningUi>>,
+    mut text_query: Query<&mut Text, With<TuningText>>,
+) {
+    if keys.just_pressed(KeyCode::F2) {
+        panel.open = !panel.open;
+        if panel.open {
+            mixer_panel.open = false;
+        }
+    } else if mixer_panel.open {
+        panel.open = false;
+    }
+    for mut visibility in panel_query.iter_mut() {
+        visibility.is_visible = panel.open;
+    }
+    // the arrow keys go through the console's history while it is open.
+    if !panel.open || console.open {
+        return;
+    }
+
+    let mut combine_ids: Vec<i32> = combine_query
+        .iter()
+        .map(|(combine, _)| combine.combine_id)
+        .collect();
+    combine_ids.sort();
+    if combine_ids.is_empty() {
+        return;
+    }
+    let current = combine_ids
+        .iter()
+        .position(|combine_id| *combine_id == panel.combine_id);
+    let combine_count = combine_ids.len();
+    if keys.just_pressed(KeyCode::PageUp) {
+        let index = current.map_or(0, |index| (index + combine_count - 1) % combine_count);
+        panel.combine_id = combine_ids[index];
+    } else if keys.just_pressed(KeyCode::PageDown) {
+        let index = current.map_or(0, |index| (index + 1) % combine_count);
+        panel.combine_id = combine_ids[index];
+    } else if current.is_none() {
+        panel.combine_id = combine_ids[0];
+    }
+
+    let tunable_count = Tunable::ALL.len();
+    if keys.just_pressed(KeyCode::Up) {
+        panel.selected = (panel.selected + tunable_count - 1) % tunable_count;
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        panel.selected = (panel.selected + 1) % tunable_count;
+    }
+
+    let spec = combine_query
+        .iter_mut()
+        .find(|(combine, _)| combine.combine_id == panel.combine_id)
+        .map(|(_, spec)| spec);
+    let mut spec = match spec {
+        Some(spec) => spec,
+        None => return,
+    };
+    let tunable = Tunable::ALL[panel.selected];
+    if keys.just_pressed(KeyCode::Left) {
+        tunable.adjust(&mut spec, -1.0);
+    }
+    if keys.just_pressed(KeyCode::Right) {
+        tunable.adjust(&mut spec, 1.0);
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        panel.status = match spec.save(COMBINE_SPEC_PATH) {
+            Ok(()) => format!("Exported to {}", COMBINE_SPEC_PATH),
+            Err(error) => error,
+        };
+    }
+
+    let mut value = format!("Tuning combine {} (F2 to close)\n", panel.combine_id);
+    for (index, tunable) in Tunable::ALL.iter().enumerate() {
+        value.push_str(&format!(
+            "{} {:<19}{:>8.2}\n",
+            if index == panel.selected { ">" } else { " " },
+            tunable.name(),
+            tunable.value(&spec)
+        ));
+    }
+    value.push_str("Up/Down select, Left/Right change\nPgUp/PgDn combine, Enter export");
+    if !panel.status.is_empty() {
+        value.push_str(&format!("\n{}", panel.status));
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{combine::CombineSpec, tuning::Tunable};
+
+    #[test]
+    fn adjust_tunable_test() {
+        let mut spec = CombineSpec::default();
+        let friction = spec.wheel_friction;
+
+        Tunable::WheelFriction.adjust(&mut spec, 2.0);
+        assert!((spec.wheel_friction - (friction + 0.2)).abs() < 0.001);
+        assert_eq!(Tunable::WheelFriction.value(&spec), spec.wheel_friction);
+    }
+
+    #[test]
+    fn only_centre_of_mass_goes_negative_test() {
+        let mut spec = CombineSpec::default();
+        for tunable in Tunable::ALL {
+            tunable.adjust(&mut spec, -10000.0);
+        }
+
+        assert_eq!(0.0, spec.wheel_friction);
+        assert_eq!(0.0, spec.motor_factor);
+        assert_eq!(0.0, spec.ballast_density);
+        assert!(spec.centre_of_mass.max_element() < 0.0);
+    }
+}
@@ -8,8 +8,58 @@ use crate::{
     camera,
     combine::{Combine, PLAYER_COMBINE_ID},
     config,
+    engine::{Engine, REDLINE_RPM},
+    gauge::{spawn_gauge, Gauge, GaugeSpec},
+    layout::ArenaLayout,
 };
 
+const GAUGE_SIZE: f32 = 170.0;
+const TACHOMETER_MAX_RPM: f32 = 3000.0;
+
+/// Units the speedometer reads in.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpeedUnit {
+    MetresPerSecond,
+    #[default]
+    KilometresPerHour,
+    MilesPerHour,
+}
+
+impl SpeedUnit {
+    pub const ALL: [SpeedUnit; 3] = [
+        SpeedUnit::MetresPerSecond,
+        SpeedUnit::KilometresPerHour,
+        SpeedUnit::MilesPerHour,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpeedUnit::MetresPerSecond => "m/s",
+            SpeedUnit::KilometresPerHour => "km/h",
+            SpeedUnit::MilesPerHour => "mph",
+        }
+    }
+
+    /// Converts a speed in metres per second to this unit.
+    fn from_metres_per_second(&self, speed: f32) -> f32 {
+        match self {
+            SpeedUnit::MetresPerSecond => speed,
+            SpeedUnit::KilometresPerHour => speed * 3.6,
+            SpeedUnit::MilesPerHour => speed * 2.236_936,
+        }
+    }
+
+    /// Gets the top of the speedometer dial and the gap between its numbers. Each unit tops out
+    /// a little above a boosting combine's top speed.
+    fn dial(&self) -> (f32, f32) {
+        match self {
+            SpeedUnit::MetresPerSecond => (70.0, 10.0),
+            SpeedUnit::KilometresPerHour => (250.0, 50.0),
+            SpeedUnit::MilesPerHour => (160.0, 20.0),
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct DebugInfo {
     pub enabled: bool,
@@ -19,7 +69,17 @@ pub struct DebugInfo {
 pub struct TextChanges;
 
 #[derive(Component)]
-pub struct SpeedoUi;
+pub struct GearUi;
+
+#[derive(Component)]
+pub struct Speedometer;
+
+/// Holds the speedometer, which is replaced whenever the speed unit changes.
+#[derive(Component)]
+pub struct SpeedometerSlot;
+
+#[derive(Component)]
+pub struct Tachometer;
 
 #[derive(Component)]
 pub struct DescriptionUi;
@@ -83,7 +143,7 @@ pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
             },
             ..default()
         })
-        .insert(SpeedoUi);
+        .insert(GearUi);
 
     commands
         .spawn(TextBundle {
@@ -128,7 +188,7 @@ pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
                 sections: vec![TextSection {
                     value: "".to_string(),
                     style: TextStyle {
-                        font: mono_font,
+                        font: mono_font.clone(),
                         font_size: 18.0,
                         color: Color::WHITE,
                     },
@@ -138,6 +198,44 @@ pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..default()
         })
         .insert(DebugUi);
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    background_color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(SpeedometerSlot);
+            spawn_gauge(
+                parent,
+                mono_font,
+                GaugeSpec {
+                    title: "rpm x1000".to_string(),
+                    unit: "rpm".to_string(),
+                    max: TACHOMETER_MAX_RPM,
+                    step: 500.0,
+                    label_divisor: 1000.0,
+                    red_from: Some(REDLINE_RPM),
+                    size: GAUGE_SIZE,
+                },
+                Tachometer,
+            );
+        });
 }
 
 pub fn change_text_system(
@@ -155,12 +253,21 @@ pub fn change_text_system(
                     fps = fps_avg;
                 }
             }
+            let mut frame_time = 0.0;
+            if let Some(frame_time_diagnostic) =
+                diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+            {
+                if let Some(frame_time_avg) = frame_time_diagnostic.average() {
+                    frame_time = frame_time_avg;
+                }
+            }
 
             text.sections[0].value = format!(
-                "Build {}, {} {:.1} fps",
+                "Build {}, {} {:.1} fps ({:.2} ms)",
                 config::GIT_VERSION,
                 config::BUILD_DATE,
-                fps
+                fps,
+                frame_time
             );
         } else {
             text.sections[0].value = String::new();
@@ -168,28 +275,88 @@ pub fn change_text_system(
     }
 }
 
+/// Shows the player's speed and engine speed on the gauges, and which gear they are in.
+#[allow(clippy::type_complexity)]
 pub fn combine_ui_system(
-    combine_query: Query<&Combine>,
-    mut text_query: Query<&mut Text, With<SpeedoUi>>,
+    speed_unit: Res<SpeedUnit>,
+    combine_query: Query<(&Combine, &Engine)>,
+    mut text_query: Query<&mut Text, With<GearUi>>,
+    mut speedometer_query: Query<&mut Gauge, (With<Speedometer>, Without<Tachometer>)>,
+    mut tachometer_query: Query<&mut Gauge, (With<Tachometer>, Without<Speedometer>)>,
 ) {
     let mut text = text_query.single_mut();
 
-    for combine in combine_query.iter() {
+    for (combine, engine) in combine_query.iter() {
         if combine.combine_id == PLAYER_COMBINE_ID {
-            text.sections[0].value = format!("{:.0} m/s", combine.velocity);
+            let gear = if engine.reversing {
+                "R".to_string()
+            } else {
+                engine.gear.to_string()
+            };
+            text.sections[0].value = format!(
+                "Gear {}{}",
+                gear,
+                if engine.automatic { " (auto)" } else { "" }
+            );
+
+            for mut gauge in speedometer_query.iter_mut() {
+                gauge.value = speed_unit.from_metres_per_second(combine.velocity);
+            }
+            for mut gauge in tachometer_query.iter_mut() {
+                gauge.value = engine.rpm;
+            }
         }
     }
 }
 
+/// Puts in a speedometer marked out for the speed unit, replacing the last one when the unit
+/// changes.
+pub fn speedometer_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    speed_unit: Res<SpeedUnit>,
+    slot_query: Query<(Entity, Option<&Children>), With<SpeedometerSlot>>,
+) {
+    for (slot, children) in slot_query.iter() {
+        if children.is_some() && !speed_unit.is_changed() {
+            continue;
+        }
+        let (max, step) = speed_unit.dial();
+        commands.entity(slot).despawn_descendants();
+        commands.entity(slot).with_children(|parent| {
+            spawn_gauge(
+                parent,
+                asset_server.load("fonts/FiraMono-Regular.ttf"),
+                GaugeSpec {
+                    title: "Speed".to_string(),
+                    unit: speed_unit.name().to_string(),
+                    max,
+                    step,
+                    label_divisor: 1.0,
+                    red_from: None,
+                    size: GAUGE_SIZE,
+                },
+                Speedometer,
+            );
+        });
+    }
+}
+
 pub fn update_debug_ui_system(
     windows: Res<Windows>,
+    arena_layout: Res<ArenaLayout>,
     mut query: Query<&mut Text, With<DebugUi>>,
     mut style_query: Query<&mut Style, With<DebugUi>>,
 ) {
     for mut text in query.iter_mut() {
         let resolution = camera::get_primary_window_size(&windows);
 
-        text.sections[0].value = format!("Resolution:{}, {}", resolution.x, resolution.y);
+        text.sections[0].value = format!(
+            "Resolution:{}, {}\n{}",
+            resolution.x,
+            resolution.y,
+            arena_layout.description()
+        );
     }
     for mut text in style_query.iter_mut() {
         text.position = UiRect {
@@ -199,3 +366,35 @@ pub fn update_debug_ui_system(
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ui::SpeedUnit;
+
+    #[test]
+    fn speed_units_test() {
+        let speed = 10.0;
+        assert_eq!(
+            10.0,
+            SpeedUnit::MetresPerSecond.from_metres_per_second(speed)
+        );
+        assert!((SpeedUnit::KilometresPerHour.from_metres_per_second(speed) - 36.0).abs() < 0.001);
+        assert!((SpeedUnit::MilesPerHour.from_metres_per_second(speed) - 22.369).abs() < 0.001);
+    }
+
+    #[test]
+    fn speedometer_dials_match_test() {
+        // every unit's dial should reach about the same speed.
+        let tops: Vec<f32> = SpeedUnit::ALL
+            .iter()
+            .map(|unit| unit.dial().0 / unit.from_metres_per_second(1.0))
+            .collect();
+        for top in tops.iter() {
+            assert!((top - tops[0]).abs() < 5.0);
+        }
+        for unit in SpeedUnit::ALL {
+            let (max, step) = unit.dial();
+            assert_eq!(0.0, max % step);
+        }
+    }
+}